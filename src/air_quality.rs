@@ -0,0 +1,76 @@
+use crate::{
+    i18n::Lang,
+    report::{Report, ReportSection, SectionRepr},
+};
+use serde::Serialize;
+
+/// Air quality measurements normalized across providers
+/// Pollutant concentrations are in µg/m³
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AirQuality {
+    pub aqi: Option<f64>,
+    pub category: Option<String>,
+    pub us_epa_index: Option<u8>,
+    pub gb_defra_index: Option<u8>,
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    pub o3: Option<f64>,
+    pub no2: Option<f64>,
+}
+
+/// Category name of a US EPA index, as used by WeatherAPI (1 to 6)
+pub fn us_epa_category(index: u8) -> Option<&'static str> {
+    match index {
+        1 => Some("Good"),
+        2 => Some("Moderate"),
+        3 => Some("Unhealthy for sensitive groups"),
+        4 => Some("Unhealthy"),
+        5 => Some("Very unhealthy"),
+        6 => Some("Hazardous"),
+        _ => None,
+    }
+}
+
+/// Band name of a UK DEFRA Daily Air Quality Index (1 to 10)
+pub fn gb_defra_band(index: u8) -> Option<&'static str> {
+    match index {
+        1..=3 => Some("Low"),
+        4..=6 => Some("Moderate"),
+        7..=9 => Some("High"),
+        10 => Some("Very high"),
+        _ => None,
+    }
+}
+
+/// Appends an air quality section to a report, missing measurements are skipped
+pub fn add_to_report(report: &mut Report, air_quality: AirQuality, lang: Lang) {
+    let tr = |text| lang.tr(text).to_owned();
+    let mut details = SectionRepr::new();
+    if let Some(category) = air_quality.category {
+        details.push((tr("Category"), category));
+    }
+    if let Some(aqi) = air_quality.aqi {
+        details.push(("AQI".to_owned(), format!("{}", aqi)));
+    }
+    if let Some(index) = air_quality.us_epa_index {
+        let band = us_epa_category(index).unwrap_or("-");
+        details.push((tr("US EPA index"), format!("{} ({})", index, band)));
+    }
+    if let Some(index) = air_quality.gb_defra_index {
+        let band = gb_defra_band(index).unwrap_or("-");
+        details.push((tr("UK DEFRA index"), format!("{} ({})", index, band)));
+    }
+    [
+        ("PM2.5, µg/m³", air_quality.pm2_5),
+        ("PM10, µg/m³", air_quality.pm10),
+        ("O3, µg/m³", air_quality.o3),
+        ("NO2, µg/m³", air_quality.no2),
+    ]
+    .into_iter()
+    .for_each(|(label, value)| {
+        if let Some(value) = value {
+            details.push((label.to_owned(), format!("{:.1}", value)));
+        }
+    });
+    report.add_section(ReportSection::new(tr("Air quality"), details));
+}
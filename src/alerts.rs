@@ -0,0 +1,153 @@
+use crate::{
+    report::{Report, ReportSection, SectionRepr},
+    types::Clock,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+
+/// Alert severity that every provider's rating is normalized to
+/// Variants are ordered from the least to the most severe one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Severity {
+    Unknown,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+impl Severity {
+    /// Map a CAP (Common Alerting Protocol) severity name, unknown names are kept as `Unknown`
+    pub fn from_cap<S: AsRef<str>>(severity: S) -> Self {
+        match severity.as_ref().trim().to_ascii_lowercase().as_str() {
+            "minor" => Severity::Minor,
+            "moderate" => Severity::Moderate,
+            "severe" => Severity::Severe,
+            "extreme" => Severity::Extreme,
+            _ => Severity::Unknown,
+        }
+    }
+}
+
+/// A weather alert or warning, as issued for a location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub severity: Severity,
+    pub event: String,
+    pub area: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub description: String,
+}
+
+/// Alerts normalized across providers
+/// The offset of the location from UTC is kept when a provider tells it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    #[serde(skip)]
+    pub utc_offset: Option<FixedOffset>,
+}
+
+/// Order alerts from the most severe one, alerts of the same severity start with the earliest one
+pub fn sort_by_severity(alerts: &mut [Alert]) {
+    alerts.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.start.cmp(&b.start)));
+}
+
+fn format_time(time: Option<DateTime<Utc>>, clock: &Clock) -> String {
+    time.map(|t| clock.format(t))
+        .unwrap_or_else(|| "-".to_owned())
+}
+
+/// Appends a section per alert to a report, the most severe alerts go first
+/// Times are shown on the location's clock when the alerts carry its offset
+pub fn add_to_report(report: &mut Report, alerts: Alerts, clock: &Clock) {
+    let clock = &Clock::new(alerts.utc_offset.or(clock.location), clock.tz).with_lang(clock.lang);
+    let mut alerts = alerts.alerts;
+    sort_by_severity(&mut alerts);
+    let tr = |text| clock.lang.tr(text).to_owned();
+    alerts.into_iter().for_each(|alert| {
+        let mut details = SectionRepr::new();
+        details.push((tr("Severity"), format!("{:?}", alert.severity)));
+        if let Some(area) = alert.area {
+            details.push((tr("Area"), area));
+        }
+        details.push((tr("Start"), format_time(alert.start, clock)));
+        details.push((tr("End"), format_time(alert.end, clock)));
+        details.push((tr("Description"), alert.description));
+        let rs = ReportSection::new(format!("{} - {}", tr("Alert"), alert.event), details);
+        report.add_section(rs);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i18n::Lang, types::TimeZoneChoice};
+    use chrono::TimeZone;
+
+    fn alert(severity: Severity, event: &str, start: i64) -> Alert {
+        Alert {
+            severity,
+            event: event.to_owned(),
+            area: None,
+            start: Utc.timestamp_opt(start, 0).single(),
+            end: None,
+            description: "".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sort_by_severity() {
+        let mut alerts = vec![
+            alert(Severity::Minor, "Fog", 0),
+            alert(Severity::Extreme, "Tornado", 10),
+            alert(Severity::Severe, "Flood", 20),
+            alert(Severity::Severe, "Wind", 5),
+        ];
+        sort_by_severity(&mut alerts);
+        let events: Vec<&str> = alerts.iter().map(|a| a.event.as_str()).collect();
+        assert_eq!(events, vec!["Tornado", "Wind", "Flood", "Fog"]);
+    }
+
+    #[test]
+    fn test_location_clock() {
+        let alerts = Alerts {
+            alerts: vec![alert(Severity::Severe, "Flood", 1680285600)],
+            utc_offset: FixedOffset::west_opt(5 * 3600),
+        };
+        let mut report = Report::new("Alerts");
+        add_to_report(
+            &mut report,
+            alerts,
+            &Clock::new(None, TimeZoneChoice::Location),
+        );
+        assert!(report.sections()[0].contents().contains(&(
+            "Start".to_owned(),
+            "31/03/2023 01:00 PM (UTC-05:00)".to_owned()
+        )));
+    }
+
+    #[test]
+    fn test_lang() {
+        let alerts = Alerts {
+            alerts: vec![alert(Severity::Severe, "Flood", 1680285600)],
+            utc_offset: FixedOffset::west_opt(5 * 3600),
+        };
+        let mut report = Report::new("Alerts");
+        let clock = Clock::new(None, TimeZoneChoice::Location).with_lang(Lang::De);
+        add_to_report(&mut report, alerts, &clock);
+        assert_eq!(report.sections()[0].title(), "Warnung - Flood");
+        assert!(report.sections()[0].contents().contains(&(
+            "Beginn".to_owned(),
+            "31.03.2023 13:00 (UTC-05:00)".to_owned()
+        )));
+    }
+
+    #[test]
+    fn test_severity_from_cap() {
+        assert_eq!(Severity::from_cap("Severe"), Severity::Severe);
+        assert_eq!(Severity::from_cap(" extreme "), Severity::Extreme);
+        assert_eq!(Severity::from_cap(""), Severity::Unknown);
+    }
+}
@@ -1,22 +1,240 @@
-use crate::types::AvailableProviders;
+use crate::{
+    conditions::Field,
+    export::Output,
+    i18n::Lang,
+    notify,
+    terminal::Icons,
+    types::{AvailableProviders, TimeZoneChoice, Units},
+    util, watch,
+};
+use chrono::NaiveTime;
 use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 #[derive(Parser, Debug)]
 pub struct GetArgs {
-    /// A location to look up the weather conditions for
-    pub location: String,
+    /// A location to look up the weather conditions for, defaults to the first location of the profile
+    pub location: Option<String>,
     /// An optional time offset in the form of
     pub date: Option<String>,
+    /// First day of a range, a `YYYY-MM-DD` date or a day offset, e.g. `h7d`; defaults to today
+    #[arg(long, conflicts_with = "date")]
+    pub from: Option<String>,
+    /// Last day of a range, a `YYYY-MM-DD` date or a day offset, e.g. `f3d`; defaults to today
+    #[arg(long, conflicts_with = "date")]
+    pub to: Option<String>,
+    /// Append current air quality to the report
+    #[arg(long)]
+    pub aqi: bool,
+    /// Append sunrise, sunset and moon data for the day to the report
+    #[arg(long)]
+    pub astro: bool,
+    /// Comma-separated fields to show, e.g. `temperature,wind,pressure`; all available fields by default
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<Field>,
+    /// Glyphs shown next to conditions on a terminal
+    #[arg(long, value_enum, default_value = "unicode")]
+    pub icons: Icons,
+    /// Print the plain table even on a terminal
+    #[arg(long)]
+    pub plain: bool,
+    /// Render the report with a template from the `templates` directory next to the configuration,
+    /// or a built-in one such as `table`
+    #[arg(long, conflicts_with_all = ["plain", "format", "waybar"])]
+    pub template: Option<String>,
+    /// Export the report as a document instead of a table
+    #[arg(long, value_enum, conflicts_with_all = ["plain", "template", "format", "waybar"])]
+    pub output: Option<Output>,
+    /// Write the report to a file rather than to the terminal
+    #[arg(long, conflicts_with_all = ["format", "waybar"])]
+    pub out_file: Option<PathBuf>,
+    /// Print one line for status bars from a template, e.g. `{icon} {temp}°{unit} {condition}`
+    #[arg(long, conflicts_with_all = ["aqi", "astro", "plain"])]
+    pub format: Option<String>,
+    /// Print waybar JSON with the line as `text` and the report as `tooltip`
+    #[arg(long, conflicts_with_all = ["aqi", "astro", "plain"])]
+    pub waybar: bool,
+    /// How long a one-line result is reused for, e.g. `90s` or `10m`
+    #[arg(long, default_value = "5m", value_parser = watch::parse_interval)]
+    pub max_age: Duration,
+}
+
+#[derive(Parser, Debug)]
+pub struct BatchArgs {
+    /// CSV file of sites with a header: `name`, `location` and optionally `date` and `provider`
+    #[arg(long)]
+    pub input: PathBuf,
+    /// Write the results as JSON instead of CSV, the default for `--out-file` ending in `.json`
+    #[arg(long)]
+    pub json: bool,
+    /// Write the results to a file rather than to the terminal
+    #[arg(long)]
+    pub out_file: Option<PathBuf>,
+    /// Most sites queried at the same time
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+    /// Most requests sent to each provider per minute
+    #[arg(long, default_value_t = 60)]
+    pub rate: u32,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileAction {
+    /// List the available profiles
+    List,
+    /// Create a new empty profile
+    Create {
+        name: String,
+        /// Measurement system used in reports
+        #[arg(short, long)]
+        units: Option<Units>,
+        /// Language of reports
+        #[arg(long, value_enum)]
+        lang: Option<Lang>,
+        /// Saved location, can be repeated
+        #[arg(short, long = "location")]
+        locations: Vec<String>,
+    },
+    /// Copy an existing profile under a new name
+    Copy { from: String, to: String },
+    /// Delete a profile
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RuleAction {
+    /// Add a rule for a location, e.g. `weather notify add Kyiv "wind_kph > 50"`
+    Add { location: String, rule: String },
+    /// Remove a rule of a location
+    Remove { location: String, rule: String },
+    /// List the rules of every location
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Open the configuration file in $EDITOR, it is only written back if it stays valid
+    Edit,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Action {
     /// Get weather status for a given location
     Get(GetArgs),
+    /// List active weather alerts and warnings for a location, the most severe ones first
+    Alerts {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+    },
+    /// Show current air quality for a location
+    Air {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+    },
+    /// Show sunrise, sunset and moon data for a location
+    Astro {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+        /// A `YYYY-MM-DD` date or a day offset, e.g. `f1d`, defaults to today
+        date: Option<String>,
+    },
+    /// Tell whether it will rain today, exits with 0 when it will, with 1 when it won't
+    /// and with 2 when it couldn't be told, e.g. because of a network or configuration failure
+    Rain {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+        /// Start of the time window on the location's clock, `HH:MM`; the current hour by default
+        #[arg(long, value_parser = util::parse_time_arg)]
+        from: Option<NaiveTime>,
+        /// End of the time window on the location's clock, `HH:MM`, exclusive
+        #[arg(long, default_value = "23:59", value_parser = util::parse_time_arg)]
+        to: NaiveTime,
+    },
+    /// Summarize the daily history of a range: temperatures, precipitation and the hottest and coldest days
+    Stats {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+        /// First day, a `YYYY-MM-DD` date or a day offset, e.g. `h30d`
+        #[arg(long)]
+        from: String,
+        /// Last day, a `YYYY-MM-DD` date or a day offset; has to be before today
+        #[arg(long)]
+        to: String,
+        /// Print the summary as CSV instead of a report
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Query every site of a CSV file and write the results with a status per site
+    Batch(BatchArgs),
+    /// Refresh a report periodically, redrawing it in place and highlighting changed values
+    Watch {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+        /// An optional time offset, as for `get`
+        date: Option<String>,
+        /// Time between refreshes, e.g. `90s`, `10m` or `1h`
+        #[arg(long, default_value = "10m", value_parser = watch::parse_interval)]
+        interval: Duration,
+        /// Comma-separated fields to show, all available fields by default
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<Field>,
+    },
+    /// Evaluate notification rules against the latest forecast, every event is only notified about once
+    Notify {
+        #[command(subcommand)]
+        action: Option<RuleAction>,
+        /// Where notifications are delivered to
+        #[arg(long, value_enum, default_value = "stdout")]
+        output: notify::Output,
+        /// Shell command run per notification for the exec output, it gets `WEATHER_*` variables
+        #[arg(long, required_if_eq("output", "exec"))]
+        command: Option<String>,
+    },
+    /// Serve reports as JSON over HTTP with the configured provider, until interrupted
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: SocketAddr,
+    },
+    /// Poll locations on a schedule and serve their conditions as Prometheus metrics at `/metrics`
+    Exporter {
+        /// Locations to poll, the profile's saved locations by default
+        locations: Vec<String>,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:9090")]
+        bind: SocketAddr,
+        /// Time between polls, e.g. `90s`, `10m` or `1h`
+        #[arg(long, default_value = "5m", value_parser = watch::parse_interval)]
+        interval: Duration,
+    },
+    /// Show wave height, swell, water temperature and tides near a location
+    Marine {
+        /// Defaults to the first location of the profile
+        location: Option<String>,
+    },
+    /// Show what each provider can be asked for
+    Providers,
+    /// Set the order configured providers are preferred in, when several can serve a request
+    Prefer {
+        #[arg(required = true)]
+        providers: Vec<AvailableProviders>,
+    },
     /// Interactive Configuration of a weather provider
-    Configure { provider: AvailableProviders },
+    Configure {
+        provider: AvailableProviders,
+        /// Days of history your WeatherAPI plan covers, e.g. 365 on paid plans, 7 by default
+        #[arg(long)]
+        history_days: Option<u32>,
+    },
     /// Set the default provider to be used later
     Default { provider: AvailableProviders },
+    /// Manage configuration profiles
+    #[command(subcommand)]
+    Profile(ProfileAction),
+    /// Manage the configuration file
+    #[command(subcommand)]
+    Config(ConfigAction),
 }
 
 #[derive(Parser, Debug)]
@@ -27,4 +245,16 @@ pub struct Args {
     /// Specify a path for a configuration file
     #[arg(short, long)]
     pub config_path: Option<PathBuf>,
+    /// Select a configuration profile
+    #[arg(short, long, env = "WEATHER_PROFILE", global = true)]
+    pub profile: Option<String>,
+    /// Clock timestamps are shown on: the location's, the user's local one or UTC
+    #[arg(long, value_enum, default_value = "location", global = true)]
+    pub tz: TimeZoneChoice,
+    /// Language of report labels, dates and condition texts, the profile's one by default
+    #[arg(long, value_enum, global = true)]
+    pub lang: Option<Lang>,
+    /// Show which provider serves a request and why
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
 }
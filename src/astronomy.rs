@@ -0,0 +1,213 @@
+use crate::{
+    i18n::Lang,
+    report::{Report, ReportSection, SectionRepr},
+};
+use chrono::{Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+
+/// Mean length of a lunar cycle in days
+const SYNODIC_MONTH: f64 = 29.530588853;
+/// A new moon to count lunar cycles from, 2000-01-06 18:14 UTC
+const NEW_MOON_EPOCH: i64 = 947182440;
+/// Julian day of the Unix epoch
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+/// Julian day of the J2000 epoch
+const J2000_JD: f64 = 2451545.0;
+
+/// Sun and moon data for a day, times are local to the location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Astronomy {
+    pub date: NaiveDate,
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub moonrise: Option<NaiveTime>,
+    pub moonset: Option<NaiveTime>,
+    pub moon_phase: Option<String>,
+    /// Illuminated fraction of the moon in percent
+    pub moon_illumination: Option<f64>,
+}
+
+impl Astronomy {
+    /// Time between sunrise and sunset, unknown when the sun doesn't rise or set that day
+    pub fn day_length(&self) -> Option<Duration> {
+        match (self.sunrise, self.sunset) {
+            (Some(sunrise), Some(sunset)) if sunset > sunrise => Some(sunset - sunrise),
+            _ => None,
+        }
+    }
+}
+
+fn julian_day_to_local(jd: f64, utc_offset: FixedOffset) -> Option<NaiveTime> {
+    let timestamp = ((jd - UNIX_EPOCH_JD) * 86400.0).round() as i64;
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.with_timezone(&utc_offset).time())
+}
+
+/// Sunrise and sunset following the sunrise equation, as used by NOAA
+/// Longitude is positive to the east, `None` is returned for polar days and nights
+fn sun_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    utc_offset: FixedOffset,
+) -> (Option<NaiveTime>, Option<NaiveTime>) {
+    // Julian day at local noon is good enough of a starting point
+    let noon = date.and_hms_opt(12, 0, 0).expect("Noon is a valid time");
+    let noon_jd = Utc.from_utc_datetime(&noon).timestamp() as f64 / 86400.0 + UNIX_EPOCH_JD
+        - utc_offset.local_minus_utc() as f64 / 86400.0;
+    let n = (noon_jd - J2000_JD + 0.0008).round();
+    let mean_noon = n - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit =
+        J2000_JD + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return (None, None);
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    (
+        julian_day_to_local(transit - hour_angle / 360.0, utc_offset),
+        julian_day_to_local(transit + hour_angle / 360.0, utc_offset),
+    )
+}
+
+/// Age of the moon in days and its phase name at a given moment
+fn moon_phase(timestamp: i64) -> (f64, &'static str) {
+    let age = ((timestamp - NEW_MOON_EPOCH) as f64 / 86400.0).rem_euclid(SYNODIC_MONTH);
+    let name = match age / SYNODIC_MONTH * 8.0 {
+        p if p < 0.5 => "New Moon",
+        p if p < 1.5 => "Waxing Crescent",
+        p if p < 2.5 => "First Quarter",
+        p if p < 3.5 => "Waxing Gibbous",
+        p if p < 4.5 => "Full Moon",
+        p if p < 5.5 => "Waning Gibbous",
+        p if p < 6.5 => "Last Quarter",
+        p if p < 7.5 => "Waning Crescent",
+        _ => "New Moon",
+    };
+    (age, name)
+}
+
+/// Offline fallback for providers that don't offer astronomy data
+/// Moonrise and moonset aren't calculated
+pub fn calculate(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    utc_offset: FixedOffset,
+) -> Astronomy {
+    let (sunrise, sunset) = sun_times(date, latitude, longitude, utc_offset);
+    let noon = Utc
+        .from_utc_datetime(&date.and_hms_opt(12, 0, 0).expect("Noon is a valid time"))
+        .timestamp()
+        - utc_offset.local_minus_utc() as i64;
+    let (age, phase) = moon_phase(noon);
+    let illumination = (1.0 - (2.0 * std::f64::consts::PI * age / SYNODIC_MONTH).cos()) / 2.0;
+    Astronomy {
+        date,
+        sunrise,
+        sunset,
+        moonrise: None,
+        moonset: None,
+        moon_phase: Some(phase.to_owned()),
+        moon_illumination: Some((illumination * 100.0).round()),
+    }
+}
+
+fn format_time(time: Option<NaiveTime>, lang: Lang) -> String {
+    time.map(|t| t.format(lang.time_format()).to_string())
+        .unwrap_or_else(|| "-".to_owned())
+}
+
+/// Appends an astronomy section to a report
+pub fn add_to_report(report: &mut Report, astronomy: Astronomy, lang: Lang) {
+    let tr = |text| lang.tr(text).to_owned();
+    let mut details = SectionRepr::new();
+    details.push((tr("Sunrise"), format_time(astronomy.sunrise, lang)));
+    details.push((tr("Sunset"), format_time(astronomy.sunset, lang)));
+    let day_length = astronomy
+        .day_length()
+        .map(|d| format!("{}h {:02}m", d.num_hours(), d.num_minutes() % 60))
+        .unwrap_or_else(|| "-".to_owned());
+    details.push((tr("Day length"), day_length));
+    details.push((tr("Moonrise"), format_time(astronomy.moonrise, lang)));
+    details.push((tr("Moonset"), format_time(astronomy.moonset, lang)));
+    details.push((
+        tr("Moon phase"),
+        astronomy.moon_phase.unwrap_or_else(|| "-".to_owned()),
+    ));
+    details.push((
+        tr("Moon illumination"),
+        astronomy
+            .moon_illumination
+            .map(|i| format!("{}%", i))
+            .unwrap_or_else(|| "-".to_owned()),
+    ));
+    let rs = ReportSection::new(
+        format!(
+            "{} - {}",
+            tr("Astronomy"),
+            astronomy.date.format(lang.date_format())
+        ),
+        details,
+    );
+    report.add_section(rs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(time: Option<NaiveTime>, expected: &str) {
+        let expected = NaiveTime::parse_from_str(expected, "%H:%M:%S").expect("Bad time");
+        let time = time.expect("No time was calculated");
+        assert!(
+            (time - expected).num_minutes().abs() <= 2,
+            "{} is too far from {}",
+            time,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_calculate_sun_times() {
+        // OpenWeatherMap reports 1680232860 and 1680278783 for Zaporizhzhia
+        let date = NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+        let offset = FixedOffset::east_opt(10800).expect("Bad offset");
+        let astronomy = calculate(date, 47.8508, 35.1183, offset);
+        assert_close(astronomy.sunrise, "06:21:00");
+        assert_close(astronomy.sunset, "19:06:23");
+        assert_eq!(astronomy.day_length().map(|d| d.num_hours()), Some(12));
+    }
+
+    #[test]
+    fn test_calculate_polar_night() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 21).expect("Bad date");
+        let offset = FixedOffset::east_opt(3600).expect("Bad offset");
+        let astronomy = calculate(date, 78.22, 15.65, offset);
+        assert_eq!(astronomy.sunrise, None);
+        assert_eq!(astronomy.day_length(), None);
+    }
+
+    #[test]
+    fn test_moon_phase() {
+        // WeatherAPI reports a waxing gibbous moon, 68% illuminated on 2023-03-31
+        let date = NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+        let offset = FixedOffset::east_opt(10800).expect("Bad offset");
+        let astronomy = calculate(date, 47.8508, 35.1183, offset);
+        assert_eq!(astronomy.moon_phase.as_deref(), Some("Waxing Gibbous"));
+        let illumination = astronomy.moon_illumination.expect("No illumination");
+        assert!((illumination - 68.0).abs() <= 5.0);
+    }
+}
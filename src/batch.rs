@@ -0,0 +1,432 @@
+use crate::{
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    conditions::Conditions,
+    stats::csv_field,
+    types::{AvailableProviders, Provider},
+    util,
+};
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Columns of a result CSV, after the ones describing the site and the outcome
+const CONDITION_COLUMNS: &str = "time,condition,temp_c,temp_min_c,temp_max_c,feels_like_c,humidity,pressure_mb,precip_mm,precip_chance,wind_kph,gust_kph,wind_dir,cloud_cover";
+
+/// A row of the input file, the line it was read from is kept for error messages and ordering
+#[derive(Debug, Clone, PartialEq)]
+pub struct Site {
+    pub line: usize,
+    pub name: String,
+    pub location: String,
+    pub date: Option<String>,
+    pub provider: Option<String>,
+}
+
+/// Splits CSV text into records, quoted fields may hold commas, quotes and line breaks
+fn records(text: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        bail!("A quoted field starting on line {} is never closed", start);
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    // blank lines separate nothing
+    records.retain(|(_, r)| r.iter().any(|f| !f.trim().is_empty()));
+    Ok(records)
+}
+
+/// Reads sites from CSV with a header of `name`, `location` and optionally `date` and `provider`
+pub fn parse_sites(text: &str) -> Result<Vec<Site>> {
+    let mut records = records(text)?.into_iter();
+    let (_, header) = records.next().context("The input file is empty")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let location = column("location").context("The input file has no `location` column")?;
+    let (name, date, provider) = (column("name"), column("date"), column("provider"));
+    let value = |record: &[String], index: Option<usize>| {
+        index
+            .and_then(|i| record.get(i))
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+    };
+    Ok(records
+        .map(|(line, record)| {
+            let location = value(&record, Some(location)).unwrap_or_default();
+            Site {
+                line,
+                name: value(&record, name).unwrap_or_else(|| location.clone()),
+                location,
+                date: value(&record, date),
+                provider: value(&record, provider),
+            }
+        })
+        .collect())
+}
+
+/// A provider named as in the CLI, e.g. `weather-api`, or as in the configuration, e.g. `WeatherAPI`
+pub fn parse_provider(name: &str) -> Result<AvailableProviders> {
+    AvailableProviders::from_str(name, true)
+        .ok()
+        .or_else(|| AvailableProviders::from_string(name))
+        .with_context(|| {
+            format!(
+                "Unknown provider {}, known ones are {}",
+                name,
+                AvailableProviders::value_variants()
+                    .iter()
+                    .filter_map(|p| p.to_possible_value())
+                    .map(|p| p.get_name().to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// A day offset such as `f1d`, or a single `YYYY-MM-DD` day; no date means current conditions
+pub fn parse_date(date: Option<&str>) -> Result<DateOffsetRepresentation> {
+    match date {
+        None => Ok(DateOffsetRepresentation::Now),
+        Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(day) => Ok(DateOffsetRepresentation::DayRange(
+                DayRange::new(day, day).expect("A day is a range"),
+            )),
+            Err(_) if util::is_offset_arg(date) => util::parse_date_arg(date),
+            Err(_) => bail!(
+                "Invalid date '{}', expected YYYY-MM-DD or an offset such as f1d",
+                date
+            ),
+        },
+    }
+}
+
+/// Outcome of the query of a site, failures keep the reason instead of conditions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Row {
+    pub name: String,
+    pub location: String,
+    pub date: Option<String>,
+    pub provider: Option<String>,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub periods: Vec<Conditions>,
+}
+
+impl Row {
+    fn new(site: &Site, provider: Option<String>, outcome: Result<Vec<Conditions>>) -> Self {
+        let (status, error, periods) = match outcome {
+            Ok(periods) => ("ok", None, periods),
+            Err(e) => ("error", Some(format!("{:#}", e)), Vec::new()),
+        };
+        Self {
+            name: site.name.clone(),
+            location: site.location.clone(),
+            date: site.date.clone(),
+            provider,
+            status,
+            error,
+            periods,
+        }
+    }
+}
+
+/// Picks the provider for a site's command, the one the site names or a routed one
+pub type Resolve<'a> =
+    dyn Fn(&Site, &WeatherCommand) -> Result<(AvailableProviders, Arc<dyn Provider>)> + Sync + 'a;
+
+/// Queries every site with at most `concurrency` queries in flight
+/// A failed site becomes a failed row and doesn't stop the others; rows are in the order of the sites
+pub async fn run(
+    sites: Vec<Site>,
+    concurrency: usize,
+    command: impl Fn(&Site) -> Result<WeatherCommand> + Sync,
+    resolve: &Resolve<'_>,
+) -> Vec<Row> {
+    let command = &command;
+    stream::iter(sites)
+        .map(|site| async move {
+            let prepared = command(&site).and_then(|command| {
+                let (provider, adapter) = resolve(&site, &command)?;
+                Ok((format!("{:?}", provider), adapter, command))
+            });
+            let (provider, adapter, command) = match prepared {
+                Ok(prepared) => prepared,
+                Err(e) => return Row::new(&site, None, Err(e)),
+            };
+            log::info!("Querying {} with {}", site.name, provider);
+            let outcome = adapter.run(command).await.map(|report| {
+                report
+                    .sections()
+                    .iter()
+                    .filter_map(|s| s.conditions())
+                    .cloned()
+                    .collect()
+            });
+            if let Err(e) = &outcome {
+                log::warn!(
+                    "Couldn't query {} on line {}: {:#}",
+                    site.name,
+                    site.line,
+                    e
+                );
+            }
+            Row::new(&site, Some(provider), outcome)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Results as CSV with a line per period, a site without periods still gets a line for its status
+pub fn to_csv(rows: &[Row]) -> String {
+    let mut out = format!(
+        "name,location,date,provider,status,error,{}\n",
+        CONDITION_COLUMNS
+    );
+    let number = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    rows.iter().for_each(|row| {
+        let site = [
+            csv_field(&row.name),
+            csv_field(&row.location),
+            csv_field(row.date.as_deref().unwrap_or_default()),
+            csv_field(row.provider.as_deref().unwrap_or_default()),
+            row.status.to_owned(),
+            csv_field(row.error.as_deref().unwrap_or_default()),
+        ]
+        .join(",");
+        let periods: Vec<String> = row
+            .periods
+            .iter()
+            .map(|c| {
+                [
+                    c.time.to_rfc3339(),
+                    csv_field(c.condition.as_deref().unwrap_or_default()),
+                    number(c.temp_c),
+                    number(c.temp_min_c),
+                    number(c.temp_max_c),
+                    number(c.feels_like_c),
+                    number(c.humidity),
+                    number(c.pressure_mb),
+                    number(c.precip_mm),
+                    number(c.precip_chance),
+                    number(c.wind_kph),
+                    number(c.gust_kph),
+                    csv_field(c.wind_dir.as_deref().unwrap_or_default()),
+                    number(c.cloud_cover),
+                ]
+                .join(",")
+            })
+            .collect();
+        if periods.is_empty() {
+            let empty = ",".repeat(CONDITION_COLUMNS.matches(',').count());
+            out.push_str(&format!("{},{}\n", site, empty));
+        }
+        periods
+            .iter()
+            .for_each(|period| out.push_str(&format!("{},{}\n", site, period)));
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capabilities::Capabilities,
+        report::{Report, ReportSection},
+    };
+    use async_trait::async_trait;
+    use chrono::{TimeZone, Utc};
+
+    /// Answers with the conditions of a day, or fails for a location
+    struct Fake;
+
+    #[async_trait]
+    impl Provider for Fake {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::NONE
+        }
+
+        async fn run(&self, command: WeatherCommand) -> Result<Report> {
+            if command.location == "Atlantis" {
+                bail!("No matching location found");
+            }
+            let mut report = Report::new("Fake");
+            let conditions = Conditions {
+                time: Utc
+                    .with_ymd_and_hms(2023, 3, 31, 0, 0, 0)
+                    .single()
+                    .expect("Bad time"),
+                condition: Some("Light rain, showers".to_owned()),
+                temp_c: Some(7.5),
+                ..Default::default()
+            };
+            report.add_section(ReportSection::from_conditions(
+                command.location.clone(),
+                conditions,
+                &command.view(),
+            ));
+            Ok(report)
+        }
+    }
+
+    #[test]
+    fn test_parse_sites() {
+        let sites = parse_sites(
+            "Name,Location,Date,Provider\r\n\
+             Depot,\"Kyiv, Ukraine\",f1d,weather-api\r\n\
+             \r\n\
+             ,Lviv,,\n\
+             \"Quay \"\"B\"\"\",\"Odesa\nport\",2023-03-31,AerisWeather",
+        )
+        .expect("Couldn't parse the sites");
+        assert_eq!(sites.len(), 3);
+        assert_eq!(
+            sites[0],
+            Site {
+                line: 2,
+                name: "Depot".to_owned(),
+                location: "Kyiv, Ukraine".to_owned(),
+                date: Some("f1d".to_owned()),
+                provider: Some("weather-api".to_owned()),
+            }
+        );
+        assert_eq!(sites[1].name, "Lviv");
+        assert_eq!(sites[1].date, None);
+        assert_eq!((sites[2].line, sites[2].name.as_str()), (5, "Quay \"B\""));
+        assert_eq!(sites[2].location, "Odesa\nport");
+        assert!(parse_sites("name,date\nDepot,f1d").is_err());
+        assert!(parse_sites("location\n\"Kyiv").is_err());
+
+        assert_eq!(
+            parse_provider("AerisWeather").expect("Unknown provider"),
+            AvailableProviders::AerisWeather
+        );
+        assert!(parse_provider("Meteo").is_err());
+        assert!(matches!(
+            parse_date(Some("2023-03-31")).expect("Bad date"),
+            DateOffsetRepresentation::DayRange(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_bad_dates() {
+        let sites = parse_sites(
+            "name,location,date
+Depot,Kyiv,f1d
+Leap,Lviv,2023-02-30
+Typo,Odesa,tomorow",
+        )
+        .expect("Couldn't parse the sites");
+        let command = |site: &Site| -> Result<WeatherCommand> {
+            Ok(WeatherCommand::new(
+                site.location.as_str(),
+                parse_date(site.date.as_deref())?,
+            ))
+        };
+        let resolve =
+            |_: &Site, _: &WeatherCommand| -> Result<(AvailableProviders, Arc<dyn Provider>)> {
+                Ok((AvailableProviders::WeatherAPI, Arc::new(Fake)))
+            };
+        let rows = run(sites, 2, command, &resolve).await;
+        let statuses: Vec<(&str, &str)> =
+            rows.iter().map(|r| (r.name.as_str(), r.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![("Depot", "ok"), ("Leap", "error"), ("Typo", "error")]
+        );
+        assert_eq!(
+            rows[2].error.as_deref(),
+            Some("Invalid date 'tomorow', expected YYYY-MM-DD or an offset such as f1d")
+        );
+        assert!(rows[1].periods.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_keeps_going_after_failures() {
+        let sites = parse_sites(
+            "name,location,date\nDepot,Kyiv,\nLost,Atlantis,\nBad,Lviv,f2x\nQuay,Odesa,",
+        )
+        .expect("Couldn't parse the sites");
+        let command = |site: &Site| -> Result<WeatherCommand> {
+            if site.date.as_deref() == Some("f2x") {
+                bail!("Unknown date f2x");
+            }
+            Ok(WeatherCommand::new(
+                site.location.as_str(),
+                parse_date(None)?,
+            ))
+        };
+        let resolve =
+            |_: &Site, _: &WeatherCommand| -> Result<(AvailableProviders, Arc<dyn Provider>)> {
+                Ok((AvailableProviders::WeatherAPI, Arc::new(Fake)))
+            };
+        let rows = run(sites, 2, command, &resolve).await;
+        let statuses: Vec<(&str, &str)> =
+            rows.iter().map(|r| (r.name.as_str(), r.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("Depot", "ok"),
+                ("Lost", "error"),
+                ("Bad", "error"),
+                ("Quay", "ok")
+            ]
+        );
+        assert_eq!(rows[1].error.as_deref(), Some("No matching location found"));
+        assert_eq!(rows[2].provider, None);
+
+        let csv = to_csv(&rows);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with(
+            "Depot,Kyiv,,WeatherAPI,ok,,2023-03-31T00:00:00+00:00,\"Light rain, showers\",7.5,"
+        ));
+        assert_eq!(
+            lines[2],
+            "Lost,Atlantis,,WeatherAPI,error,No matching location found,,,,,,,,,,,,,,"
+        );
+        assert_eq!(lines[2].matches(',').count(), lines[0].matches(',').count());
+        let json = serde_json::to_value(&rows).expect("Couldn't serialize");
+        assert_eq!(json[0]["periods"][0]["temp_c"], 7.5);
+        assert_eq!(json[2]["error"], "Unknown date f2x");
+    }
+}
@@ -0,0 +1,407 @@
+use crate::command::{DateOffsetRepresentation, WeatherCommand};
+use chrono::NaiveDate;
+use std::net::IpAddr;
+
+/// A way of telling a provider where to look up the weather
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationFormat {
+    /// A place name such as `Kyiv` or `Paris, France`
+    Name,
+    /// Latitude and longitude, e.g. `48.85,2.35`
+    Coordinates,
+    /// A numeric postal code such as a US ZIP code
+    PostalCode,
+    /// An airport code, e.g. `iata:KBP` or `metar:UKBB`
+    Airport,
+    /// An IP address or `auto:ip`
+    Ip,
+}
+
+impl LocationFormat {
+    /// Tells the format of a location, anything that isn't recognized is a name
+    pub fn detect(location: &str) -> Self {
+        let location = location.trim();
+        let lower = location.to_ascii_lowercase();
+        let coordinates = location
+            .split_once(',')
+            .map(|(lat, lon)| {
+                lat.trim().parse::<f64>().is_ok() && lon.trim().parse::<f64>().is_ok()
+            })
+            .unwrap_or(false);
+        if coordinates {
+            LocationFormat::Coordinates
+        } else if lower == "auto:ip" || location.parse::<IpAddr>().is_ok() {
+            LocationFormat::Ip
+        } else if lower.starts_with("iata:") || lower.starts_with("metar:") {
+            LocationFormat::Airport
+        } else if !location.is_empty() && location.chars().all(|c| c.is_ascii_digit()) {
+            LocationFormat::PostalCode
+        } else {
+            LocationFormat::Name
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LocationFormat::Name => "name",
+            LocationFormat::Coordinates => "coordinates",
+            LocationFormat::PostalCode => "postal code",
+            LocationFormat::Airport => "airport code",
+            LocationFormat::Ip => "IP address",
+        }
+    }
+
+    fn described(&self) -> &'static str {
+        match self {
+            LocationFormat::Name => "a name",
+            LocationFormat::Coordinates => "coordinates",
+            LocationFormat::PostalCode => "a postal code",
+            LocationFormat::Airport => "an airport code",
+            LocationFormat::Ip => "an IP address",
+        }
+    }
+}
+
+/// Requests other than a report, which a provider may or may not offer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Alerts,
+    AirQuality,
+    Astronomy,
+    Marine,
+    /// Hourly conditions of the current day, as `rain` and `notify` use them
+    Hourly,
+    /// Daily conditions of past days, as `stats` uses them
+    History,
+}
+
+impl Feature {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Alerts => "weather alerts",
+            Feature::AirQuality => "air quality",
+            Feature::Astronomy => "astronomy data",
+            Feature::Marine => "marine data",
+            Feature::Hourly => "hourly forecasts for today",
+            Feature::History => "daily history",
+        }
+    }
+}
+
+/// What a provider adapter can be asked for, so that requests can be checked before anything is sent
+/// Horizons are as far as the adapter requests data, a plan may allow less; zero means unsupported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    pub current: bool,
+    pub hourly_forecast_hours: u32,
+    pub hourly_history_hours: u32,
+    pub daily_forecast_days: u32,
+    pub history_days: u32,
+    pub hourly: bool,
+    pub alerts: bool,
+    pub air_quality: bool,
+    pub astronomy: bool,
+    pub marine: bool,
+    pub location_formats: &'static [LocationFormat],
+}
+
+impl Capabilities {
+    /// Capabilities of an adapter that can't be asked for anything yet
+    pub const NONE: Capabilities = Capabilities {
+        current: false,
+        hourly_forecast_hours: 0,
+        hourly_history_hours: 0,
+        daily_forecast_days: 0,
+        history_days: 0,
+        hourly: false,
+        alerts: false,
+        air_quality: false,
+        astronomy: false,
+        marine: false,
+        location_formats: &[],
+    };
+
+    /// Whether a feature is offered, with the reason when it isn't
+    pub fn check_feature(&self, feature: Feature) -> Result<(), String> {
+        let supported = match feature {
+            Feature::Alerts => self.alerts,
+            Feature::AirQuality => self.air_quality,
+            Feature::Astronomy => self.astronomy,
+            Feature::Marine => self.marine,
+            Feature::Hourly => self.hourly,
+            Feature::History => self.history_days > 0,
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(format!("There is no support for {}", feature.name()))
+        }
+    }
+
+    /// Whether a location is given in a format the provider understands
+    pub fn check_location(&self, location: &str) -> Result<(), String> {
+        let format = LocationFormat::detect(location);
+        if self.location_formats.contains(&format) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Locations can't be given as {}",
+                format.described()
+            ))
+        }
+    }
+
+    /// Whether a report can be made for a command, with the reason when it can't
+    /// Days are counted from a given day, which should be today at the location
+    pub fn check_command(&self, command: &WeatherCommand, today: NaiveDate) -> Result<(), String> {
+        self.check_location(&command.location)?;
+        match &command.date {
+            DateOffsetRepresentation::Now => {
+                check(self.current, "Current conditions are not supported")
+            }
+            DateOffsetRepresentation::HourOffset(hours) => within(
+                *hours,
+                self.hourly_forecast_hours,
+                self.hourly_history_hours,
+                "hourly",
+                "hours",
+            ),
+            DateOffsetRepresentation::DayOffset(days) => within(
+                *days,
+                self.daily_forecast_days,
+                self.history_days,
+                "daily",
+                "days",
+            ),
+            DateOffsetRepresentation::DayRange(range) => {
+                within(
+                    (range.from - today).num_days() as isize,
+                    self.daily_forecast_days,
+                    self.history_days,
+                    "daily",
+                    "days",
+                )?;
+                within(
+                    (range.to - today).num_days() as isize,
+                    self.daily_forecast_days,
+                    self.history_days,
+                    "daily",
+                    "days",
+                )
+            }
+        }
+    }
+}
+
+fn check(supported: bool, reason: &str) -> Result<(), String> {
+    if supported {
+        Ok(())
+    } else {
+        Err(reason.to_owned())
+    }
+}
+
+/// Checks an offset against the forecast horizon ahead and the history depth behind
+fn within(offset: isize, ahead: u32, behind: u32, kind: &str, unit: &str) -> Result<(), String> {
+    let (limit, direction) = if offset >= 0 {
+        (ahead, "forecast")
+    } else {
+        (behind, "history")
+    };
+    if offset.unsigned_abs() as u64 <= limit as u64 {
+        Ok(())
+    } else if limit == 0 {
+        Err(format!(
+            "{} {} is not supported",
+            capitalize(kind),
+            direction
+        ))
+    } else {
+        Err(format!(
+            "{} {} only reaches {} {} {}",
+            capitalize(kind),
+            direction,
+            limit,
+            unit,
+            if offset >= 0 { "ahead" } else { "back" }
+        ))
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A capability matrix with a column per provider
+pub fn matrix(providers: &[(String, Capabilities)]) -> String {
+    let flag = |supported: bool| if supported { "yes" } else { "-" }.to_owned();
+    let horizon = |value: u32, unit: &str| {
+        if value == 0 {
+            "-".to_owned()
+        } else {
+            format!("{} {}", value, unit)
+        }
+    };
+    let rows: Vec<(&str, Vec<String>)> = vec![
+        (
+            "Current conditions",
+            providers.iter().map(|(_, c)| flag(c.current)).collect(),
+        ),
+        (
+            "Hourly forecast",
+            providers
+                .iter()
+                .map(|(_, c)| horizon(c.hourly_forecast_hours, "hours"))
+                .collect(),
+        ),
+        (
+            "Hourly history",
+            providers
+                .iter()
+                .map(|(_, c)| horizon(c.hourly_history_hours, "hours"))
+                .collect(),
+        ),
+        (
+            "Daily forecast",
+            providers
+                .iter()
+                .map(|(_, c)| horizon(c.daily_forecast_days, "days"))
+                .collect(),
+        ),
+        (
+            "Daily history",
+            providers
+                .iter()
+                .map(|(_, c)| horizon(c.history_days, "days"))
+                .collect(),
+        ),
+        (
+            "Today's hours",
+            providers.iter().map(|(_, c)| flag(c.hourly)).collect(),
+        ),
+        (
+            "Alerts",
+            providers.iter().map(|(_, c)| flag(c.alerts)).collect(),
+        ),
+        (
+            "Air quality",
+            providers.iter().map(|(_, c)| flag(c.air_quality)).collect(),
+        ),
+        (
+            "Astronomy",
+            providers.iter().map(|(_, c)| flag(c.astronomy)).collect(),
+        ),
+        (
+            "Marine",
+            providers.iter().map(|(_, c)| flag(c.marine)).collect(),
+        ),
+        (
+            "Location formats",
+            providers
+                .iter()
+                .map(|(_, c)| {
+                    c.location_formats
+                        .iter()
+                        .map(LocationFormat::name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .collect(),
+        ),
+    ];
+    let widths: Vec<usize> = providers
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            rows.iter()
+                .map(|(_, values)| values[i].len())
+                .chain(std::iter::once(name.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |label: &str, values: Vec<&str>| {
+        let cells: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect();
+        format!("{:<20} | {}", label, cells.join(" | "))
+            .trim_end()
+            .to_owned()
+            + "\n"
+    };
+    let mut out = line(
+        "Capability",
+        providers.iter().map(|(name, _)| name.as_str()).collect(),
+    );
+    rows.iter().for_each(|(label, values)| {
+        out.push_str(&line(label, values.iter().map(String::as_str).collect()));
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::DayRange;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        current: true,
+        daily_forecast_days: 14,
+        history_days: 7,
+        location_formats: &[LocationFormat::Name, LocationFormat::Coordinates],
+        ..Capabilities::NONE
+    };
+
+    fn command(date: DateOffsetRepresentation) -> WeatherCommand {
+        WeatherCommand::new("Kyiv", date)
+    }
+
+    #[test]
+    fn test_detect_location_format() {
+        assert_eq!(
+            LocationFormat::detect("48.85, 2.35"),
+            LocationFormat::Coordinates
+        );
+        assert_eq!(
+            LocationFormat::detect("Paris, France"),
+            LocationFormat::Name
+        );
+        assert_eq!(LocationFormat::detect("10001"), LocationFormat::PostalCode);
+        assert_eq!(LocationFormat::detect("iata:KBP"), LocationFormat::Airport);
+        assert_eq!(LocationFormat::detect("auto:ip"), LocationFormat::Ip);
+        assert_eq!(LocationFormat::detect("100.0.0.1"), LocationFormat::Ip);
+    }
+
+    #[test]
+    fn test_check_command() {
+        let today = NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+        let check = |date| CAPABILITIES.check_command(&command(date), today);
+        assert!(check(DateOffsetRepresentation::Now).is_ok());
+        assert!(check(DateOffsetRepresentation::DayOffset(14)).is_ok());
+        assert_eq!(
+            check(DateOffsetRepresentation::DayOffset(15)),
+            Err("Daily forecast only reaches 14 days ahead".to_owned())
+        );
+        assert_eq!(
+            check(DateOffsetRepresentation::HourOffset(-3)),
+            Err("Hourly history is not supported".to_owned())
+        );
+        let range = DayRange::new(today - chrono::Duration::days(8), today).expect("Bad range");
+        assert!(check(DateOffsetRepresentation::DayRange(range)).is_err());
+        assert_eq!(
+            CAPABILITIES.check_command(
+                &WeatherCommand::new("auto:ip", DateOffsetRepresentation::Now),
+                today
+            ),
+            Err("Locations can't be given as an IP address".to_owned())
+        );
+        assert!(CAPABILITIES.check_feature(Feature::Alerts).is_err());
+        assert!(CAPABILITIES.check_feature(Feature::History).is_ok());
+    }
+}
@@ -1,27 +1,84 @@
-use chrono::{DateTime, Duration, FixedOffset};
+use crate::{
+    conditions::{Field, View},
+    i18n::Lang,
+    types::{TimeZoneChoice, Units},
+};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use serde::Serialize;
 
 /// A simple abstraction for types of dates a user might want to enter
 /// Typically, it is used to represent a relative time period
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum DateOffsetRepresentation {
+    #[default]
     Now,
     HourOffset(isize),
     DayOffset(isize),
+    DayRange(DayRange),
 }
 
-impl Default for DateOffsetRepresentation {
-    fn default() -> Self {
-        Self::Now
+/// Inclusive range of days, it may span both history and forecast days
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct DayRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DayRange {
+    /// Build a range, `None` is returned when it ends before it starts
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Option<Self> {
+        (from <= to).then_some(Self { from, to })
+    }
+
+    /// Every day of the range in order
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let to = self.to;
+        self.from.iter_days().take_while(move |day| *day <= to)
+    }
+
+    /// Days before a given day, the ones history is looked up for
+    pub fn past_days(&self, today: NaiveDate) -> Vec<NaiveDate> {
+        self.days().filter(|day| *day < today).collect()
+    }
+
+    /// The part of the range from a given day on, the one that is forecast
+    pub fn upcoming(&self, today: NaiveDate) -> Option<DayRange> {
+        DayRange::new(self.from.max(today), self.to)
+    }
+
+    /// Consecutive ranges of at most a number of days, e.g. to respect the limits of history endpoints
+    pub fn chunks(&self, days: usize) -> Vec<DayRange> {
+        let all: Vec<NaiveDate> = self.days().collect();
+        all.chunks(days.max(1))
+            .map(|chunk| DayRange {
+                from: chunk[0],
+                to: chunk[chunk.len() - 1],
+            })
+            .collect()
     }
 }
 
 impl DateOffsetRepresentation {
+    /// Whether it lies wholly before today, when no alert is active any more
+    pub fn is_past(&self, today: NaiveDate) -> bool {
+        match self {
+            DateOffsetRepresentation::Now => false,
+            DateOffsetRepresentation::HourOffset(hours) => *hours < 0,
+            DateOffsetRepresentation::DayOffset(days) => *days < 0,
+            DateOffsetRepresentation::DayRange(range) => range.to < today,
+        }
+    }
+
     /// Generate a datetime relative to a user-provided origin datetime, using a variant
     /// e.g., good for generating datetimes for APIs that'll ask you for precise timestamps, when requesting forecast or history data
+    /// The origin should be the location's current time, so that days are counted on the location's calendar
     pub fn to_chrono(&self, origin: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
         match self {
             DateOffsetRepresentation::DayOffset(days) => origin + Duration::days(*days as i64),
             DateOffsetRepresentation::HourOffset(hours) => origin + Duration::hours(*hours as i64),
+            DateOffsetRepresentation::DayRange(range) => {
+                origin + (range.from - origin.date_naive())
+            }
             _ => origin,
         }
     }
@@ -32,6 +89,11 @@ impl DateOffsetRepresentation {
 pub struct WeatherCommand {
     pub location: String,
     pub date: DateOffsetRepresentation,
+    pub units: Option<Units>,
+    pub fields: Vec<Field>,
+    pub tz: TimeZoneChoice,
+    pub lang: Lang,
+    pub alerts: bool,
 }
 
 impl WeatherCommand {
@@ -39,13 +101,76 @@ impl WeatherCommand {
         Self {
             location: location.into(),
             date,
+            units: None,
+            fields: Vec::new(),
+            tz: TimeZoneChoice::default(),
+            lang: Lang::default(),
+            alerts: false,
         }
     }
+
+    /// Restrict the report to a measurement system
+    pub fn with_units(mut self, units: Option<Units>) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Restrict the report to a set of fields, all of them are shown when it's empty
+    pub fn with_fields(mut self, fields: Vec<Field>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Show timestamps on a clock other than the location's one
+    pub fn with_tz(mut self, tz: TimeZoneChoice) -> Self {
+        self.tz = tz;
+        self
+    }
+
+    /// Ask for labels, dates and condition texts in a language
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Add the location's active alerts to the report, where the provider issues any
+    pub fn with_alerts(mut self, alerts: bool) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// Fields, units, language and the clock the report is rendered with
+    pub fn view(&self) -> View {
+        View::new(self.fields.clone(), self.units)
+            .with_tz(self.tz)
+            .with_lang(self.lang)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_is_past() {
+        let today = NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+        let range = |from, to| {
+            DateOffsetRepresentation::DayRange(
+                DayRange::new(
+                    NaiveDate::from_ymd_opt(2023, 3, from).expect("Bad date"),
+                    NaiveDate::from_ymd_opt(2023, 3, to).expect("Bad date"),
+                )
+                .expect("Bad range"),
+            )
+        };
+        assert!(!DateOffsetRepresentation::Now.is_past(today));
+        assert!(DateOffsetRepresentation::DayOffset(-1).is_past(today));
+        assert!(!DateOffsetRepresentation::DayOffset(1).is_past(today));
+        assert!(DateOffsetRepresentation::HourOffset(-5).is_past(today));
+        assert!(range(20, 30).is_past(today));
+        assert!(!range(20, 31).is_past(today));
+    }
+
     #[test]
     fn test_to_chrono() {
         let d = DateTime::<FixedOffset>::parse_from_rfc3339("2019-10-12T07:20:50.52Z")
@@ -56,4 +181,34 @@ mod tests {
         let plus_8h = DateOffsetRepresentation::HourOffset(8).to_chrono(d);
         assert_eq!(plus_8h.to_rfc3339(), "2019-10-12T15:20:50.520+00:00")
     }
+
+    #[test]
+    fn test_day_range_split() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 3, d).expect("Bad date");
+        let range = DayRange::new(day(25), day(31)).expect("Bad range");
+        assert_eq!(range.days().count(), 7);
+        assert_eq!(
+            range.past_days(day(29)),
+            vec![day(25), day(26), day(27), day(28)]
+        );
+        assert_eq!(range.upcoming(day(29)), DayRange::new(day(29), day(31)));
+        assert_eq!(range.upcoming(day(20)), Some(range));
+        assert_eq!(DayRange::new(day(31), day(25)), None);
+    }
+
+    #[test]
+    fn test_day_range_chunks() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2023, m, d).expect("Bad date");
+        let range = DayRange::new(day(1, 1), day(3, 31)).expect("Bad range");
+        let chunks = range.chunks(30);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks[0],
+            DayRange::new(day(1, 1), day(1, 30)).expect("Bad range")
+        );
+        assert_eq!(
+            chunks[2],
+            DayRange::new(day(3, 2), day(3, 31)).expect("Bad range")
+        );
+    }
 }
@@ -0,0 +1,401 @@
+use crate::{
+    i18n::Lang,
+    report::SectionRepr,
+    types::{Clock, TimeZoneChoice, Units},
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Weather conditions of a single period, be it a moment, an hour or a day, normalized across providers
+/// Values are kept in metric units, for daily periods `temp_c` is the average temperature
+/// and `time` is the location's date at midnight UTC, so that it reads as the same date anywhere
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Conditions {
+    pub time: DateTime<Utc>,
+    pub condition: Option<String>,
+    pub sky: Option<Sky>,
+    pub temp_c: Option<f64>,
+    pub temp_min_c: Option<f64>,
+    pub temp_max_c: Option<f64>,
+    pub feels_like_c: Option<f64>,
+    pub dew_point_c: Option<f64>,
+    pub humidity: Option<f64>,
+    pub pressure_mb: Option<f64>,
+    pub precip_mm: Option<f64>,
+    pub precip_chance: Option<f64>,
+    pub uv: Option<f64>,
+    pub visibility_km: Option<f64>,
+    pub wind_kph: Option<f64>,
+    pub gust_kph: Option<f64>,
+    pub wind_dir: Option<String>,
+    pub cloud_cover: Option<f64>,
+    pub snow_cm: Option<f64>,
+}
+
+/// Kind of sky of a period, classified by a provider's condition code rather than its description,
+/// which may be in any language
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sky {
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+impl Sky {
+    /// Name for styling, e.g. `partly-cloudy`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sky::Clear => "clear",
+            Sky::PartlyCloudy => "partly-cloudy",
+            Sky::Cloudy => "cloudy",
+            Sky::Fog => "fog",
+            Sky::Rain => "rain",
+            Sky::Snow => "snow",
+            Sky::Storm => "storm",
+        }
+    }
+}
+
+/// Hourly conditions of a location's current day
+/// Carries the location's offset from UTC, so the hours can be matched against its wall clock
+#[derive(Debug, Clone, PartialEq)]
+pub struct HourlyForecast {
+    pub utc_offset: FixedOffset,
+    pub hours: Vec<Conditions>,
+}
+
+/// Selectable report fields, in the order they are shown
+#[derive(
+    Serialize, Deserialize, Clone, Copy, ValueEnum, Debug, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+pub enum Field {
+    Condition,
+    Temperature,
+    FeelsLike,
+    DewPoint,
+    Humidity,
+    Pressure,
+    Precipitation,
+    PrecipitationChance,
+    Uv,
+    Visibility,
+    Wind,
+    Gusts,
+    WindDirection,
+    CloudCover,
+    Snow,
+}
+
+impl Field {
+    pub const ALL: [Field; 15] = [
+        Field::Condition,
+        Field::Temperature,
+        Field::FeelsLike,
+        Field::DewPoint,
+        Field::Humidity,
+        Field::Pressure,
+        Field::Precipitation,
+        Field::PrecipitationChance,
+        Field::Uv,
+        Field::Visibility,
+        Field::Wind,
+        Field::Gusts,
+        Field::WindDirection,
+        Field::CloudCover,
+        Field::Snow,
+    ];
+}
+
+pub fn celsius_to_fahrenheit(c: f64) -> f64 {
+    c * 9.0 / 5.0 + 32.0
+}
+
+/// Also converts km/h to mph
+pub fn km_to_mi(km: f64) -> f64 {
+    km / 1.609344
+}
+
+pub fn mm_to_in(mm: f64) -> f64 {
+    mm / 25.4
+}
+
+pub fn mb_to_inhg(mb: f64) -> f64 {
+    mb * 0.02953
+}
+
+/// Rounds to a tenth, the precision values are shown with
+pub fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Which fields are shown, in which units and on which clock
+/// No fields means every field a provider has supplied
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct View {
+    pub fields: Vec<Field>,
+    pub units: Option<Units>,
+    pub tz: TimeZoneChoice,
+    pub lang: Lang,
+}
+
+impl View {
+    pub fn new(fields: Vec<Field>, units: Option<Units>) -> Self {
+        Self {
+            fields,
+            units,
+            tz: TimeZoneChoice::default(),
+            lang: Lang::default(),
+        }
+    }
+
+    /// Show timestamps on another clock than the location's one
+    pub fn with_tz(mut self, tz: TimeZoneChoice) -> Self {
+        self.tz = tz;
+        self
+    }
+
+    /// Label rows and format dates in another language than English
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Clock for the timestamps of a location with a known or unknown offset from UTC
+    pub fn clock(&self, location: Option<FixedOffset>) -> Clock {
+        Clock::new(location, self.tz).with_lang(self.lang)
+    }
+
+    fn shown_fields(&self) -> Vec<Field> {
+        let mut fields = if self.fields.is_empty() {
+            Field::ALL.to_vec()
+        } else {
+            self.fields.clone()
+        };
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+
+    /// Pushes a metric and an imperial row for a value, as the units preference allows
+    fn push_measurement(
+        &self,
+        rows: &mut SectionRepr,
+        label: &'static str,
+        value: Option<f64>,
+        metric: (&str, &str),
+        imperial: (&str, &str, fn(f64) -> f64),
+    ) {
+        let value = match value {
+            Some(value) => value,
+            None => return,
+        };
+        let label = self.lang.tr(label);
+        if Units::shows_metric(self.units) {
+            rows.push((
+                format!("{}, {}", label, metric.0),
+                format!("{}{}", value, metric.1),
+            ));
+        }
+        if Units::shows_imperial(self.units) {
+            rows.push((
+                format!("{}, {}", label, imperial.0),
+                format!("{}{}", round(imperial.2(value)), imperial.1),
+            ));
+        }
+    }
+
+    /// Render the selected fields of a period into report rows, fields without a value are skipped
+    pub fn rows(&self, c: &Conditions) -> SectionRepr {
+        let mut rows = SectionRepr::new();
+        let temperature = ("C", "°");
+        let fahrenheit = ("F", "°", celsius_to_fahrenheit as fn(f64) -> f64);
+        self.shown_fields()
+            .into_iter()
+            .for_each(|field| match field {
+                Field::Condition => {
+                    if let Some(condition) = &c.condition {
+                        rows.push((self.lang.tr("Condition").to_owned(), condition.clone()));
+                    }
+                }
+                Field::Temperature => {
+                    let daily = c.temp_min_c.is_some() || c.temp_max_c.is_some();
+                    let label = if daily {
+                        "Average temp."
+                    } else {
+                        "Temperature"
+                    };
+                    self.push_measurement(&mut rows, label, c.temp_c, temperature, fahrenheit);
+                    self.push_measurement(
+                        &mut rows,
+                        "Min. temp.",
+                        c.temp_min_c,
+                        temperature,
+                        fahrenheit,
+                    );
+                    self.push_measurement(
+                        &mut rows,
+                        "Max. temp.",
+                        c.temp_max_c,
+                        temperature,
+                        fahrenheit,
+                    );
+                }
+                Field::FeelsLike => self.push_measurement(
+                    &mut rows,
+                    "Feels like",
+                    c.feels_like_c,
+                    temperature,
+                    fahrenheit,
+                ),
+                Field::DewPoint => self.push_measurement(
+                    &mut rows,
+                    "Dew point",
+                    c.dew_point_c,
+                    temperature,
+                    fahrenheit,
+                ),
+                Field::Humidity => {
+                    if let Some(humidity) = c.humidity {
+                        rows.push((
+                            self.lang.tr("Humidity").to_owned(),
+                            format!("{}%", humidity),
+                        ));
+                    }
+                }
+                Field::Pressure => self.push_measurement(
+                    &mut rows,
+                    "Pressure",
+                    c.pressure_mb,
+                    ("mb", ""),
+                    ("inHg", "", mb_to_inhg),
+                ),
+                Field::Precipitation => self.push_measurement(
+                    &mut rows,
+                    "Precipitation",
+                    c.precip_mm,
+                    ("mm", ""),
+                    ("in", "", mm_to_in),
+                ),
+                Field::PrecipitationChance => {
+                    if let Some(chance) = c.precip_chance {
+                        rows.push((
+                            self.lang.tr("Chance of precipitation").to_owned(),
+                            format!("{}%", chance),
+                        ));
+                    }
+                }
+                Field::Uv => {
+                    if let Some(uv) = c.uv {
+                        rows.push((self.lang.tr("UV index").to_owned(), format!("{}", uv)));
+                    }
+                }
+                Field::Visibility => self.push_measurement(
+                    &mut rows,
+                    "Visibility",
+                    c.visibility_km,
+                    ("km", ""),
+                    ("mi", "", km_to_mi),
+                ),
+                Field::Wind => self.push_measurement(
+                    &mut rows,
+                    "Wind speed",
+                    c.wind_kph,
+                    ("km/h", ""),
+                    ("mph", "", km_to_mi),
+                ),
+                Field::Gusts => self.push_measurement(
+                    &mut rows,
+                    "Wind gusts",
+                    c.gust_kph,
+                    ("km/h", ""),
+                    ("mph", "", km_to_mi),
+                ),
+                Field::WindDirection => {
+                    if let Some(wind_dir) = &c.wind_dir {
+                        rows.push((self.lang.tr("Wind direction").to_owned(), wind_dir.clone()));
+                    }
+                }
+                Field::CloudCover => {
+                    if let Some(cloud_cover) = c.cloud_cover {
+                        rows.push((
+                            self.lang.tr("Cloud cover").to_owned(),
+                            format!("{}%", cloud_cover),
+                        ));
+                    }
+                }
+                Field::Snow => self.push_measurement(
+                    &mut rows,
+                    "Snow",
+                    c.snow_cm,
+                    ("cm", ""),
+                    ("in", "", |cm| mm_to_in(cm * 10.0)),
+                ),
+            });
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions() -> Conditions {
+        Conditions {
+            condition: Some("Overcast".to_owned()),
+            temp_c: Some(10.0),
+            humidity: Some(57.0),
+            pressure_mb: Some(1010.0),
+            wind_kph: Some(23.4),
+            wind_dir: Some("SSW".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rows_skip_missing_fields() {
+        let rows = View::new(Vec::new(), Some(Units::Metric)).rows(&conditions());
+        let labels: Vec<&str> = rows.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Condition",
+                "Temperature, C",
+                "Humidity",
+                "Pressure, mb",
+                "Wind speed, km/h",
+                "Wind direction"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rows_selected_fields_and_units() {
+        let view = View::new(vec![Field::Wind, Field::Temperature], Some(Units::Imperial));
+        let rows = view.rows(&conditions());
+        assert_eq!(
+            rows,
+            vec![
+                ("Temperature, F".to_owned(), "50°".to_owned()),
+                ("Wind speed, mph".to_owned(), "14.5".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rows_both_units() {
+        let view = View::new(vec![Field::Pressure], None);
+        let rows = view.rows(&conditions());
+        assert_eq!(
+            rows,
+            vec![
+                ("Pressure, mb".to_owned(), "1010".to_owned()),
+                ("Pressure, inHg".to_owned(), "29.8".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,36 +1,191 @@
-use crate::types::AvailableProviders;
-use anyhow::{Context, Result};
+use crate::{
+    i18n::Lang,
+    types::{AvailableProviders, Units},
+};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, File},
-    io::{BufReader, BufWriter},
-    path::PathBuf,
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
 };
 
+/// Name under which the top-level settings of a configuration file are addressed
+pub const DEFAULT_PROFILE: &str = "default";
+
 /// Application configuration
-#[derive(Serialize, Deserialize, Default)]
+/// Top-level settings form the default profile, named profiles are kept alongside them
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
 pub struct Configuration {
     #[serde(skip)]
     config_path: Option<PathBuf>,
+    #[serde(flatten)]
+    pub default_profile: Profile,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A set of provider credentials and preferences that can be switched between
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct Profile {
     pub default_provider: Option<AvailableProviders>,
+    /// Providers to prefer when several can serve a request, the default one comes first otherwise
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_order: Vec<AvailableProviders>,
     // pub accuweather_api_key: Option<String>,
     pub weatherapi_api_key: Option<String>,
+    /// Days of history the WeatherAPI plan covers, the free plan's 7 when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weatherapi_history_days: Option<u32>,
     pub aerisweather_client_id: Option<String>,
     pub aerisweather_client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+    /// Language of reports, English when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<Lang>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<String>,
+    /// Notification rules by location, see `weather notify`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<String, Vec<String>>,
+}
+
+/// On-disk format of a configuration file, detected by its extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Anything but a `.toml` file is treated as JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// File extension used for the format, e.g. for temporary copies
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => ".json",
+            ConfigFormat::Toml => ".toml",
+        }
+    }
 }
 
 /// Try to obtain the config path
 /// Will attempt create a path, if it doesn't exist already
-fn obtain_default_os_config_path() -> Result<PathBuf> {
+/// An existing `config.toml` takes precedence over `config.json`
+pub fn obtain_default_os_config_path() -> Result<PathBuf> {
     let mut config_path = dirs::config_dir().context("Couldn't find the config path")?;
     config_path.push("weather");
     if config_path.exists() {
         fs::create_dir_all(&config_path)?;
     }
+    let toml_path = config_path.join("config.toml");
+    if toml_path.exists() {
+        return Ok(toml_path);
+    }
     config_path.push("config.json");
     Ok(config_path)
 }
 
+/// Parse configuration contents written in a given format
+pub fn parse_configuration(contents: &str, format: ConfigFormat) -> Result<Configuration> {
+    let configuration = match format {
+        ConfigFormat::Json => serde_json::from_str(contents)?,
+        ConfigFormat::Toml => toml::from_str(contents)?,
+    };
+    Ok(configuration)
+}
+
+/// Carry the values of a freshly serialized table over to a parsed document,
+/// so that comments and formatting of the keys that are still there survive
+fn merge_toml_table(original: &mut toml_edit::Table, updated: &toml_edit::Table) {
+    let stale: Vec<String> = original
+        .iter()
+        .filter(|(key, _)| !updated.contains_key(key))
+        .map(|(key, _)| key.to_owned())
+        .collect();
+    stale.iter().for_each(|key| {
+        original.remove(key);
+    });
+    updated
+        .iter()
+        .for_each(|(key, item)| match (original.get_mut(key), item) {
+            (Some(toml_edit::Item::Table(orig)), toml_edit::Item::Table(new)) => {
+                merge_toml_table(orig, new)
+            }
+            (Some(toml_edit::Item::Value(orig)), toml_edit::Item::Value(new)) => {
+                let decor = orig.decor().clone();
+                *orig = new.clone();
+                *orig.decor_mut() = decor;
+            }
+            _ => {
+                original.insert(key, item.clone());
+            }
+        });
+}
+
+/// Exclusive advisory lock on a sidecar `<config>.lock` file, released once it's dropped
+/// Hold it from loading a configuration until it's saved, so that concurrent changes aren't lost
+pub struct ConfigLock {
+    _file: File,
+}
+
+impl ConfigLock {
+    /// Block until no other process holds the lock of a config file
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut lock_name = path
+            .file_name()
+            .context("The config path doesn't name a file")?
+            .to_os_string();
+        lock_name.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.with_file_name(lock_name))
+            .context("Couldn't open the config lock file")?;
+        file.lock_exclusive()
+            .context("Couldn't lock the config file")?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Run a closure while holding the config lock
+fn with_config_lock<T>(path: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let _lock = ConfigLock::acquire(path)?;
+    f()
+}
+
+/// Write a file next to the destination first and rename it over, so readers never see a partial file
+fn replace_file(path: &Path, contents: &str) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir).context("Couldn't create the config directory")?;
+    let mut tmp =
+        tempfile::NamedTempFile::new_in(dir).context("Couldn't create a temporary config file")?;
+    tmp.write_all(contents.as_bytes())?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path)
+        .context("Couldn't replace the config file")?;
+    Ok(())
+}
+
+/// Atomically replace a configuration file with given contents under the config lock
+pub fn write_config_file(path: &Path, contents: &str) -> Result<()> {
+    with_config_lock(path, || replace_file(path, contents))
+}
+
 /// Attempt to open a config file at a specified location or revert to using an XDG-compliant configuration directory
 pub fn open_or_default(path_opt: Option<PathBuf>) -> Result<Configuration> {
     let path = match path_opt {
@@ -38,11 +193,10 @@ pub fn open_or_default(path_opt: Option<PathBuf>) -> Result<Configuration> {
         None => obtain_default_os_config_path()?,
     };
     if path.exists() {
-        let cfg_file = File::open(path).context("Couldn't open the configuration file")?;
-        let rdr = BufReader::new(cfg_file);
-        let read_cfg =
-            serde_json::from_reader(rdr).context("Couldn't parse the configuration file")?;
-        Ok(read_cfg)
+        let contents = fs::read_to_string(&path).context("Couldn't open the configuration file")?;
+        let read_cfg = parse_configuration(&contents, ConfigFormat::from_path(&path))
+            .context("Couldn't parse the configuration file")?;
+        Ok(read_cfg.with_config_path(Some(path)))
     } else {
         Ok(Configuration {
             config_path: Some(path),
@@ -51,7 +205,7 @@ pub fn open_or_default(path_opt: Option<PathBuf>) -> Result<Configuration> {
     }
 }
 
-impl Configuration {
+impl Profile {
     // pub fn set_accuweather_api_key(&mut self, api_key_opt: Option<String>) {
     // self.accuweather_api_key = api_key_opt;
     // }
@@ -60,6 +214,10 @@ impl Configuration {
         self.weatherapi_api_key = api_key_opt;
     }
 
+    pub fn set_weatherapi_history_days(&mut self, history_days_opt: Option<u32>) {
+        self.weatherapi_history_days = history_days_opt;
+    }
+
     pub fn set_aerisweather_client_secret(
         &mut self,
         client_id_opt: Option<String>,
@@ -73,17 +231,172 @@ impl Configuration {
         self.default_provider = provider_opt;
     }
 
+    pub fn set_provider_order(&mut self, providers: Vec<AvailableProviders>) {
+        self.provider_order = providers;
+    }
+
+    /// Every provider from the most preferred one: the given order, the default provider, then the rest
+    pub fn provider_preference(&self) -> Vec<AvailableProviders> {
+        let mut preference = self.provider_order.clone();
+        self.default_provider
+            .iter()
+            .chain(AvailableProviders::value_variants())
+            .for_each(|provider| {
+                if !preference.contains(provider) {
+                    preference.push(provider.clone());
+                }
+            });
+        preference
+    }
+
+    pub fn set_units(&mut self, units_opt: Option<Units>) {
+        self.units = units_opt;
+    }
+
+    pub fn set_lang(&mut self, lang: Option<Lang>) {
+        self.lang = lang;
+    }
+
+    /// Language a report is made in, one given for a command wins over the profile's one
+    pub fn lang(&self, requested: Option<Lang>) -> Lang {
+        requested.or(self.lang).unwrap_or_default()
+    }
+
+    pub fn set_locations(&mut self, locations: Vec<String>) {
+        self.locations = locations;
+    }
+
+    /// Adds a notification rule for a location, a rule is only kept once
+    pub fn add_rule(&mut self, location: &str, rule: &str) -> Result<()> {
+        let rules = self.rules.entry(location.to_owned()).or_default();
+        if rules.iter().any(|r| r == rule) {
+            bail!("Rule {} already exists for {}", rule, location);
+        }
+        rules.push(rule.to_owned());
+        Ok(())
+    }
+
+    /// Removes a notification rule of a location, locations without rules are dropped
+    pub fn remove_rule(&mut self, location: &str, rule: &str) -> Result<()> {
+        let rules = self
+            .rules
+            .get_mut(location)
+            .with_context(|| format!("There are no rules for {}", location))?;
+        let before = rules.len();
+        rules.retain(|r| r != rule);
+        if rules.len() == before {
+            bail!("Rule {} doesn't exist for {}", rule, location);
+        }
+        if rules.is_empty() {
+            self.rules.remove(location);
+        }
+        Ok(())
+    }
+}
+
+impl Configuration {
+    /// Look up a profile by name, the default profile is used when no name is given
+    pub fn profile(&self, name_opt: Option<&str>) -> Result<&Profile> {
+        match name_opt {
+            None | Some(DEFAULT_PROFILE) => Ok(&self.default_profile),
+            Some(name) => self
+                .profiles
+                .get(name)
+                .with_context(|| format!("Profile {} doesn't exist", name)),
+        }
+    }
+
+    /// Mutable counterpart of [`Configuration::profile`]
+    pub fn profile_mut(&mut self, name_opt: Option<&str>) -> Result<&mut Profile> {
+        match name_opt {
+            None | Some(DEFAULT_PROFILE) => Ok(&mut self.default_profile),
+            Some(name) => self
+                .profiles
+                .get_mut(name)
+                .with_context(|| format!("Profile {} doesn't exist", name)),
+        }
+    }
+
+    /// Names of all the profiles, starting with the default one
+    pub fn profile_names(&self) -> Vec<&str> {
+        std::iter::once(DEFAULT_PROFILE)
+            .chain(self.profiles.keys().map(String::as_str))
+            .collect()
+    }
+
+    /// Adds a new named profile
+    pub fn create_profile<S: Into<String>>(&mut self, name: S, profile: Profile) -> Result<()> {
+        let name = name.into();
+        if name == DEFAULT_PROFILE || self.profiles.contains_key(&name) {
+            bail!("Profile {} already exists", name);
+        }
+        self.profiles.insert(name, profile);
+        Ok(())
+    }
+
+    /// Duplicates an existing profile under a new name
+    pub fn copy_profile<S: Into<String>>(&mut self, from: &str, to: S) -> Result<()> {
+        let profile = self.profile(Some(from))?.clone();
+        self.create_profile(to, profile)
+    }
+
+    /// Removes a named profile, the default profile can't be removed
+    pub fn delete_profile(&mut self, name: &str) -> Result<()> {
+        if name == DEFAULT_PROFILE {
+            bail!("The default profile can't be deleted");
+        }
+        self.profiles
+            .remove(name)
+            .with_context(|| format!("Profile {} doesn't exist", name))?;
+        Ok(())
+    }
+
+    /// Location the configuration is read from and written to
+    pub fn config_path(&self) -> Result<PathBuf> {
+        match &self.config_path {
+            Some(p) => Ok(p.clone()),
+            None => obtain_default_os_config_path()
+                .context("Was unable to obtain OS-dependent config path"),
+        }
+    }
+
+    /// Serialize the configuration in a given format
+    /// For TOML, an original document can be given to keep its comments and layout
+    pub fn serialize(&self, format: ConfigFormat, original: Option<&str>) -> Result<String> {
+        match format {
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ConfigFormat::Toml => {
+                let updated = toml::to_string(self)?.parse::<toml_edit::Document>()?;
+                let mut document = match original.map(str::parse::<toml_edit::Document>) {
+                    Some(Ok(document)) => document,
+                    _ => return Ok(updated.to_string()),
+                };
+                merge_toml_table(document.as_table_mut(), updated.as_table());
+                Ok(document.to_string())
+            }
+        }
+    }
+
     /// Handles serializing a configuration file to either a location specified on itself or a default os-dependent one
+    /// The format of the file is kept, judging by its extension
+    /// The file is replaced atomically while holding the config lock, so concurrent saves don't corrupt it
     pub fn save(&self) -> Result<()> {
-        let path = match &self.config_path {
-            Some(p) => p.clone(),
-            None => obtain_default_os_config_path()
-                .context("Was unable to obtain OS-dependent config path")?,
+        let lock = ConfigLock::acquire(&self.config_path()?)?;
+        self.save_locked(&lock)
+    }
+
+    /// Same as `save`, for when the config lock is already held since the configuration was loaded
+    pub fn save_locked(&self, _lock: &ConfigLock) -> Result<()> {
+        let path = self.config_path()?;
+        let format = ConfigFormat::from_path(&path);
+        let original = match format {
+            ConfigFormat::Toml => fs::read_to_string(&path).ok(),
+            ConfigFormat::Json => None,
         };
-        let file = File::create(path).context("Config file was not found")?;
-        let wrtr = BufWriter::new(file);
-        serde_json::to_writer_pretty(wrtr, &self).context("Couldn't save the config file")?;
-        Ok(())
+        let contents = self
+            .serialize(format, original.as_deref())
+            .context("Couldn't save the config file")?;
+        replace_file(&path, &contents)
     }
 
     /// Modifies the path the config will be attempted to be written to
@@ -122,30 +435,257 @@ mod tests {
         // Win: Some(C:\Users\Alice\AppData\Roaming)
         // Mac: Some(/Users/Alice/Library/Application Support)
 
-        assert_eq!(
-            obtain_default_os_config_path()
-                .expect("Unable to obtain the path")
-                .as_path()
-                .ends_with(expected_path_end),
-            true
-        );
+        assert!(obtain_default_os_config_path()
+            .expect("Unable to obtain the path")
+            .as_path()
+            .ends_with(expected_path_end));
     }
 
     #[test]
     fn test_save_load_cycle() {
         let mut config = Configuration::default().with_config_path(Some(".tmp/test.json".into()));
-        // config.set_accuweather_api_key(Some("11111".to_owned()));
-        config.set_weatherapi_api_key(Some("api_key_opt".to_owned()));
-        // config.set_default_provider(Some(AvailableProviders::AccuWeather));
-        config.set_default_provider(Some(AvailableProviders::AerisWeather));
+        // config.default_profile.set_accuweather_api_key(Some("11111".to_owned()));
+        config
+            .default_profile
+            .set_weatherapi_api_key(Some("api_key_opt".to_owned()));
+        // config.default_profile.set_default_provider(Some(AvailableProviders::AccuWeather));
+        config
+            .default_profile
+            .set_default_provider(Some(AvailableProviders::AerisWeather));
         fs::create_dir_all(".tmp").expect("Wasn't able to create a temporary test directory");
         config.save().expect("Was unable to save a config");
         let config =
             open_or_default(Some(".tmp/test.json".into())).expect("Couldn't open a config file");
-        assert_eq!(config.weatherapi_api_key, Some("api_key_opt".into()));
         assert_eq!(
-            config.default_provider,
+            config.default_profile.weatherapi_api_key,
+            Some("api_key_opt".into())
+        );
+        assert_eq!(
+            config.default_profile.default_provider,
             Some(AvailableProviders::AerisWeather)
         );
     }
+
+    #[test]
+    fn test_toml_save_keeps_comments() {
+        let path = PathBuf::from(".tmp/test_comments.toml");
+        fs::create_dir_all(".tmp").expect("Wasn't able to create a temporary test directory");
+        fs::write(
+            &path,
+            "# personal keys\nweatherapi_api_key = \"old\" # free plan\n\n\
+             # paid plan\n[profiles.work]\ndefault_provider = \"AerisWeather\"\n",
+        )
+        .expect("Couldn't write a config file");
+        let mut config = open_or_default(Some(path.clone())).expect("Couldn't open a config");
+        assert_eq!(
+            config
+                .profile(Some("work"))
+                .expect("No work profile")
+                .default_provider,
+            Some(AvailableProviders::AerisWeather)
+        );
+        config
+            .default_profile
+            .set_weatherapi_api_key(Some("new".into()));
+        config.save().expect("Was unable to save a config");
+
+        let contents = fs::read_to_string(&path).expect("Couldn't read a config file");
+        assert!(contents.contains("# personal keys"));
+        assert!(contents.contains("weatherapi_api_key = \"new\" # free plan"));
+        assert!(contents.contains("# paid plan"));
+        let config = open_or_default(Some(path)).expect("Couldn't open a config");
+        assert_eq!(
+            config.default_profile.weatherapi_api_key,
+            Some("new".into())
+        );
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        fs::create_dir_all(".tmp/race").expect("Wasn't able to create a temporary test directory");
+        let path = PathBuf::from(".tmp/race/config.json");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let mut config = Configuration::default().with_config_path(Some(path));
+                    // a long enough payload makes torn writes likely without the lock
+                    config
+                        .default_profile
+                        .set_locations((0..200).map(|l| format!("{}-{}", i, l)).collect());
+                    (0..20).for_each(|_| config.save().expect("Was unable to save a config"));
+                    config
+                })
+            })
+            .collect();
+        let written: Vec<Configuration> = writers
+            .into_iter()
+            .map(|w| w.join().expect("A writer has panicked"))
+            .collect();
+
+        let config = open_or_default(Some(path)).expect("The config got corrupted");
+        assert!(written.contains(&config));
+        let leftovers = fs::read_dir(".tmp/race")
+            .expect("Couldn't list the test directory")
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "config.json" && name != "config.json.lock")
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        fs::create_dir_all(".tmp/updates")
+            .expect("Wasn't able to create a temporary test directory");
+        let path = PathBuf::from(".tmp/updates/config.json");
+        let _ = fs::remove_file(&path);
+        let updaters: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    (0..10).for_each(|_| {
+                        let lock = ConfigLock::acquire(&path).expect("Couldn't lock the config");
+                        let mut config =
+                            open_or_default(Some(path.clone())).expect("Couldn't open a config");
+                        let days = config.default_profile.weatherapi_history_days.unwrap_or(0);
+                        config
+                            .default_profile
+                            .set_weatherapi_history_days(Some(days + 1));
+                        config
+                            .save_locked(&lock)
+                            .expect("Was unable to save a config");
+                    })
+                })
+            })
+            .collect();
+        updaters
+            .into_iter()
+            .for_each(|u| u.join().expect("An updater has panicked"));
+
+        let config = open_or_default(Some(path)).expect("The config got corrupted");
+        // no update was lost to another one that loaded the config before it got saved
+        assert_eq!(config.default_profile.weatherapi_history_days, Some(40));
+    }
+
+    #[test]
+    fn test_config_format_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.toml")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.json")),
+            ConfigFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_legacy_configuration_is_default_profile() {
+        let config: Configuration = serde_json::from_str(
+            r#"{"default_provider": "WeatherAPI", "weatherapi_api_key": "key",
+            "aerisweather_client_id": null, "aerisweather_client_secret": null}"#,
+        )
+        .expect("Couldn't parse a legacy config");
+        let profile = config.profile(None).expect("No default profile");
+        assert_eq!(
+            profile.default_provider,
+            Some(AvailableProviders::WeatherAPI)
+        );
+        assert_eq!(profile.weatherapi_api_key, Some("key".into()));
+        assert_eq!(config.profile_names(), vec![DEFAULT_PROFILE]);
+    }
+
+    #[test]
+    fn test_profiles_cycle() {
+        let mut config =
+            Configuration::default().with_config_path(Some(".tmp/test_profiles.json".into()));
+        let mut work = Profile::default();
+        work.set_default_provider(Some(AvailableProviders::AerisWeather));
+        work.set_aerisweather_client_secret(Some("id".into()), Some("secret".into()));
+        work.set_units(Some(Units::Metric));
+        work.set_locations(vec!["Kyiv".into()]);
+        config
+            .create_profile("work", work.clone())
+            .expect("Couldn't create a profile");
+        assert!(config.create_profile("work", Profile::default()).is_err());
+        assert!(config
+            .create_profile(DEFAULT_PROFILE, Profile::default())
+            .is_err());
+        config
+            .copy_profile("work", "home")
+            .expect("Couldn't copy a profile");
+        config
+            .profile_mut(Some("home"))
+            .expect("No copied profile")
+            .set_default_provider(Some(AvailableProviders::WeatherAPI));
+        assert!(config.delete_profile(DEFAULT_PROFILE).is_err());
+
+        fs::create_dir_all(".tmp").expect("Wasn't able to create a temporary test directory");
+        config.save().expect("Was unable to save a config");
+        let mut config = open_or_default(Some(".tmp/test_profiles.json".into()))
+            .expect("Couldn't open a config file");
+        assert_eq!(
+            config.profile_names(),
+            vec![DEFAULT_PROFILE, "home", "work"]
+        );
+        assert_eq!(
+            config.profile(Some("work")).expect("No work profile"),
+            &work
+        );
+        assert_eq!(
+            config
+                .profile(Some("home"))
+                .expect("No home profile")
+                .default_provider,
+            Some(AvailableProviders::WeatherAPI)
+        );
+        config
+            .delete_profile("home")
+            .expect("Couldn't delete a profile");
+        assert!(config.profile(Some("home")).is_err());
+    }
+
+    #[test]
+    fn test_rules_cycle() {
+        let path = PathBuf::from(".tmp/test_rules.toml");
+        fs::create_dir_all(".tmp").expect("Wasn't able to create a temporary test directory");
+        let _ = fs::remove_file(&path);
+        let mut config = Configuration::default().with_config_path(Some(path.clone()));
+        let mut work = Profile::default();
+        work.add_rule("Kyiv, UA", "temp < 0")
+            .expect("Couldn't add a rule");
+        config
+            .create_profile("work", work)
+            .expect("Couldn't create a profile");
+        let profile = &mut config.default_profile;
+        profile
+            .add_rule("Kyiv, UA", "temp < 0")
+            .expect("Couldn't add a rule");
+        profile
+            .add_rule("Kyiv, UA", "wind_kph > 50")
+            .expect("Couldn't add a rule");
+        assert!(profile.add_rule("Kyiv, UA", "temp < 0").is_err());
+        assert!(profile.remove_rule("Kyiv, UA", "uv > 8").is_err());
+        assert!(profile.remove_rule("Lviv", "temp < 0").is_err());
+        config.save().expect("Was unable to save a config");
+
+        let mut config = open_or_default(Some(path)).expect("Couldn't open a config file");
+        assert_eq!(
+            config.default_profile.rules["Kyiv, UA"],
+            vec!["temp < 0".to_owned(), "wind_kph > 50".to_owned()]
+        );
+        assert_eq!(
+            config.profile(Some("work")).expect("No work profile").rules["Kyiv, UA"],
+            vec!["temp < 0".to_owned()]
+        );
+        let profile = &mut config.default_profile;
+        profile
+            .remove_rule("Kyiv, UA", "temp < 0")
+            .expect("Couldn't remove a rule");
+        profile
+            .remove_rule("Kyiv, UA", "wind_kph > 50")
+            .expect("Couldn't remove a rule");
+        assert!(profile.rules.is_empty());
+    }
 }
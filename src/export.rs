@@ -0,0 +1,359 @@
+use crate::{
+    conditions::{celsius_to_fahrenheit, View},
+    report::{Report, ReportSection},
+    terminal::{self, Icons},
+    types::Units,
+};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::ValueEnum;
+use std::fmt::Write;
+
+/// Document formats a report can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// A heading and a table per section
+    Markdown,
+    /// A standalone page with inline CSS
+    Html,
+    /// An iCalendar file with an all-day event per day of a daily forecast
+    Ics,
+}
+
+/// Longest content line of an iCalendar file in octets, longer ones are folded
+const ICS_LINE_OCTETS: usize = 75;
+
+/// Inline style of exported pages, so that they can be sent or archived as a single file
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.5em; }
+h2 { font-size: 1.15em; margin-top: 1.5em; }
+table { border-collapse: collapse; min-width: 24em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #f0f0f0; }
+tr:nth-child(even) td { background: #fafafa; }";
+
+/// Renders a report on a location as a document of a format, summaries follow the view's units
+pub fn render(report: &Report, location: &str, output: Output, view: &View) -> Result<String> {
+    match output {
+        Output::Markdown => Ok(markdown(report)),
+        Output::Html => Ok(html(report)),
+        Output::Ics => ics(report, location, view, Utc::now()),
+    }
+}
+
+/// Keeps pipes and line breaks of a value from breaking a table row
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn markdown_section(out: &mut String, section: &ReportSection) {
+    let _ = writeln!(out, "## {}\n", section.title());
+    if section.contents().is_empty() {
+        return;
+    }
+    out.push_str("| Field | Value |\n| --- | --- |\n");
+    section.contents().iter().for_each(|(label, value)| {
+        let _ = writeln!(
+            out,
+            "| {} | {} |",
+            markdown_cell(label),
+            markdown_cell(value)
+        );
+    });
+    out.push('\n');
+}
+
+/// A level-one heading for the report and a table per section
+pub fn markdown(report: &Report) -> String {
+    let mut out = format!("# {}\n\n", report.title());
+    report
+        .sections()
+        .iter()
+        .for_each(|section| markdown_section(&mut out, section));
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut out, c| {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                c => out.push(c),
+            }
+            out
+        })
+}
+
+fn html_section(out: &mut String, section: &ReportSection) {
+    let _ = writeln!(out, "<h2>{}</h2>", escape_html(section.title()));
+    if section.contents().is_empty() {
+        return;
+    }
+    out.push_str("<table>\n<tr><th>Field</th><th>Value</th></tr>\n");
+    section.contents().iter().for_each(|(label, value)| {
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(label),
+            escape_html(value)
+        );
+    });
+    out.push_str("</table>\n");
+}
+
+/// A standalone page, titled like the report, with a table per section
+pub fn html(report: &Report) -> String {
+    let title = escape_html(report.title());
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title
+    );
+    report
+        .sections()
+        .iter()
+        .for_each(|section| html_section(&mut out, section));
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escapes text values as RFC 5545 requires
+fn escape_ics(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Writes a content line, folding it into continuation lines that start with a space
+fn ics_line(out: &mut String, line: &str) {
+    let mut octets = 0;
+    line.chars().for_each(|c| {
+        if octets + c.len_utf8() > ICS_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    });
+    out.push_str("\r\n");
+}
+
+/// Identifier of a location's day, the same on every export so that re-imports update events
+fn ics_uid(location: &str, day: NaiveDate) -> String {
+    let slug: String = location
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{}-{}@weather", day.format("%Y%m%d"), slug)
+}
+
+/// One line about a day, e.g. `☁ 12–18°C, 60% rain`
+/// Temperatures are in Fahrenheit when the view only shows imperial units
+fn ics_summary(section: &ReportSection, view: &View) -> Option<String> {
+    let c = section.conditions()?;
+    let (unit, convert): (&str, fn(f64) -> f64) = if Units::shows_metric(view.units) {
+        ("C", |celsius| celsius)
+    } else {
+        ("F", celsius_to_fahrenheit)
+    };
+    let low = c.temp_min_c.or(c.temp_c).map(convert);
+    let high = c.temp_max_c.or(c.temp_c).map(convert);
+    let temperature = match (low, high) {
+        (Some(low), Some(high)) if low.round() != high.round() => {
+            Some(format!("{:.0}–{:.0}°{}", low, high, unit))
+        }
+        (Some(temp), _) | (_, Some(temp)) => Some(format!("{:.0}°{}", temp, unit)),
+        _ => None,
+    };
+    let chance = c.precip_chance.map(|chance| format!("{:.0}% rain", chance));
+    let details = temperature
+        .into_iter()
+        .chain(chance)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let details = match c.condition.as_deref() {
+        Some(condition) if details.is_empty() => condition.to_owned(),
+        _ => details,
+    };
+    let icon = c.sky.and_then(|sky| terminal::icon(sky, Icons::Unicode));
+    Some(match icon {
+        Some(icon) => format!("{} {}", icon, details),
+        None => details,
+    })
+}
+
+/// A calendar with an all-day event per daily period, the report's rows are the description
+pub fn ics(report: &Report, location: &str, view: &View, stamp: DateTime<Utc>) -> Result<String> {
+    let days: Vec<&ReportSection> = report
+        .sections()
+        .iter()
+        .filter(|s| {
+            s.conditions()
+                .is_some_and(|c| c.temp_min_c.is_some() || c.temp_max_c.is_some())
+        })
+        .collect();
+    if days.is_empty() {
+        bail!("Calendars are made of daily forecasts, ask for days, e.g. `f7d`");
+    }
+    let mut out = String::new();
+    [
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//weather//weather forecast//EN".to_owned(),
+        "CALSCALE:GREGORIAN".to_owned(),
+        format!(
+            "X-WR-CALNAME:{}",
+            escape_ics(&format!("Weather - {}", location))
+        ),
+    ]
+    .iter()
+    .for_each(|line| ics_line(&mut out, line));
+    days.into_iter().for_each(|section| {
+        let day = section
+            .conditions()
+            .map(|c| c.time.date_naive())
+            .unwrap_or_default();
+        let description = section
+            .contents()
+            .iter()
+            .map(|(label, value)| format!("{}: {}", label, value))
+            .collect::<Vec<_>>()
+            .join("\n");
+        [
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{}", ics_uid(location, day)),
+            format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")),
+            format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                (day + Duration::days(1)).format("%Y%m%d")
+            ),
+            format!(
+                "SUMMARY:{}",
+                escape_ics(&ics_summary(section, view).unwrap_or_default())
+            ),
+            format!("LOCATION:{}", escape_ics(location)),
+            format!("DESCRIPTION:{}", escape_ics(&description)),
+            "TRANSP:TRANSPARENT".to_owned(),
+            "END:VEVENT".to_owned(),
+        ]
+        .iter()
+        .for_each(|line| ics_line(&mut out, line));
+    });
+    ics_line(&mut out, "END:VCALENDAR");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        conditions::{Conditions, Sky, View},
+        types::Units,
+    };
+    use chrono::TimeZone;
+
+    fn report() -> Report {
+        let mut report = Report::new("WeatherAPI - current");
+        report.add_section(ReportSection::new(
+            "Status".to_owned(),
+            vec![
+                ("Condition".to_owned(), "Rain | sleet".to_owned()),
+                ("Temperature, C".to_owned(), "7.5°".to_owned()),
+            ],
+        ));
+        report.add_section(ReportSection::new("Alerts".to_owned(), Vec::new()));
+        report
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            markdown(&report()),
+            "# WeatherAPI - current\n\n\
+             ## Status\n\n\
+             | Field | Value |\n| --- | --- |\n\
+             | Condition | Rain \\| sleet |\n\
+             | Temperature, C | 7.5° |\n\n\
+             ## Alerts\n\n"
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let mut report = report();
+        report.add_section(ReportSection::new(
+            "<script>".to_owned(),
+            vec![("A & B".to_owned(), "\"quoted\"".to_owned())],
+        ));
+        let page = html(&report);
+        assert!(page.starts_with("<!DOCTYPE html>\n"));
+        assert!(page.contains("<title>WeatherAPI - current</title>"));
+        assert!(page.contains("<style>\nbody {"));
+        assert!(page.contains("<tr><td>Temperature, C</td><td>7.5°</td></tr>"));
+        assert!(page.contains("<h2>&lt;script&gt;</h2>"));
+        assert!(page.contains("<tr><td>A &amp; B</td><td>&quot;quoted&quot;</td></tr>"));
+        assert!(page.ends_with("</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn test_ics() {
+        let stamp = Utc
+            .with_ymd_and_hms(2023, 3, 30, 8, 0, 0)
+            .single()
+            .expect("Bad time");
+        let view = View::new(Vec::new(), Some(Units::Metric));
+        assert!(ics(&report(), "Kyiv", &view, stamp).is_err());
+
+        let mut report = Report::new("WeatherAPI - forecast");
+        let day = Conditions {
+            time: Utc
+                .with_ymd_and_hms(2023, 3, 31, 0, 0, 0)
+                .single()
+                .expect("Bad time"),
+            condition: Some("Overcast".to_owned()),
+            sky: Some(Sky::Cloudy),
+            temp_c: Some(14.6),
+            temp_min_c: Some(11.6),
+            temp_max_c: Some(18.2),
+            precip_chance: Some(60.0),
+            wind_dir: Some("NE".to_owned()),
+            ..Default::default()
+        };
+        report.add_section(ReportSection::from_conditions(
+            "31/03/2023".to_owned(),
+            day,
+            &view,
+        ));
+        let calendar = ics(&report, "Kyiv, Ukraine", &view, stamp).expect("Couldn't export");
+        let lines: Vec<&str> = calendar.split("\r\n").collect();
+        assert_eq!(lines[0], "BEGIN:VCALENDAR");
+        assert!(lines.contains(&"UID:20230331-kyiv--ukraine@weather"));
+        assert!(lines.contains(&"DTSTAMP:20230330T080000Z"));
+        assert!(lines.contains(&"DTSTART;VALUE=DATE:20230331"));
+        assert!(lines.contains(&"DTEND;VALUE=DATE:20230401"));
+        assert!(lines.contains(&"SUMMARY:☁ 12–18°C\\, 60% rain"));
+        assert!(lines.contains(&"LOCATION:Kyiv\\, Ukraine"));
+        assert!(calendar.contains("DESCRIPTION:Condition: Overcast\\nAverage temp.\\, C: 14.6°"));
+        assert!(lines.iter().all(|line| line.len() <= ICS_LINE_OCTETS));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        // the same day of the same location keeps its identifier
+        assert_eq!(
+            calendar.replace("20230330T080000Z", ""),
+            ics(&report, "Kyiv, Ukraine", &view, stamp + Duration::hours(5))
+                .expect("Couldn't export")
+                .replace("20230330T130000Z", "")
+        );
+        // imperial profiles get Fahrenheit summaries
+        let imperial = View::new(Vec::new(), Some(Units::Imperial));
+        let calendar = ics(&report, "Kyiv, Ukraine", &imperial, stamp).expect("Couldn't export");
+        assert!(calendar
+            .split("\r\n")
+            .any(|line| line == "SUMMARY:☁ 53–65°F\\, 60% rain"));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    command::{DateOffsetRepresentation, WeatherCommand},
+    conditions::Conditions,
+    types::{AvailableProviders, Provider},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, StatusCode,
+};
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::Write,
+    future::Future,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Content type of the Prometheus text exposition format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// A gauge exported from the current conditions of a location
+struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: fn(&Conditions) -> Option<f64>,
+}
+
+const GAUGES: [Gauge; 9] = [
+    Gauge {
+        name: "weather_temperature_celsius",
+        help: "Air temperature",
+        value: |c| c.temp_c,
+    },
+    Gauge {
+        name: "weather_feels_like_celsius",
+        help: "Apparent temperature",
+        value: |c| c.feels_like_c,
+    },
+    Gauge {
+        name: "weather_humidity_percent",
+        help: "Relative humidity",
+        value: |c| c.humidity,
+    },
+    Gauge {
+        name: "weather_pressure_hectopascals",
+        help: "Atmospheric pressure",
+        value: |c| c.pressure_mb,
+    },
+    Gauge {
+        name: "weather_wind_speed_kilometers_per_hour",
+        help: "Wind speed",
+        value: |c| c.wind_kph,
+    },
+    Gauge {
+        name: "weather_wind_gust_kilometers_per_hour",
+        help: "Wind gust speed",
+        value: |c| c.gust_kph,
+    },
+    Gauge {
+        name: "weather_precipitation_millimeters",
+        help: "Precipitation amount",
+        value: |c| c.precip_mm,
+    },
+    Gauge {
+        name: "weather_cloud_cover_percent",
+        help: "Cloud cover",
+        value: |c| c.cloud_cover,
+    },
+    Gauge {
+        name: "weather_uv_index",
+        help: "UV index",
+        value: |c| c.uv,
+    },
+];
+
+/// What is known about a location since the exporter has started
+#[derive(Debug, Clone, Default)]
+struct LocationMetrics {
+    provider: String,
+    conditions: Option<Conditions>,
+    updated: Option<DateTime<Utc>>,
+    requests: u64,
+    request_seconds: f64,
+    errors: u64,
+}
+
+/// Latest conditions of the polled locations and their providers' request statistics
+#[derive(Debug, Default)]
+pub struct Metrics {
+    locations: Mutex<BTreeMap<String, LocationMetrics>>,
+}
+
+/// A polled location and the provider routed to for it
+pub struct Target {
+    pub location: String,
+    pub provider: AvailableProviders,
+    pub adapter: Arc<dyn Provider>,
+}
+
+/// Escapes a label value of the text format
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    fn update<F: FnOnce(&mut LocationMetrics)>(&self, target: &Target, f: F) {
+        let mut locations = self.locations.lock().expect("The metrics lock is poisoned");
+        let m = locations.entry(target.location.clone()).or_default();
+        m.provider = format!("{:?}", target.provider);
+        f(m);
+    }
+
+    /// Records a successful request, the conditions replace the previous ones
+    pub fn record_success(
+        &self,
+        target: &Target,
+        conditions: Conditions,
+        elapsed: Duration,
+        now: DateTime<Utc>,
+    ) {
+        self.update(target, |m| {
+            m.conditions = Some(conditions);
+            m.updated = Some(now);
+            m.requests += 1;
+            m.request_seconds += elapsed.as_secs_f64();
+        });
+    }
+
+    /// Records a failed request, the last known conditions are kept
+    pub fn record_failure(&self, target: &Target, elapsed: Duration) {
+        self.update(target, |m| {
+            m.requests += 1;
+            m.request_seconds += elapsed.as_secs_f64();
+            m.errors += 1;
+        });
+    }
+
+    /// Renders every metric in the Prometheus text format
+    /// Gauges are left out for locations that haven't had a value yet
+    pub fn render(&self) -> String {
+        let locations = self.locations.lock().expect("The metrics lock is poisoned");
+        let labels = |location: &str| {
+            format!(
+                "location=\"{}\",provider=\"{}\"",
+                label(location),
+                label(&locations[location].provider)
+            )
+        };
+        let mut out = String::new();
+        GAUGES.iter().for_each(|gauge| {
+            let _ = writeln!(out, "# HELP {} {}", gauge.name, gauge.help);
+            let _ = writeln!(out, "# TYPE {} gauge", gauge.name);
+            locations.iter().for_each(|(location, m)| {
+                if let Some(value) = m.conditions.as_ref().and_then(gauge.value) {
+                    let _ = writeln!(out, "{}{{{}}} {}", gauge.name, labels(location), value);
+                }
+            });
+        });
+        let _ = writeln!(
+            out,
+            "# HELP weather_last_update_timestamp_seconds Time of the last successful request"
+        );
+        let _ = writeln!(out, "# TYPE weather_last_update_timestamp_seconds gauge");
+        locations.iter().for_each(|(location, m)| {
+            if let Some(updated) = m.updated {
+                let _ = writeln!(
+                    out,
+                    "weather_last_update_timestamp_seconds{{{}}} {}",
+                    labels(location),
+                    updated.timestamp()
+                );
+            }
+        });
+        let _ = writeln!(
+            out,
+            "# HELP weather_provider_request_duration_seconds Latency of provider requests"
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE weather_provider_request_duration_seconds summary"
+        );
+        locations.iter().for_each(|(location, m)| {
+            let _ = writeln!(
+                out,
+                "weather_provider_request_duration_seconds_sum{{{}}} {}",
+                labels(location),
+                m.request_seconds
+            );
+            let _ = writeln!(
+                out,
+                "weather_provider_request_duration_seconds_count{{{}}} {}",
+                labels(location),
+                m.requests
+            );
+        });
+        let _ = writeln!(
+            out,
+            "# HELP weather_provider_errors_total Failed provider requests"
+        );
+        let _ = writeln!(out, "# TYPE weather_provider_errors_total counter");
+        locations.iter().for_each(|(location, m)| {
+            let _ = writeln!(
+                out,
+                "weather_provider_errors_total{{{}}} {}",
+                labels(location),
+                m.errors
+            );
+        });
+        out
+    }
+}
+
+/// Requests the current conditions of every location once, one after another
+pub async fn poll(targets: &[Target], metrics: &Metrics) {
+    for target in targets {
+        let started = Instant::now();
+        let command = WeatherCommand::new(target.location.as_str(), DateOffsetRepresentation::Now);
+        let conditions = target.adapter.run(command).await.and_then(|report| {
+            report
+                .sections()
+                .iter()
+                .find_map(|s| s.conditions().cloned())
+                .context("The report has no conditions")
+        });
+        match conditions {
+            Ok(conditions) => {
+                metrics.record_success(target, conditions, started.elapsed(), Utc::now())
+            }
+            Err(e) => {
+                log::warn!("Couldn't poll {}: {:#}", target.location, e);
+                metrics.record_failure(target, started.elapsed());
+            }
+        }
+    }
+}
+
+/// Serves `/metrics` on a bound listener until the shutdown future resolves
+pub async fn serve<F>(listener: TcpListener, metrics: Arc<Metrics>, shutdown: F) -> Result<()>
+where
+    F: Future<Output = ()>,
+{
+    listener
+        .set_nonblocking(true)
+        .context("Couldn't configure the listener")?;
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response = match request.uri().path() {
+                    "/metrics" => Response::builder()
+                        .header(CONTENT_TYPE, TEXT_FORMAT)
+                        .body(Body::from(metrics.render())),
+                    _ => Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::from("Metrics are served at /metrics\n")),
+                };
+                async move {
+                    Ok::<_, Infallible>(
+                        response.expect("A response with valid parts couldn't be built"),
+                    )
+                }
+            }))
+        }
+    });
+    hyper::Server::from_tcp(listener)
+        .context("Couldn't start the exporter")?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context("The exporter has failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::weatherapi::WeatherAPIProvider;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const CURRENT_MOCK: &str = include_str!("./providers/weatherapi/fixtures/current.json");
+
+    #[tokio::test]
+    async fn test_poll_and_render() {
+        let upstream = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/current.json"))
+            .and(query_param("q", "Zaporizhzhia"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/json")
+                    .set_body_string(CURRENT_MOCK),
+            )
+            .mount(&upstream)
+            .await;
+        let provider: Arc<dyn Provider> = Arc::new(
+            WeatherAPIProvider::default()
+                .with_base_url(format!("http://{}", upstream.address()))
+                .with_api_key("22222"),
+        );
+        let metrics = Metrics::default();
+        let targets: Vec<Target> = ["Zaporizhzhia", "Nowhere \"A\""]
+            .into_iter()
+            .map(|location| Target {
+                location: location.to_owned(),
+                provider: AvailableProviders::WeatherAPI,
+                adapter: provider.clone(),
+            })
+            .collect();
+        poll(&targets, &metrics).await;
+
+        let rendered = metrics.render();
+        let labels = "location=\"Zaporizhzhia\",provider=\"WeatherAPI\"";
+        assert!(rendered.contains("# TYPE weather_temperature_celsius gauge\n"));
+        assert!(rendered.contains(&format!("weather_temperature_celsius{{{}}} 7.5\n", labels)));
+        assert!(rendered.contains(&format!(
+            "weather_wind_speed_kilometers_per_hour{{{}}} 23.4\n",
+            labels
+        )));
+        assert!(rendered.contains(&format!("weather_provider_errors_total{{{}}} 0\n", labels)));
+        let failed = "location=\"Nowhere \\\"A\\\"\",provider=\"WeatherAPI\"";
+        assert!(rendered.contains(&format!("weather_provider_errors_total{{{}}} 1\n", failed)));
+        assert!(rendered.contains(&format!(
+            "weather_provider_request_duration_seconds_count{{{}}} 1\n",
+            failed
+        )));
+        assert!(!rendered.contains(&format!("weather_temperature_celsius{{{}}}", failed)));
+    }
+}
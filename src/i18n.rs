@@ -0,0 +1,388 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Language of report labels, dates and, where a provider can localize them, condition texts
+#[derive(Serialize, Deserialize, Clone, Copy, ValueEnum, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+    Uk,
+}
+
+/// Message catalogs keyed by the English text, texts missing from a catalog stay in English
+const DE: &[(&str, &str)] = &[
+    ("Condition", "Wetterlage"),
+    ("Temperature", "Temperatur"),
+    ("Average temp.", "Durchschn. Temp."),
+    ("Min. temp.", "Min. Temp."),
+    ("Max. temp.", "Max. Temp."),
+    ("Feels like", "Gefühlt"),
+    ("Dew point", "Taupunkt"),
+    ("Humidity", "Luftfeuchtigkeit"),
+    ("Pressure", "Luftdruck"),
+    ("Precipitation", "Niederschlag"),
+    ("Chance of precipitation", "Niederschlagsrisiko"),
+    ("UV index", "UV-Index"),
+    ("Visibility", "Sichtweite"),
+    ("Wind speed", "Windgeschwindigkeit"),
+    ("Wind gusts", "Windböen"),
+    ("Wind direction", "Windrichtung"),
+    ("Cloud cover", "Bewölkung"),
+    ("Snow", "Schnee"),
+    ("Temperature trend", "Temperaturverlauf"),
+    ("Alert", "Warnung"),
+    ("Severity", "Schweregrad"),
+    ("Area", "Gebiet"),
+    ("Start", "Beginn"),
+    ("End", "Ende"),
+    ("Description", "Beschreibung"),
+    ("Weather alerts", "Wetterwarnungen"),
+    ("Air quality", "Luftqualität"),
+    ("Category", "Kategorie"),
+    ("US EPA index", "US-EPA-Index"),
+    ("UK DEFRA index", "UK-DEFRA-Index"),
+    ("Astronomy", "Astronomie"),
+    ("Sunrise", "Sonnenaufgang"),
+    ("Sunset", "Sonnenuntergang"),
+    ("Day length", "Tageslänge"),
+    ("Moonrise", "Mondaufgang"),
+    ("Moonset", "Monduntergang"),
+    ("Moon phase", "Mondphase"),
+    ("Moon illumination", "Mondbeleuchtung"),
+    ("Marine", "Meer"),
+    ("Tides", "Gezeiten"),
+    ("High tide", "Hochwasser"),
+    ("Low tide", "Niedrigwasser"),
+    ("Significant wave height", "Signifikante Wellenhöhe"),
+    ("Swell height", "Dünungshöhe"),
+    ("Swell direction", "Dünungsrichtung"),
+    ("Swell period", "Dünungsperiode"),
+    ("Water temp.", "Wassertemp."),
+    ("Statistics", "Statistik"),
+    ("to", "bis"),
+    ("Days", "Tage"),
+    ("Mean temp.", "Mittl. Temp."),
+    ("Total precipitation", "Gesamtniederschlag"),
+    ("Rainy days", "Regentage"),
+    ("Hottest day", "Heißester Tag"),
+    ("Coldest day", "Kältester Tag"),
+    ("Rain", "Regen"),
+    ("Rain between", "Regen zwischen"),
+    ("and", "und"),
+    ("Will it rain", "Wird es regnen"),
+    ("Yes, take an umbrella", "Ja, nimm einen Schirm mit"),
+    ("No", "Nein"),
+    ("Wet hours", "Nasse Stunden"),
+    ("Wettest hour", "Nasseste Stunde"),
+    ("Status", "Aktuell"),
+    ("current", "aktuell"),
+    ("forecast", "Vorhersage"),
+    ("history", "Rückblick"),
+    ("range", "Zeitraum"),
+    ("hourly", "stündlich"),
+    ("daily", "täglich"),
+];
+
+const FR: &[(&str, &str)] = &[
+    ("Condition", "Conditions"),
+    ("Temperature", "Température"),
+    ("Average temp.", "Temp. moyenne"),
+    ("Min. temp.", "Temp. min."),
+    ("Max. temp.", "Temp. max."),
+    ("Feels like", "Ressenti"),
+    ("Dew point", "Point de rosée"),
+    ("Humidity", "Humidité"),
+    ("Pressure", "Pression"),
+    ("Precipitation", "Précipitations"),
+    ("Chance of precipitation", "Risque de précipitations"),
+    ("UV index", "Indice UV"),
+    ("Visibility", "Visibilité"),
+    ("Wind speed", "Vitesse du vent"),
+    ("Wind gusts", "Rafales"),
+    ("Wind direction", "Direction du vent"),
+    ("Cloud cover", "Couverture nuageuse"),
+    ("Snow", "Neige"),
+    ("Temperature trend", "Évolution de la température"),
+    ("Alert", "Alerte"),
+    ("Severity", "Gravité"),
+    ("Area", "Zone"),
+    ("Start", "Début"),
+    ("End", "Fin"),
+    ("Description", "Description"),
+    ("Weather alerts", "Alertes météo"),
+    ("Air quality", "Qualité de l'air"),
+    ("Category", "Catégorie"),
+    ("US EPA index", "Indice US EPA"),
+    ("UK DEFRA index", "Indice UK DEFRA"),
+    ("Astronomy", "Astronomie"),
+    ("Sunrise", "Lever du soleil"),
+    ("Sunset", "Coucher du soleil"),
+    ("Day length", "Durée du jour"),
+    ("Moonrise", "Lever de la lune"),
+    ("Moonset", "Coucher de la lune"),
+    ("Moon phase", "Phase de la lune"),
+    ("Moon illumination", "Illumination de la lune"),
+    ("Marine", "Mer"),
+    ("Tides", "Marées"),
+    ("High tide", "Marée haute"),
+    ("Low tide", "Marée basse"),
+    (
+        "Significant wave height",
+        "Hauteur significative des vagues",
+    ),
+    ("Swell height", "Hauteur de la houle"),
+    ("Swell direction", "Direction de la houle"),
+    ("Swell period", "Période de la houle"),
+    ("Water temp.", "Temp. de l'eau"),
+    ("Statistics", "Statistiques"),
+    ("to", "au"),
+    ("Days", "Jours"),
+    ("Mean temp.", "Temp. moyenne"),
+    ("Total precipitation", "Précipitations totales"),
+    ("Rainy days", "Jours de pluie"),
+    ("Hottest day", "Jour le plus chaud"),
+    ("Coldest day", "Jour le plus froid"),
+    ("Rain", "Pluie"),
+    ("Rain between", "Pluie entre"),
+    ("and", "et"),
+    ("Will it rain", "Va-t-il pleuvoir"),
+    ("Yes, take an umbrella", "Oui, prenez un parapluie"),
+    ("No", "Non"),
+    ("Wet hours", "Heures humides"),
+    ("Wettest hour", "Heure la plus humide"),
+    ("Status", "Actuellement"),
+    ("current", "actuel"),
+    ("forecast", "prévisions"),
+    ("history", "historique"),
+    ("range", "période"),
+    ("hourly", "horaire"),
+    ("daily", "quotidien"),
+];
+
+const ES: &[(&str, &str)] = &[
+    ("Condition", "Estado"),
+    ("Temperature", "Temperatura"),
+    ("Average temp.", "Temp. media"),
+    ("Min. temp.", "Temp. mín."),
+    ("Max. temp.", "Temp. máx."),
+    ("Feels like", "Sensación térmica"),
+    ("Dew point", "Punto de rocío"),
+    ("Humidity", "Humedad"),
+    ("Pressure", "Presión"),
+    ("Precipitation", "Precipitación"),
+    ("Chance of precipitation", "Probabilidad de precipitación"),
+    ("UV index", "Índice UV"),
+    ("Visibility", "Visibilidad"),
+    ("Wind speed", "Velocidad del viento"),
+    ("Wind gusts", "Ráfagas"),
+    ("Wind direction", "Dirección del viento"),
+    ("Cloud cover", "Nubosidad"),
+    ("Snow", "Nieve"),
+    ("Temperature trend", "Tendencia de la temperatura"),
+    ("Alert", "Alerta"),
+    ("Severity", "Gravedad"),
+    ("Area", "Zona"),
+    ("Start", "Inicio"),
+    ("End", "Fin"),
+    ("Description", "Descripción"),
+    ("Weather alerts", "Alertas meteorológicas"),
+    ("Air quality", "Calidad del aire"),
+    ("Category", "Categoría"),
+    ("US EPA index", "Índice US EPA"),
+    ("UK DEFRA index", "Índice UK DEFRA"),
+    ("Astronomy", "Astronomía"),
+    ("Sunrise", "Salida del sol"),
+    ("Sunset", "Puesta del sol"),
+    ("Day length", "Duración del día"),
+    ("Moonrise", "Salida de la luna"),
+    ("Moonset", "Puesta de la luna"),
+    ("Moon phase", "Fase lunar"),
+    ("Moon illumination", "Iluminación lunar"),
+    ("Marine", "Mar"),
+    ("Tides", "Mareas"),
+    ("High tide", "Marea alta"),
+    ("Low tide", "Marea baja"),
+    (
+        "Significant wave height",
+        "Altura significativa de las olas",
+    ),
+    ("Swell height", "Altura del oleaje"),
+    ("Swell direction", "Dirección del oleaje"),
+    ("Swell period", "Periodo del oleaje"),
+    ("Water temp.", "Temp. del agua"),
+    ("Statistics", "Estadísticas"),
+    ("to", "a"),
+    ("Days", "Días"),
+    ("Mean temp.", "Temp. media"),
+    ("Total precipitation", "Precipitación total"),
+    ("Rainy days", "Días de lluvia"),
+    ("Hottest day", "Día más caluroso"),
+    ("Coldest day", "Día más frío"),
+    ("Rain", "Lluvia"),
+    ("Rain between", "Lluvia entre"),
+    ("and", "y"),
+    ("Will it rain", "Lloverá"),
+    ("Yes, take an umbrella", "Sí, lleva paraguas"),
+    ("No", "No"),
+    ("Wet hours", "Horas húmedas"),
+    ("Wettest hour", "Hora más húmeda"),
+    ("Status", "Ahora"),
+    ("current", "actual"),
+    ("forecast", "pronóstico"),
+    ("history", "historial"),
+    ("range", "periodo"),
+    ("hourly", "por horas"),
+    ("daily", "diario"),
+];
+
+const UK: &[(&str, &str)] = &[
+    ("Condition", "Погода"),
+    ("Temperature", "Температура"),
+    ("Average temp.", "Середня темп."),
+    ("Min. temp.", "Мін. темп."),
+    ("Max. temp.", "Макс. темп."),
+    ("Feels like", "Відчувається як"),
+    ("Dew point", "Точка роси"),
+    ("Humidity", "Вологість"),
+    ("Pressure", "Тиск"),
+    ("Precipitation", "Опади"),
+    ("Chance of precipitation", "Імовірність опадів"),
+    ("UV index", "УФ-індекс"),
+    ("Visibility", "Видимість"),
+    ("Wind speed", "Швидкість вітру"),
+    ("Wind gusts", "Пориви вітру"),
+    ("Wind direction", "Напрямок вітру"),
+    ("Cloud cover", "Хмарність"),
+    ("Snow", "Сніг"),
+    ("Temperature trend", "Динаміка температури"),
+    ("Alert", "Попередження"),
+    ("Severity", "Рівень небезпеки"),
+    ("Area", "Район"),
+    ("Start", "Початок"),
+    ("End", "Кінець"),
+    ("Description", "Опис"),
+    ("Weather alerts", "Погодні попередження"),
+    ("Air quality", "Якість повітря"),
+    ("Category", "Категорія"),
+    ("US EPA index", "Індекс US EPA"),
+    ("UK DEFRA index", "Індекс UK DEFRA"),
+    ("Astronomy", "Астрономія"),
+    ("Sunrise", "Схід сонця"),
+    ("Sunset", "Захід сонця"),
+    ("Day length", "Тривалість дня"),
+    ("Moonrise", "Схід місяця"),
+    ("Moonset", "Захід місяця"),
+    ("Moon phase", "Фаза місяця"),
+    ("Moon illumination", "Освітленість місяця"),
+    ("Marine", "Море"),
+    ("Tides", "Припливи"),
+    ("High tide", "Приплив"),
+    ("Low tide", "Відплив"),
+    ("Significant wave height", "Значуща висота хвиль"),
+    ("Swell height", "Висота зибу"),
+    ("Swell direction", "Напрямок зибу"),
+    ("Swell period", "Період зибу"),
+    ("Water temp.", "Темп. води"),
+    ("Statistics", "Статистика"),
+    ("to", "по"),
+    ("Days", "Днів"),
+    ("Mean temp.", "Середня темп."),
+    ("Total precipitation", "Сума опадів"),
+    ("Rainy days", "Дощові дні"),
+    ("Hottest day", "Найтепліший день"),
+    ("Coldest day", "Найхолодніший день"),
+    ("Rain", "Дощ"),
+    ("Rain between", "Дощ між"),
+    ("and", "і"),
+    ("Will it rain", "Чи буде дощ"),
+    ("Yes, take an umbrella", "Так, візьміть парасольку"),
+    ("No", "Ні"),
+    ("Wet hours", "Вологі години"),
+    ("Wettest hour", "Найвологіша година"),
+    ("Status", "Зараз"),
+    ("current", "зараз"),
+    ("forecast", "прогноз"),
+    ("history", "архів"),
+    ("range", "період"),
+    ("hourly", "погодинний"),
+    ("daily", "щоденний"),
+];
+
+impl Lang {
+    /// ISO 639-1 code, as providers take it
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+            Lang::Fr => "fr",
+            Lang::Es => "es",
+            Lang::Uk => "uk",
+        }
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => &[],
+            Lang::De => DE,
+            Lang::Fr => FR,
+            Lang::Es => ES,
+            Lang::Uk => UK,
+        }
+    }
+
+    /// Translation of an English text, or the text itself when there is none
+    pub fn tr(&self, text: &'static str) -> &'static str {
+        self.catalog()
+            .iter()
+            .find(|(english, _)| *english == text)
+            .map(|(_, translated)| *translated)
+            .unwrap_or(text)
+    }
+
+    /// Format of a date, e.g. `31/03/2023` or `31.03.2023`
+    pub fn date_format(&self) -> &'static str {
+        match self {
+            Lang::En | Lang::Fr | Lang::Es => "%d/%m/%Y",
+            Lang::De | Lang::Uk => "%d.%m.%Y",
+        }
+    }
+
+    /// Format of a time of day, e.g. `06:30 PM` or `18:30`
+    pub fn time_format(&self) -> &'static str {
+        match self {
+            Lang::En => "%I:%M %p",
+            Lang::De | Lang::Fr | Lang::Es | Lang::Uk => "%H:%M",
+        }
+    }
+
+    /// Format of a date with a time of day, only English uses a 12-hour clock
+    pub fn datetime_format(&self) -> &'static str {
+        match self {
+            Lang::En => "%d/%m/%Y %I:%M %p",
+            Lang::Fr | Lang::Es => "%d/%m/%Y %H:%M",
+            Lang::De | Lang::Uk => "%d.%m.%Y %H:%M",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs() {
+        assert_eq!(Lang::En.tr("Wind direction"), "Wind direction");
+        assert_eq!(Lang::De.tr("Wind direction"), "Windrichtung");
+        assert_eq!(Lang::Uk.tr("Feels like"), "Відчувається як");
+        assert_eq!(Lang::Fr.tr("Not in a catalog"), "Not in a catalog");
+        // every catalog translates the same texts
+        let keys = |lang: Lang| lang.catalog().iter().map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys(Lang::De), keys(Lang::Fr));
+        assert_eq!(keys(Lang::De), keys(Lang::Es));
+        assert_eq!(keys(Lang::De), keys(Lang::Uk));
+    }
+}
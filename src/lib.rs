@@ -1,7 +1,25 @@
+pub mod air_quality;
+pub mod alerts;
 pub mod args;
+pub mod astronomy;
+pub mod batch;
+pub mod capabilities;
 pub mod command;
+pub mod conditions;
 pub mod configuration;
+pub mod export;
+pub mod exporter;
+pub mod i18n;
+pub mod marine;
+pub mod notify;
 pub mod providers;
+pub mod rain;
 pub mod report;
+pub mod server;
+pub mod stats;
+pub mod status;
+pub mod templates;
+pub mod terminal;
 pub mod types;
 pub mod util;
+pub mod watch;
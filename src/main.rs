@@ -1,11 +1,28 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use dialoguer::Password;
-use weather::configuration::{open_or_default, Configuration};
-use weather::providers::aerisweather::AerisWeatherProvider;
-use weather::providers::weatherapi::WeatherAPIProvider;
-use weather::types::{AvailableProviders, Provider};
-use weather::{args, util};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveTime, Timelike, Utc};
+use clap::{Parser, ValueEnum};
+use dialoguer::{Confirm, Editor, Password};
+use std::fs;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use weather::capabilities::{self, Feature};
+use weather::command::{DateOffsetRepresentation, DayRange, WeatherCommand};
+use weather::conditions::{Field, View};
+use weather::configuration::{
+    obtain_default_os_config_path, open_or_default, parse_configuration, write_config_file,
+    ConfigFormat, ConfigLock, Configuration, Profile, DEFAULT_PROFILE,
+};
+use weather::i18n::Lang;
+use weather::providers;
+use weather::report::Report;
+use weather::server::Server;
+use weather::types::{AvailableProviders, Clock, Provider, TimeZoneChoice};
+use weather::{
+    air_quality, alerts, args, astronomy, batch, export, exporter, marine, notify, rain, stats,
+    status, templates, terminal, util, watch,
+};
 
 /// API key prompt
 fn get_api_key<S: Into<String>>(prompt: S) -> Result<String> {
@@ -18,7 +35,14 @@ fn get_api_key<S: Into<String>>(prompt: S) -> Result<String> {
 }
 
 /// Handles provider configuration
-fn handle_condigure(configuration: &mut Configuration, provider: AvailableProviders) -> Result<()> {
+fn handle_condigure(
+    configuration: &mut Profile,
+    provider: AvailableProviders,
+    history_days: Option<u32>,
+) -> Result<()> {
+    if history_days.is_some() && provider != AvailableProviders::WeatherAPI {
+        bail!("Only WeatherAPI's history depth depends on the plan");
+    }
     match provider {
         // AvailableProviders::AccuWeather => {
         // let api_key = get_api_key("Please enter an API key for AccuWeather")?;
@@ -27,6 +51,9 @@ fn handle_condigure(configuration: &mut Configuration, provider: AvailableProvid
         AvailableProviders::WeatherAPI => {
             let api_key = get_api_key("Please enter an API key for WeatherAPI")?;
             configuration.set_weatherapi_api_key(Some(api_key));
+            if history_days.is_some() {
+                configuration.set_weatherapi_history_days(history_days);
+            }
         }
         AvailableProviders::AerisWeather => {
             let client_id = get_api_key("Please enter a client id for AerisWeather")?;
@@ -38,78 +65,971 @@ fn handle_condigure(configuration: &mut Configuration, provider: AvailableProvid
 }
 
 /// Handles the configuration of a default provider selection
-fn handle_change_default_provider(configuration: &mut Configuration, provider: AvailableProviders) {
+fn handle_change_default_provider(configuration: &mut Profile, provider: AvailableProviders) {
     println!("Have set the new default provider {:?}", provider);
     configuration.set_default_provider(Some(provider));
 }
 
+/// Picks a provider that offers a feature and understands the location, before anything is sent
+fn route_feature(
+    configuration: &Profile,
+    location: &str,
+    feature: Feature,
+) -> Result<Arc<dyn Provider>> {
+    let (_, provider) = providers::route(configuration, |c| {
+        c.check_feature(feature)?;
+        c.check_location(location)
+    })?;
+    Ok(provider)
+}
+
+/// Handles the order in which configured providers are preferred
+fn handle_prefer(configuration: &mut Profile, providers: Vec<AvailableProviders>) {
+    configuration.set_provider_order(providers);
+    println!(
+        "Providers are now preferred in this order: {}",
+        configuration
+            .provider_preference()
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Handles the capability matrix of every provider
+fn handle_providers(configuration: &Profile) {
+    let providers: Vec<_> = AvailableProviders::value_variants()
+        .iter()
+        .map(|p| {
+            (
+                format!("{:?}", p),
+                providers::capabilities(configuration, p),
+            )
+        })
+        .collect();
+    print!("{}", capabilities::matrix(&providers));
+}
+
+/// Routes the command of `get` arguments, with `dated` the days are counted on the location's calendar
+async fn route_get<B, F>(
+    adapters: &providers::Adapters,
+    dated: bool,
+    build: B,
+    check: F,
+) -> Result<(WeatherCommand, DateTime<FixedOffset>, Arc<dyn Provider>)>
+where
+    B: Fn(DateTime<FixedOffset>) -> Result<WeatherCommand>,
+    F: Fn(&capabilities::Capabilities, &WeatherCommand, NaiveDate) -> Result<(), String>,
+{
+    if dated {
+        return adapters.route_dated(build, check).await;
+    }
+    let now = Utc::now().fixed_offset();
+    let command = build(now)?;
+    let (_, provider) = adapters.route(|c| check(c, &command, now.date_naive()))?;
+    Ok((command, now, provider))
+}
+
 /// Handles the weather provider interaction and report generation
-async fn handle_get(configuration: &Configuration, get_action: args::GetArgs) -> Result<()> {
-    let weather_command = util::parse_get_action(get_action);
+async fn handle_get(
+    configuration: &Profile,
+    get_action: args::GetArgs,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+    templates: &Path,
+) -> Result<()> {
+    let with_air_quality = get_action.aqi;
+    let template = get_action.template.clone();
+    let output = get_action.output;
+    let out_file = get_action.out_file.clone();
+    let with_astronomy = get_action.astro;
+    let terminal = if get_action.plain || output.is_some() || out_file.is_some() {
+        None
+    } else {
+        terminal::Terminal::detect(get_action.icons)
+    };
+    let build = |now| {
+        util::parse_get_action(&get_action, configuration, now)
+            .map(|command| command.with_tz(tz).with_lang(configuration.lang(lang)))
+    };
+    let check = |c: &capabilities::Capabilities, command: &WeatherCommand, today| {
+        c.check_command(command, today)?;
+        if with_air_quality {
+            c.check_feature(Feature::AirQuality)?;
+        }
+        if with_astronomy {
+            c.check_feature(Feature::Astronomy)?;
+        }
+        Ok(())
+    };
+    // days of a range and of astronomy data are counted on the location's calendar
+    let dated = get_action.from.is_some() || get_action.to.is_some() || with_astronomy;
+    let (weather_command, now, provider) = route_get(
+        &providers::Adapters::new(configuration),
+        dated,
+        build,
+        check,
+    )
+    .await?;
     log::debug!("Weather command: {:?}", weather_command);
-    let provider_tag = configuration.default_provider.clone().context(
-            "You haven't selected a default provider yet, please run >weather configure <provider> first")?;
-    let report = match provider_tag {
-        // AvailableProviders::AccuWeather => {
-        // let api_key = configuration
-        // .accuweather_api_key
-        // .clone()
-        // .context("You haven't set AccuWeather API key")?;
-        // AccuWeatherProvider::new()
-        // .with_api_key(api_key)
-        // .run(weather_command)
-        // .await
-        // }
-        AvailableProviders::WeatherAPI => {
-            let api_key = configuration
-                .weatherapi_api_key
-                .clone()
-                .context("You haven't set WeatherAPI API key")?;
-            WeatherAPIProvider::default()
-                .with_api_key(api_key)
-                .run(weather_command)
-                .await
+    // alerts come with the report, they are only active at the present and in the future
+    let past = weather_command.date.is_past(now.date_naive());
+    let weather_command = weather_command.with_alerts(!past);
+    let location = weather_command.location.clone();
+    let view = weather_command.view();
+    let air_quality = async {
+        if with_air_quality {
+            Some(provider.air_quality(&location).await)
+        } else {
+            None
         }
-        AvailableProviders::AerisWeather => {
-            let client_id = configuration
-                .aerisweather_client_id
-                .clone()
-                .context("You haven't set AerisWeather client id")?;
-            let client_secret = configuration
-                .aerisweather_client_secret
-                .clone()
-                .context("You haven't set AerisWeather client secret")?;
-            AerisWeatherProvider::default()
-                .with_credentials(client_id, client_secret)
-                .run(weather_command)
+    };
+    let day = weather_command.date.to_chrono(now).date_naive();
+    let astronomy = async {
+        if with_astronomy {
+            Some(provider.astronomy(&location, day).await)
+        } else {
+            None
+        }
+    };
+    let (report, air_quality, astronomy) =
+        futures::join!(provider.run(weather_command), air_quality, astronomy);
+    let mut report = report.context("Failed to build a report")?;
+    match air_quality {
+        Some(Ok(air_quality)) => air_quality::add_to_report(&mut report, air_quality, view.lang),
+        Some(Err(e)) => log::warn!("Air quality is unavailable: {:#}", e),
+        None => {}
+    }
+    match astronomy {
+        Some(Ok(astronomy)) => astronomy::add_to_report(&mut report, astronomy, view.lang),
+        Some(Err(e)) => log::warn!("Astronomy data is unavailable: {:#}", e),
+        None => {}
+    }
+    let rendered = match (template, output, terminal) {
+        (Some(name), _, _) => templates::render(templates, &name, &location, &report)? + "\n",
+        (None, Some(output), _) => export::render(&report, &location, output, &view)?,
+        (None, None, Some(terminal)) => terminal::render(&report, &terminal, &view),
+        (None, None, None) => format!("{}\n", report),
+    };
+    match out_file {
+        Some(path) => {
+            fs::write(&path, rendered)
+                .with_context(|| format!("Couldn't write the report to {}", path.display()))?;
+            println!("Have written the report to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Handles one-line output for status bars, recent results are reused from a cache next to the configuration
+async fn handle_status(
+    configuration: &Configuration,
+    profile: Option<&str>,
+    get_action: args::GetArgs,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let template = status::Template::parse(
+        get_action
+            .format
+            .as_deref()
+            .unwrap_or(status::DEFAULT_TEMPLATE),
+    )?;
+    let waybar = get_action.waybar;
+    let icons = get_action.icons;
+    let max_age =
+        chrono::Duration::from_std(get_action.max_age).context("The maximum age is too long")?;
+    let key_profile = profile.unwrap_or(DEFAULT_PROFILE).to_owned();
+    let profile = configuration.profile(profile)?;
+    let build = |now| {
+        util::parse_get_action(&get_action, profile, now)
+            .map(|command| command.with_tz(tz).with_lang(profile.lang(lang)))
+    };
+    let command = build(Utc::now().fixed_offset())?;
+    let location = command.location.clone();
+    let key = format!(
+        "{}|{}|{:?}|{:?}|{:?}|{:?}",
+        key_profile, command.location, command.date, command.units, command.fields, command.lang
+    );
+    let cache_path = configuration
+        .config_path()?
+        .with_file_name("status_cache.json");
+    let mut cache = status::Cache::load(&cache_path);
+    let now = Utc::now();
+    let entry = match cache.get(&key, now, max_age).cloned() {
+        Some(entry) => entry,
+        None => {
+            // routed like `get`, so that both pick the same provider for the same arguments
+            let dated = get_action.from.is_some() || get_action.to.is_some();
+            let (command, _, provider) = route_get(
+                &providers::Adapters::new(profile),
+                dated,
+                build,
+                |c, command, today| c.check_command(command, today),
+            )
+            .await?;
+            let report = provider
+                .run(command)
                 .await
+                .context("Failed to build a report")?;
+            let conditions = report
+                .sections()
+                .iter()
+                .find_map(|s| s.conditions().cloned())
+                .context("The report has no conditions")?;
+            let entry = status::Entry {
+                fetched: now,
+                conditions,
+                report: report.to_string(),
+            };
+            cache.insert(key, entry.clone());
+            if let Err(e) = cache.save(&cache_path, now) {
+                log::warn!("Couldn't cache the result: {:#}", e);
+            }
+            entry
         }
+    };
+    let line = template.render(&location, &entry.conditions, profile.units, icons);
+    if waybar {
+        let waybar = status::Waybar::new(line, &entry.report, &entry.conditions);
+        println!("{}", serde_json::to_string(&waybar)?);
+    } else {
+        println!("{}", line);
     }
-    .context("Failed to build a report")?;
+    Ok(())
+}
+
+/// Handles air quality lookup
+async fn handle_air(
+    configuration: &Profile,
+    location: Option<String>,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let lang = configuration.lang(lang);
+    let location = util::resolve_location(location, configuration)?;
+    let provider = route_feature(configuration, &location, Feature::AirQuality)?;
+    let air_quality = provider
+        .air_quality(&location)
+        .await
+        .context("Failed to obtain air quality")?;
+    let mut report = Report::new(format!("{} - {}", lang.tr("Air quality"), location));
+    air_quality::add_to_report(&mut report, air_quality, lang);
     println!("{}", report);
     Ok(())
 }
 
+/// Handles sun and moon data lookup
+async fn handle_astro(
+    configuration: &Profile,
+    location: Option<String>,
+    date: Option<String>,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let lang = configuration.lang(lang);
+    let location = util::resolve_location(location, configuration)?;
+    let provider = route_feature(configuration, &location, Feature::Astronomy)?;
+    let now = match date
+        .as_deref()
+        .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+    {
+        Some(Ok(_)) => Utc::now().fixed_offset(),
+        // days relative to today are counted on the location's calendar
+        _ => providers::location_now(provider.as_ref(), &location).await,
+    };
+    let date = util::parse_day_arg(date.as_deref(), now)?;
+    let astronomy = provider
+        .astronomy(&location, date)
+        .await
+        .context("Failed to obtain astronomy data")?;
+    let mut report = Report::new(format!("{} - {}", lang.tr("Astronomy"), location));
+    astronomy::add_to_report(&mut report, astronomy, lang);
+    println!("{}", report);
+    Ok(())
+}
+
+/// Handles sea state and tides lookup
+async fn handle_marine(
+    configuration: &Profile,
+    location: Option<String>,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let lang = configuration.lang(lang);
+    let location = util::resolve_location(location, configuration)?;
+    let provider = route_feature(configuration, &location, Feature::Marine)?;
+    let marine = provider
+        .marine(&location)
+        .await
+        .context("Failed to obtain marine data")?;
+    if marine.conditions.is_empty() && marine.tides.is_empty() {
+        println!("There is no marine data for {}", location);
+        return Ok(());
+    }
+    let mut report = Report::new(format!("{} - {}", lang.tr("Marine"), location));
+    let clock = Clock::new(None, tz).with_lang(lang);
+    marine::add_to_report(&mut report, marine, &clock);
+    println!("{}", report);
+    Ok(())
+}
+
+/// Handles the "will it rain" question, returns whether it will
+async fn handle_rain(
+    configuration: &Profile,
+    location: Option<String>,
+    from: Option<NaiveTime>,
+    to: NaiveTime,
+    lang: Option<Lang>,
+) -> Result<bool> {
+    let location = util::resolve_location(location, configuration)?;
+    let provider = route_feature(configuration, &location, Feature::Hourly)?;
+    let forecast = provider
+        .hourly(&location)
+        .await
+        .context("Failed to obtain the hourly forecast")?;
+    // hours that have passed can't bring rain anymore
+    let from = match from {
+        Some(from) => from,
+        None => {
+            let now = Utc::now().with_timezone(&forecast.utc_offset);
+            NaiveTime::from_hms_opt(now.hour(), 0, 0).context("Bad hour")?
+        }
+    };
+    if from >= to {
+        bail!("The time window has to start before it ends");
+    }
+    let window = rain::Window::new(from, to);
+    let verdict = rain::assess(&forecast, &window);
+    let raining = verdict.rain;
+    let view = View::new(Vec::new(), configuration.units).with_lang(configuration.lang(lang));
+    let mut report = Report::new(format!("{} - {}", view.lang.tr("Rain"), location));
+    rain::add_to_report(&mut report, verdict, &window, &forecast, &view);
+    println!("{}", report);
+    Ok(raining)
+}
+
+/// Handles historical statistics of a range of past days
+async fn handle_stats(
+    configuration: &Profile,
+    location: Option<String>,
+    from: &str,
+    to: &str,
+    csv: bool,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let location = util::resolve_location(location, configuration)?;
+    let range_at = |now: DateTime<FixedOffset>| -> Result<DayRange> {
+        let from = util::parse_day_arg(Some(from), now)?;
+        let to = util::parse_day_arg(Some(to), now)?;
+        let range = DayRange::new(from, to).context("The range has to start before it ends")?;
+        if range.to >= now.date_naive() {
+            bail!("Statistics are only available for past days, the range has to end before today");
+        }
+        Ok(range)
+    };
+    let history = |now| {
+        range_at(now).map(|range| {
+            WeatherCommand::new(location.as_str(), DateOffsetRepresentation::DayRange(range))
+        })
+    };
+    let (_, now, provider) = providers::Adapters::new(configuration)
+        .route_dated(history, |c, history, today| c.check_command(history, today))
+        .await?;
+    let range = range_at(now)?;
+    let days = provider
+        .history(&location, range)
+        .await
+        .context("Failed to obtain the daily history")?;
+    let summary = stats::summarize(range, &days);
+    if csv {
+        print!("{}", stats::to_csv(&location, &summary));
+    } else {
+        let lang = configuration.lang(lang);
+        let mut report = Report::new(format!("{} - {}", lang.tr("Statistics"), location));
+        stats::add_to_report(&mut report, &summary, configuration.units, lang);
+        println!("{}", report);
+    }
+    Ok(())
+}
+
+/// Queries the sites of a CSV file, a failed site is reported in its row and the others carry on
+async fn handle_batch(
+    configuration: &Profile,
+    batch_args: args::BatchArgs,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let input = fs::read_to_string(&batch_args.input)
+        .with_context(|| format!("Couldn't read {}", batch_args.input.display()))?;
+    let sites = batch::parse_sites(&input)?;
+    let lang = configuration.lang(lang);
+    let command = |site: &batch::Site| -> Result<WeatherCommand> {
+        if site.location.is_empty() {
+            bail!("The location is missing");
+        }
+        let date = batch::parse_date(site.date.as_deref())?;
+        Ok(WeatherCommand::new(site.location.as_str(), date)
+            .with_units(configuration.units)
+            .with_tz(tz)
+            .with_lang(lang))
+    };
+    let today = Utc::now().date_naive();
+    // sites share the adapters, so that every request to a provider counts towards its rate
+    let adapters = providers::Adapters::rate_limited(configuration, batch_args.rate);
+    let resolve = |site: &batch::Site, command: &WeatherCommand| match site.provider.as_deref() {
+        Some(name) => {
+            let provider = batch::parse_provider(name)?;
+            let adapter = adapters.get(&provider)?;
+            adapter
+                .capabilities()
+                .check_command(command, today)
+                .map_err(|reason| anyhow!("{:?} can't serve this request: {}", provider, reason))?;
+            Ok((provider, adapter))
+        }
+        None => adapters.route(|c| c.check_command(command, today)),
+    };
+    let rows = batch::run(sites, batch_args.concurrency, command, &resolve).await;
+    let json = batch_args.json
+        || batch_args
+            .out_file
+            .as_ref()
+            .and_then(|path| path.extension())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let results = if json {
+        serde_json::to_string_pretty(&rows)? + "\n"
+    } else {
+        batch::to_csv(&rows)
+    };
+    match &batch_args.out_file {
+        Some(path) => fs::write(path, results)
+            .with_context(|| format!("Couldn't write the results to {}", path.display()))?,
+        None => print!("{}", results),
+    }
+    let failed = rows.iter().filter(|row| row.error.is_some()).count();
+    eprintln!(
+        "Have queried {} sites, {} of them failed",
+        rows.len(),
+        failed
+    );
+    Ok(())
+}
+
+/// Handles periodic refreshes of a report until interrupted
+/// Upstream errors keep the last report on screen and delay the next attempt
+async fn handle_watch(
+    configuration: &Profile,
+    location: Option<String>,
+    date: Option<String>,
+    interval: Duration,
+    fields: Vec<Field>,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let location = util::resolve_location(location, configuration)?;
+    let lang = configuration.lang(lang);
+    // offsets are parsed on every refresh, so that they stay relative to the time of the refresh
+    let weather_command = || -> Result<WeatherCommand> {
+        let date = match date.as_deref() {
+            Some(date) => util::parse_date_arg(date)?,
+            None => Default::default(),
+        };
+        Ok(WeatherCommand::new(location.as_str(), date)
+            .with_units(configuration.units)
+            .with_fields(fields.clone())
+            .with_tz(tz)
+            .with_lang(lang))
+    };
+    let first = weather_command()?;
+    let (_, provider) = providers::route(configuration, |c| {
+        c.check_command(&first, Utc::now().date_naive())
+    })?;
+    let highlight = watch::highlights_enabled();
+    let mut backoff = watch::Backoff::new(interval);
+    let mut last: Option<(Report, DateTime<Local>)> = None;
+    loop {
+        let weather_command = weather_command()?;
+        let delay = match provider.run(weather_command).await {
+            Ok(report) => {
+                let previous = last.as_ref().map(|(report, _)| report);
+                let updated = Local::now();
+                print!(
+                    "{}{}",
+                    watch::CLEAR,
+                    watch::render(&report, previous, highlight)
+                );
+                println!("Last updated: {}", updated.format("%d/%m/%Y %H:%M:%S"));
+                last = Some((report, updated));
+                backoff.succeeded()
+            }
+            Err(e) => {
+                let delay = backoff.failed();
+                print!("{}", watch::CLEAR);
+                if let Some((report, updated)) = &last {
+                    print!("{}", watch::render(report, None, highlight));
+                    println!("Last updated: {}", updated.format("%d/%m/%Y %H:%M:%S"));
+                }
+                println!(
+                    "Update failed at {}: {:#}, retrying in {}s",
+                    Local::now().format("%H:%M:%S"),
+                    e,
+                    delay.as_secs()
+                );
+                delay
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Handles bookkeeping of notification rules, rules are checked before they are stored
+fn handle_rules(configuration: &mut Profile, action: args::RuleAction) -> Result<()> {
+    match action {
+        args::RuleAction::Add { location, rule } => {
+            let rule = notify::Rule::parse(&rule)?;
+            configuration.add_rule(&location, &rule.source)?;
+            println!("Have added the rule {} for {}", rule.source, location);
+        }
+        args::RuleAction::Remove { location, rule } => {
+            configuration.remove_rule(&location, rule.trim())?;
+            println!("Have removed the rule {} for {}", rule.trim(), location);
+        }
+        args::RuleAction::List => {
+            if configuration.rules.is_empty() {
+                println!("There are no notification rules");
+            }
+            configuration.rules.iter().for_each(|(location, rules)| {
+                println!("{}", location);
+                rules.iter().for_each(|rule| println!("  {}", rule));
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Handles evaluation of notification rules against the hourly forecast of their locations
+/// Events are remembered next to the configuration file once delivered, so they don't fire twice
+async fn handle_notify(
+    configuration: &Configuration,
+    profile: Option<&str>,
+    output: notify::Output,
+    command: Option<&str>,
+) -> Result<()> {
+    let profile = configuration.profile(profile)?;
+    if profile.rules.is_empty() {
+        bail!("There are no notification rules, add one with `weather notify add`");
+    }
+    let state_path = configuration
+        .config_path()?
+        .with_file_name("notify_state.json");
+    let mut state = notify::State::load(&state_path)?;
+    let (_, provider) = providers::route(profile, |c| c.check_feature(Feature::Hourly))?;
+    let capabilities = provider.capabilities();
+    let now = Utc::now();
+    let mut pending = Vec::new();
+    for (location, rules) in &profile.rules {
+        let rules = rules
+            .iter()
+            .map(|rule| notify::Rule::parse(rule))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid notification rules for {}", location))?;
+        if let Err(reason) = capabilities.check_location(location) {
+            log::warn!("Skipping {}: {}", location, reason);
+            continue;
+        }
+        let forecast = match provider.hourly(location).await {
+            Ok(forecast) => forecast,
+            Err(e) => {
+                log::warn!(
+                    "Couldn't obtain the hourly forecast for {}: {:#}",
+                    location,
+                    e
+                );
+                continue;
+            }
+        };
+        notify::evaluate(location, &rules, &forecast, now)
+            .into_iter()
+            .map(|notification| (notification.key(&forecast), notification))
+            .filter(|(key, _)| !state.has_fired(key))
+            .for_each(|event| pending.push(event));
+    }
+    let (keys, notifications): (Vec<String>, Vec<notify::Notification>) =
+        pending.into_iter().unzip();
+    let delivered = notify::deliver_all(output, command, notifications.clone()).await?;
+    keys.into_iter()
+        .zip(notifications)
+        .filter(|(_, notification)| delivered.contains(notification))
+        .for_each(|(key, _)| state.mark_fired(key, now));
+    state.save(&state_path, now)
+}
+
+/// Handles serving the JSON API until interrupted
+async fn handle_serve(configuration: &Profile, bind: SocketAddr) -> Result<()> {
+    let adapters = providers::Adapters::new(configuration);
+    // nothing is served without a configured provider
+    adapters.route(|_| Ok(()))?;
+    let listener =
+        TcpListener::bind(bind).with_context(|| format!("Couldn't listen on {}", bind))?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    Server::new(adapters, configuration.clone())
+        .serve(listener, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+/// Handles polling of locations and serving their metrics until interrupted
+async fn handle_exporter(
+    configuration: &Profile,
+    locations: Vec<String>,
+    bind: SocketAddr,
+    interval: Duration,
+) -> Result<()> {
+    let locations = if locations.is_empty() {
+        configuration.locations.clone()
+    } else {
+        locations
+    };
+    if locations.is_empty() {
+        bail!("There are no locations to poll, pass some or save them in the profile");
+    }
+    // each location goes to the most preferred provider that can serve it
+    let adapters = providers::Adapters::new(configuration);
+    let today = Utc::now().date_naive();
+    let targets = locations
+        .into_iter()
+        .map(|location| {
+            let command = WeatherCommand::new(location.as_str(), DateOffsetRepresentation::Now);
+            let (provider, adapter) = adapters
+                .route(|c| c.check_command(&command, today))
+                .with_context(|| format!("Couldn't route {}", location))?;
+            Ok(exporter::Target {
+                location,
+                provider,
+                adapter,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let metrics = Arc::new(exporter::Metrics::default());
+    let listener =
+        TcpListener::bind(bind).with_context(|| format!("Couldn't listen on {}", bind))?;
+    println!(
+        "Serving metrics at http://{}/metrics",
+        listener.local_addr()?
+    );
+    let polled = metrics.clone();
+    let polling = async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            exporter::poll(&targets, &polled).await;
+        }
+    };
+    tokio::select! {
+        result = exporter::serve(listener, metrics, async {
+            let _ = tokio::signal::ctrl_c().await;
+        }) => result,
+        _ = polling => Ok(()),
+    }
+}
+
+/// Handles weather alerts lookup
+async fn handle_alerts(
+    configuration: &Profile,
+    location: Option<String>,
+    tz: TimeZoneChoice,
+    lang: Option<Lang>,
+) -> Result<()> {
+    let lang = configuration.lang(lang);
+    let location = util::resolve_location(location, configuration)?;
+    let provider = route_feature(configuration, &location, Feature::Alerts)?;
+    let alerts = provider
+        .alerts(&location)
+        .await
+        .context("Failed to obtain weather alerts")?;
+    if alerts.alerts.is_empty() {
+        println!("There are no weather alerts for {}", location);
+        return Ok(());
+    }
+    let mut report = Report::new(format!("{} - {}", lang.tr("Weather alerts"), location));
+    let clock = Clock::new(None, tz).with_lang(lang);
+    alerts::add_to_report(&mut report, alerts, &clock);
+    println!("{}", report);
+    Ok(())
+}
+
+/// Handles listing and bookkeeping of configuration profiles
+fn handle_profile(
+    configuration: &mut Configuration,
+    selected: Option<&str>,
+    action: args::ProfileAction,
+) -> Result<()> {
+    match action {
+        args::ProfileAction::List => {
+            let selected = selected.unwrap_or(DEFAULT_PROFILE);
+            for name in configuration.profile_names() {
+                let profile = configuration.profile(Some(name))?;
+                let marker = if name == selected { "*" } else { " " };
+                let provider = profile
+                    .default_provider
+                    .as_ref()
+                    .map(|p| format!("{:?}", p))
+                    .unwrap_or_else(|| "-".to_owned());
+                println!("{} {:<20} | {:<20}", marker, name, provider);
+            }
+        }
+        args::ProfileAction::Create {
+            name,
+            units,
+            lang,
+            locations,
+        } => {
+            let mut profile = Profile::default();
+            profile.set_units(units);
+            profile.set_lang(lang);
+            profile.set_locations(locations);
+            configuration.create_profile(name.as_str(), profile)?;
+            println!("Have created the profile {}", name);
+        }
+        args::ProfileAction::Copy { from, to } => {
+            configuration.copy_profile(&from, to.as_str())?;
+            println!("Have copied the profile {} to {}", from, to);
+        }
+        args::ProfileAction::Delete { name } => {
+            configuration.delete_profile(&name)?;
+            println!("Have deleted the profile {}", name);
+        }
+    }
+    Ok(())
+}
+
+/// Lets the user edit the configuration file, returns the edited configuration once it parses
+fn handle_config_edit(configuration: &Configuration) -> Result<Option<Configuration>> {
+    let path = configuration.config_path()?;
+    let format = ConfigFormat::from_path(&path);
+    let mut contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => configuration.serialize(format, None)?,
+    };
+    loop {
+        let edited = match Editor::new()
+            .extension(format.extension())
+            .edit(&contents)
+            .context("Couldn't open an editor")?
+        {
+            Some(edited) => edited,
+            None => {
+                println!("The configuration was left unchanged");
+                return Ok(None);
+            }
+        };
+        match parse_configuration(&edited, format) {
+            Ok(parsed) => {
+                write_config_file(&path, &edited)?;
+                println!("Have saved the configuration to {}", path.display());
+                return Ok(Some(parsed.with_config_path(Some(path))));
+            }
+            Err(e) => {
+                println!("The configuration is invalid: {:#}", e);
+                let retry = Confirm::new()
+                    .with_prompt("Edit it again?")
+                    .default(true)
+                    .interact()?;
+                if !retry {
+                    return Ok(None);
+                }
+                contents = edited;
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Don't forget the RUST_LOG next time please
-    env_logger::init();
-
     let args = args::Args::parse();
-    let mut configuration = match open_or_default(args.config_path) {
+    // Don't forget the RUST_LOG next time please
+    let mut logger = env_logger::Builder::from_default_env();
+    if args.verbose && std::env::var_os("RUST_LOG").is_none() {
+        logger
+            .filter_module("weather", log::LevelFilter::Info)
+            .format_timestamp(None)
+            .format_target(false);
+    }
+    logger.init();
+    let config_path = match args.config_path {
+        Some(path) => Ok(path),
+        None => obtain_default_os_config_path(),
+    };
+    // commands that change the configuration hold its lock from loading it until it's saved
+    let changes_config = matches!(
+        args.action,
+        args::Action::Prefer { .. }
+            | args::Action::Configure { .. }
+            | args::Action::Default { .. }
+            | args::Action::Notify {
+                action: Some(_),
+                ..
+            }
+            | args::Action::Profile(_)
+    );
+    let lock = match &config_path {
+        Ok(path) if changes_config => Some(ConfigLock::acquire(path)?),
+        _ => None,
+    };
+    let mut configuration = match config_path.and_then(|path| open_or_default(Some(path))) {
         Ok(c) => c,
         Err(e) => {
             println!("{}", e);
             Configuration::default()
         }
     };
+    // only write the configuration back when a command has changed it
+    let mut loaded = configuration.clone();
+    let profile = args.profile.as_deref();
+    let mut exit_code = 0;
     match args.action {
-        args::Action::Configure { provider } => handle_condigure(&mut configuration, provider)?,
+        args::Action::Providers => handle_providers(configuration.profile(profile)?),
+        args::Action::Prefer { providers } => {
+            handle_prefer(configuration.profile_mut(profile)?, providers)
+        }
+        args::Action::Configure {
+            provider,
+            history_days,
+        } => handle_condigure(configuration.profile_mut(profile)?, provider, history_days)?,
         args::Action::Default { provider } => {
-            handle_change_default_provider(&mut configuration, provider)
+            handle_change_default_provider(configuration.profile_mut(profile)?, provider)
+        }
+        args::Action::Get(get_action) if get_action.format.is_some() || get_action.waybar => {
+            handle_status(&configuration, profile, get_action, args.tz, args.lang).await?
+        }
+        args::Action::Get(get_action) => {
+            let templates = configuration.config_path()?.with_file_name("templates");
+            handle_get(
+                configuration.profile(profile)?,
+                get_action,
+                args.tz,
+                args.lang,
+                &templates,
+            )
+            .await?
+        }
+        args::Action::Air { location } => {
+            handle_air(configuration.profile(profile)?, location, args.lang).await?
+        }
+        args::Action::Astro { location, date } => {
+            handle_astro(configuration.profile(profile)?, location, date, args.lang).await?
+        }
+        args::Action::Rain { location, from, to } => {
+            let answer = match configuration.profile(profile) {
+                Ok(profile) => handle_rain(profile, location, from, to, args.lang).await,
+                Err(e) => Err(e),
+            };
+            // failures get a code of their own, so that they aren't taken for a dry day
+            exit_code = match answer {
+                Ok(true) => 0,
+                Ok(false) => 1,
+                Err(e) => {
+                    eprintln!("Error: {:#}", e);
+                    2
+                }
+            };
+        }
+        args::Action::Stats {
+            location,
+            from,
+            to,
+            csv,
+        } => {
+            handle_stats(
+                configuration.profile(profile)?,
+                location,
+                &from,
+                &to,
+                csv,
+                args.lang,
+            )
+            .await?
         }
-        args::Action::Get(get_action) => handle_get(&configuration, get_action).await?,
+        args::Action::Batch(batch_args) => {
+            handle_batch(
+                configuration.profile(profile)?,
+                batch_args,
+                args.tz,
+                args.lang,
+            )
+            .await?
+        }
+        args::Action::Watch {
+            location,
+            date,
+            interval,
+            fields,
+        } => {
+            handle_watch(
+                configuration.profile(profile)?,
+                location,
+                date,
+                interval,
+                fields,
+                args.tz,
+                args.lang,
+            )
+            .await?
+        }
+        args::Action::Notify {
+            action: Some(action),
+            ..
+        } => handle_rules(configuration.profile_mut(profile)?, action)?,
+        args::Action::Notify {
+            action: None,
+            output,
+            command,
+        } => handle_notify(&configuration, profile, output, command.as_deref()).await?,
+        args::Action::Serve { bind } => handle_serve(configuration.profile(profile)?, bind).await?,
+        args::Action::Exporter {
+            locations,
+            bind,
+            interval,
+        } => handle_exporter(configuration.profile(profile)?, locations, bind, interval).await?,
+        args::Action::Marine { location } => {
+            handle_marine(
+                configuration.profile(profile)?,
+                location,
+                args.tz,
+                args.lang,
+            )
+            .await?
+        }
+        args::Action::Alerts { location } => {
+            handle_alerts(
+                configuration.profile(profile)?,
+                location,
+                args.tz,
+                args.lang,
+            )
+            .await?
+        }
+        args::Action::Profile(action) => handle_profile(&mut configuration, profile, action)?,
+        args::Action::Config(args::ConfigAction::Edit) => {
+            if let Some(edited) = handle_config_edit(&configuration)? {
+                loaded = edited.clone();
+                configuration = edited;
+            }
+        }
+    }
+    if configuration != loaded {
+        match &lock {
+            Some(lock) => configuration.save_locked(lock)?,
+            None => configuration.save()?,
+        }
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
     }
-    configuration.save()?;
     Ok(())
 }
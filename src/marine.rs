@@ -0,0 +1,92 @@
+use crate::{
+    report::{Report, ReportSection, SectionRepr},
+    types::Clock,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+
+/// Sea state at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarineConditions {
+    pub time: DateTime<Utc>,
+    pub wave_height_m: Option<f64>,
+    pub swell_height_m: Option<f64>,
+    pub swell_direction: Option<String>,
+    pub swell_period_s: Option<f64>,
+    pub water_temp_c: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TideKind {
+    High,
+    Low,
+}
+
+/// A high or a low tide
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tide {
+    pub time: DateTime<Utc>,
+    pub kind: TideKind,
+    pub height_m: Option<f64>,
+}
+
+/// Marine data normalized across providers, either part might be empty
+/// The offset of the location from UTC is kept when a provider tells it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Marine {
+    pub conditions: Vec<MarineConditions>,
+    pub tides: Vec<Tide>,
+    #[serde(skip)]
+    pub utc_offset: Option<FixedOffset>,
+}
+
+fn format_value(value: Option<f64>, unit: &str) -> String {
+    value
+        .map(|v| format!("{}{}", v, unit))
+        .unwrap_or_else(|| "-".to_owned())
+}
+
+/// Appends a tide table and a section per sea state period to a report
+/// Timestamps are shown at the location's offset, as far as the clock allows
+pub fn add_to_report(report: &mut Report, mut marine: Marine, clock: &Clock) {
+    let clock = Clock::new(marine.utc_offset.or(clock.location), clock.tz).with_lang(clock.lang);
+    let tr = |text| clock.lang.tr(text).to_owned();
+    if !marine.tides.is_empty() {
+        marine.tides.sort_by_key(|t| t.time);
+        let tides = marine
+            .tides
+            .into_iter()
+            .map(|tide| {
+                let kind = match tide.kind {
+                    TideKind::High => tr("High tide"),
+                    TideKind::Low => tr("Low tide"),
+                };
+                (
+                    format!("{}, {}", kind, clock.format(tide.time)),
+                    format_value(tide.height_m, " m"),
+                )
+            })
+            .collect();
+        report.add_section(ReportSection::new(tr("Tides"), tides));
+    }
+    marine.conditions.sort_by_key(|c| c.time);
+    marine.conditions.into_iter().for_each(|c| {
+        let details: SectionRepr = vec![
+            (
+                tr("Significant wave height"),
+                format_value(c.wave_height_m, " m"),
+            ),
+            (tr("Swell height"), format_value(c.swell_height_m, " m")),
+            (
+                tr("Swell direction"),
+                c.swell_direction.unwrap_or_else(|| "-".to_owned()),
+            ),
+            (tr("Swell period"), format_value(c.swell_period_s, " s")),
+            (
+                format!("{}, C", tr("Water temp.")),
+                format_value(c.water_temp_c, "°"),
+            ),
+        ];
+        report.add_section(ReportSection::new(clock.format(c.time), details));
+    });
+}
@@ -0,0 +1,471 @@
+use crate::{
+    conditions::{Conditions, HourlyForecast},
+    configuration::write_config_file,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// How long fired events are remembered for
+const STATE_RETENTION_DAYS: i64 = 7;
+
+/// A value of the conditions a rule can be written against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Temperature,
+    FeelsLike,
+    DewPoint,
+    Humidity,
+    Pressure,
+    Precipitation,
+    PrecipitationChance,
+    Uv,
+    Visibility,
+    Wind,
+    Gusts,
+    CloudCover,
+    Snow,
+    Condition,
+}
+
+impl Metric {
+    fn from_name(name: &str) -> Option<Self> {
+        let metric = match name {
+            "temp" | "temp_c" | "temperature" => Metric::Temperature,
+            "feels_like" | "feels_like_c" => Metric::FeelsLike,
+            "dew_point" | "dew_point_c" => Metric::DewPoint,
+            "humidity" => Metric::Humidity,
+            "pressure" | "pressure_mb" => Metric::Pressure,
+            "precip" | "precip_mm" => Metric::Precipitation,
+            "precip_chance" | "pop" => Metric::PrecipitationChance,
+            "uv" => Metric::Uv,
+            "visibility" | "visibility_km" => Metric::Visibility,
+            "wind" | "wind_kph" => Metric::Wind,
+            "gust" | "gust_kph" => Metric::Gusts,
+            "cloud" | "cloud_cover" => Metric::CloudCover,
+            "snow" | "snow_cm" => Metric::Snow,
+            "condition" => Metric::Condition,
+            _ => return None,
+        };
+        Some(metric)
+    }
+
+    fn number(&self, c: &Conditions) -> Option<f64> {
+        match self {
+            Metric::Temperature => c.temp_c,
+            Metric::FeelsLike => c.feels_like_c,
+            Metric::DewPoint => c.dew_point_c,
+            Metric::Humidity => c.humidity,
+            Metric::Pressure => c.pressure_mb,
+            Metric::Precipitation => c.precip_mm,
+            Metric::PrecipitationChance => c.precip_chance,
+            Metric::Uv => c.uv,
+            Metric::Visibility => c.visibility_km,
+            Metric::Wind => c.wind_kph,
+            Metric::Gusts => c.gust_kph,
+            Metric::CloudCover => c.cloud_cover,
+            Metric::Snow => c.snow_cm,
+            Metric::Condition => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    Contains,
+}
+
+impl Operator {
+    fn from_token(token: &str) -> Option<Self> {
+        let operator = match token {
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "==" | "=" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            "contains" => Operator::Contains,
+            _ => return None,
+        };
+        Some(operator)
+    }
+}
+
+/// A threshold rule such as `temp < 0`, `wind_kph > 50` or `condition contains "storm"`
+/// Numbers are compared in the metric units conditions are kept in, text is compared case-insensitively
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub source: String,
+    pub metric: Metric,
+    pub operator: Operator,
+    pub threshold: Threshold,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Threshold {
+    Number(f64),
+    Text(String),
+}
+
+impl Rule {
+    pub fn parse(source: &str) -> Result<Self> {
+        let source = source.trim();
+        let mut parts = source.splitn(3, char::is_whitespace);
+        let (metric, operator, value) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(metric), Some(operator), Some(value)) => (metric, operator, value.trim()),
+            _ => bail!(
+                "A rule consists of a value, an operator and a threshold, e.g. `temp < 0`, got {}",
+                source
+            ),
+        };
+        let metric = Metric::from_name(&metric.to_ascii_lowercase())
+            .with_context(|| format!("Unknown value {} in the rule {}", metric, source))?;
+        let operator = Operator::from_token(&operator.to_ascii_lowercase())
+            .with_context(|| format!("Unknown operator {} in the rule {}", operator, source))?;
+        let threshold = if metric == Metric::Condition {
+            if !matches!(
+                operator,
+                Operator::Equal | Operator::NotEqual | Operator::Contains
+            ) {
+                bail!("The condition can only be compared with ==, != or contains");
+            }
+            Threshold::Text(value.trim_matches('"').to_lowercase())
+        } else {
+            if operator == Operator::Contains {
+                bail!("Only the condition can be searched with contains");
+            }
+            Threshold::Number(
+                value
+                    .parse()
+                    .with_context(|| format!("{} isn't a number in the rule {}", value, source))?,
+            )
+        };
+        Ok(Self {
+            source: source.to_owned(),
+            metric,
+            operator,
+            threshold,
+        })
+    }
+
+    /// The value a period triggers the rule with, `None` when it doesn't
+    pub fn triggered_by(&self, c: &Conditions) -> Option<String> {
+        match &self.threshold {
+            Threshold::Number(threshold) => {
+                let value = self.metric.number(c)?;
+                let triggered = match self.operator {
+                    Operator::Less => value < *threshold,
+                    Operator::LessOrEqual => value <= *threshold,
+                    Operator::Greater => value > *threshold,
+                    Operator::GreaterOrEqual => value >= *threshold,
+                    Operator::Equal => value == *threshold,
+                    Operator::NotEqual => value != *threshold,
+                    Operator::Contains => false,
+                };
+                triggered.then(|| value.to_string())
+            }
+            Threshold::Text(threshold) => {
+                let condition = c.condition.as_ref()?;
+                let lower = condition.to_lowercase();
+                let triggered = match self.operator {
+                    Operator::Equal => lower == *threshold,
+                    Operator::NotEqual => lower != *threshold,
+                    Operator::Contains => lower.contains(threshold.as_str()),
+                    _ => false,
+                };
+                triggered.then(|| condition.clone())
+            }
+        }
+    }
+}
+
+/// A rule that has triggered for a location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub location: String,
+    pub rule: String,
+    pub time: DateTime<Utc>,
+    pub value: String,
+    pub message: String,
+}
+
+impl Notification {
+    /// Identity of the event, a rule fires at most once per location and local day
+    pub fn key(&self, forecast: &HourlyForecast) -> String {
+        format!(
+            "{}|{}|{}",
+            self.location,
+            self.rule,
+            self.time
+                .with_timezone(&forecast.utc_offset)
+                .format("%Y-%m-%d")
+        )
+    }
+}
+
+/// Evaluates rules against the upcoming hours, every rule yields its first triggering hour
+pub fn evaluate(
+    location: &str,
+    rules: &[Rule],
+    forecast: &HourlyForecast,
+    now: DateTime<Utc>,
+) -> Vec<Notification> {
+    let upcoming: Vec<&Conditions> = forecast
+        .hours
+        .iter()
+        .filter(|h| h.time + Duration::hours(1) > now)
+        .collect();
+    rules
+        .iter()
+        .filter_map(|rule| {
+            upcoming.iter().find_map(|hour| {
+                rule.triggered_by(hour).map(|value| Notification {
+                    location: location.to_owned(),
+                    rule: rule.source.clone(),
+                    time: hour.time,
+                    message: format!(
+                        "{}: {} at {} ({})",
+                        location,
+                        rule.source,
+                        hour.time
+                            .with_timezone(&forecast.utc_offset)
+                            .format("%d/%m/%Y %H:%M"),
+                        value
+                    ),
+                    value,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Events that have already been notified about, along with the time they were
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub fired: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl State {
+    /// A missing state file is an empty state
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path).context("Couldn't read the notification state")?;
+        serde_json::from_str(&contents).context("Couldn't parse the notification state")
+    }
+
+    /// Writes the state atomically, dropping events older than the retention period
+    pub fn save(&mut self, path: &Path, now: DateTime<Utc>) -> Result<()> {
+        let cutoff = now - Duration::days(STATE_RETENTION_DAYS);
+        self.fired.retain(|_, fired| *fired > cutoff);
+        write_config_file(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn has_fired(&self, key: &str) -> bool {
+        self.fired.contains_key(key)
+    }
+
+    pub fn mark_fired(&mut self, key: String, now: DateTime<Utc>) {
+        self.fired.insert(key, now);
+    }
+}
+
+/// Where notifications are delivered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// A line per notification
+    Stdout,
+    /// A JSON array of notifications
+    Json,
+    /// Run a shell command per notification, see `--command`
+    Exec,
+    /// Desktop notifications over D-Bus, falls back to stdout when it isn't available
+    Dbus,
+}
+
+/// Runs a shell command with the notification in `WEATHER_*` environment variables
+async fn exec(command: &str, notification: &Notification) -> Result<()> {
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("WEATHER_LOCATION", &notification.location)
+        .env("WEATHER_RULE", &notification.rule)
+        .env("WEATHER_TIME", notification.time.to_rfc3339())
+        .env("WEATHER_VALUE", &notification.value)
+        .env("WEATHER_MESSAGE", &notification.message)
+        .status()
+        .await
+        .context("Couldn't run the notification command")?;
+    if !status.success() {
+        bail!("The notification command has failed with {}", status);
+    }
+    Ok(())
+}
+
+/// Sends a desktop notification through `gdbus`, as D-Bus isn't spoken natively
+async fn dbus(notification: &Notification) -> Result<()> {
+    let output = tokio::process::Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.Notifications.Notify",
+            "weather",
+            "0",
+            "",
+        ])
+        .arg(format!("Weather - {}", notification.location))
+        .arg(&notification.message)
+        .args(["[]", "{}", "-1"])
+        .output()
+        .await
+        .context("D-Bus isn't available, gdbus couldn't be run")?;
+    if !output.status.success() {
+        bail!(
+            "Couldn't send a desktop notification: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Delivers a single notification, JSON output is printed as a whole by [`deliver_all`]
+async fn deliver(output: Output, command: Option<&str>, notification: &Notification) -> Result<()> {
+    match output {
+        Output::Stdout | Output::Json => println!("{}", notification.message),
+        Output::Exec => {
+            exec(
+                command.context("A command is required for the exec output")?,
+                notification,
+            )
+            .await?
+        }
+        Output::Dbus => {
+            if let Err(e) = dbus(notification).await {
+                log::warn!("{:#}", e);
+                println!("{}", notification.message);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Delivers notifications, returns the ones that have been delivered
+pub async fn deliver_all(
+    output: Output,
+    command: Option<&str>,
+    notifications: Vec<Notification>,
+) -> Result<Vec<Notification>> {
+    if output == Output::Json {
+        println!("{}", serde_json::to_string_pretty(&notifications)?);
+        return Ok(notifications);
+    }
+    let mut delivered = Vec::new();
+    for notification in notifications {
+        match deliver(output, command, &notification).await {
+            Ok(()) => delivered.push(notification),
+            Err(e) => log::warn!("Couldn't deliver a notification: {:#}", e),
+        }
+    }
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    fn hour(h: i64, temp: f64, condition: &str) -> Conditions {
+        Conditions {
+            time: Utc
+                .timestamp_opt(1680210000 + h * 3600, 0)
+                .single()
+                .expect("Bad time"),
+            temp_c: Some(temp),
+            condition: Some(condition.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn forecast() -> HourlyForecast {
+        HourlyForecast {
+            utc_offset: FixedOffset::east_opt(3 * 3600).expect("Bad offset"),
+            hours: vec![
+                hour(1, 1.0, "Clear"),
+                hour(2, -1.5, "Clear"),
+                hour(3, -2.0, "Thunderstorm"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rule = Rule::parse("wind_kph > 50").expect("Couldn't parse");
+        assert_eq!(rule.metric, Metric::Wind);
+        assert_eq!(rule.operator, Operator::Greater);
+        assert_eq!(rule.threshold, Threshold::Number(50.0));
+        let rule = Rule::parse("condition contains \"Storm\"").expect("Couldn't parse");
+        assert_eq!(rule.threshold, Threshold::Text("storm".to_owned()));
+        assert!(Rule::parse("temp < cold").is_err());
+        assert!(Rule::parse("temp contains 1").is_err());
+        assert!(Rule::parse("altitude > 1").is_err());
+        assert!(Rule::parse("temp").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules = vec![
+            Rule::parse("temp < 0").expect("Couldn't parse"),
+            Rule::parse("condition contains storm").expect("Couldn't parse"),
+            Rule::parse("temp > 30").expect("Couldn't parse"),
+        ];
+        let now = Utc.timestamp_opt(1680210000, 0).single().expect("Bad time");
+        let notifications = evaluate("Kyiv", &rules, &forecast(), now);
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0].value, "-1.5");
+        assert_eq!(
+            notifications[0].message,
+            "Kyiv: temp < 0 at 31/03/2023 02:00 (-1.5)"
+        );
+        assert_eq!(notifications[1].value, "Thunderstorm");
+        assert_eq!(
+            notifications[0].key(&forecast()),
+            "Kyiv|temp < 0|2023-03-31"
+        );
+        // hours that have passed are not looked at
+        let later = now + Duration::hours(4);
+        assert!(evaluate("Kyiv", &rules, &forecast(), later).is_empty());
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let path = Path::new(".tmp/notify_state_roundtrip.json");
+        fs::create_dir_all(".tmp").expect("Wasn't able to create a temporary test directory");
+        let _ = fs::remove_file(path);
+        let now = Utc.timestamp_opt(1680210000, 0).single().expect("Bad time");
+        let mut state = State::load(path).expect("Couldn't load a missing state");
+        state.mark_fired("Kyiv|temp < 0|2023-03-31".to_owned(), now);
+        state.mark_fired(
+            "Kyiv|temp < 0|2023-03-01".to_owned(),
+            now - Duration::days(30),
+        );
+        state.save(path, now).expect("Couldn't save the state");
+        let state = State::load(path).expect("Couldn't load the state");
+        assert!(state.has_fired("Kyiv|temp < 0|2023-03-31"));
+        assert!(!state.has_fired("Kyiv|temp < 0|2023-03-01"));
+    }
+}
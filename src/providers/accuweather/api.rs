@@ -21,16 +21,3 @@ pub struct WeatherConditions {
 pub type WeatherConditionsResponse = Vec<WeatherConditions>;
 
 pub type LocationSearchResponse = Vec<LocationSearchItem>;
-
-/// Current and historical weather conditions API item
-#[derive(Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct ForecastHourly {
-    pub icon_phrase: String,
-    #[serde(with = "chrono::serde::ts_seconds")]
-    pub epoch_date_time: chrono::DateTime<Utc>,
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct Temperature {}
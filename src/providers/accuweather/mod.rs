@@ -1,11 +1,14 @@
 use self::api::{LocationSearchResponse, WeatherConditionsResponse};
-use crate::{command::WeatherCommand, report::Report, types::Provider};
+use crate::{capabilities::Capabilities, command::WeatherCommand, report::Report, types::Provider};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use reqwest::Response;
 
 mod api;
 
+/// Reports aren't made yet, so nothing can be asked for
+pub const CAPABILITIES: Capabilities = Capabilities::NONE;
+
 /// AccuWeather REST API adapter
 pub struct AccuWeatherProvider {
     base_url: String,
@@ -58,9 +61,7 @@ impl AccuWeatherProvider {
 }
 
 impl AccuWeatherProvider {
-    /// Unmarshal Location API response to get a location id
-
-    /// Make a Location API request
+    /// Make a Location API request, names come back in the command's language
     async fn request_location_search(
         &self,
         command: &WeatherCommand,
@@ -68,6 +69,7 @@ impl AccuWeatherProvider {
         self.client
             .get(self.url_location_api())
             .query(&[("apikey", &self.api_key), ("q", &command.location)])
+            .query(&[("language", command.lang.code())])
             .send()
             .await
     }
@@ -122,12 +124,17 @@ async fn parse_weather_conditions(response: Response) -> Result<WeatherCondition
 
 #[async_trait]
 impl Provider for AccuWeatherProvider {
+    fn capabilities(&self) -> Capabilities {
+        CAPABILITIES
+    }
+
     async fn run(&self, command: WeatherCommand) -> Result<Report> {
         let location_resp = self
             .request_location_search(&command)
             .await
             .context("Unable to make a location query")?;
-        let _location = parse_location_search(location_resp);
+        let locations = parse_location_search(location_resp).await?;
+        let _location_key = &locations.first().context("No matching location found")?.key;
 
         unimplemented!("");
     }
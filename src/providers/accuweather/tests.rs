@@ -5,11 +5,11 @@ use wiremock::{
     Mock, MockServer, ResponseTemplate,
 };
 
-const LOCATION_KEY: &'static str = "326514";
+const LOCATION_KEY: &str = "326514";
 
 /// Mock data
-pub const LOCATION_SEARCH_MOCK: &'static str = include_str!("./fixtures/location.json");
-pub const LOCATION_WEATHER_CONDITIONS_MOCK: &'static str =
+pub const LOCATION_SEARCH_MOCK: &str = include_str!("./fixtures/location.json");
+pub const LOCATION_WEATHER_CONDITIONS_MOCK: &str =
     include_str!("./fixtures/current_weather_conditions.json");
 
 fn make_accuweather<S: Into<String>>(base_url: S, api_key: S) -> AccuWeatherProvider {
@@ -35,10 +35,8 @@ async fn test_location_request() {
     mock_server.register(search_mock).await;
     // let api_key = real_credentials();
     // let accuweather_inst = AccuWeatherProvider::new().with_api_key(api_key);
-    let accuweather_inst = make_accuweather(
-        format!("http://{}", mock_server.address().to_string()),
-        "22222".into(),
-    );
+    let accuweather_inst =
+        make_accuweather(format!("http://{}", mock_server.address()), "22222".into());
 
     let wc = WeatherCommand::new(
         "Zaporizhzhia",
@@ -71,10 +69,8 @@ async fn test_current_weather_conditions_request() {
     mock_server.register(search_mock).await;
     // let api_key = real_credentials();
     // let accuweather_inst = AccuWeatherProvider::new().with_api_key(api_key);
-    let accuweather_inst = make_accuweather(
-        format!("http://{}", mock_server.address().to_string()),
-        "22222".into(),
-    );
+    let accuweather_inst =
+        make_accuweather(format!("http://{}", mock_server.address()), "22222".into());
 
     // let wc = WeatherCommand::new("Zaporizhzhia", crate::command::DateRepresentation::Now);
 
@@ -9,7 +9,6 @@ pub mod hourly {
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Json {
-        pub success: bool,
         pub response: Vec<Response>,
     }
 
@@ -19,16 +18,44 @@ pub mod hourly {
         #[serde(with = "chrono::serde::ts_seconds")]
         pub timestamp: chrono::DateTime<Utc>,
         pub temp_c: f64,
-        pub temp_f: f64,
         pub wind_dir: String,
         pub weather: String,
+        /// `coverage:intensity:weather`, e.g. `:L:RW` for light rain showers
+        pub weather_primary_coded: Option<String>,
         pub humidity: usize,
+        pub feelslike_c: Option<f64>,
+        pub dewpoint_c: Option<f64>,
+        pub pressure_m_b: Option<f64>,
+        pub wind_speed_k_p_h: Option<f64>,
+        pub wind_gust_k_p_h: Option<f64>,
+        pub precip_m_m: Option<f64>,
+        pub snow_c_m: Option<f64>,
+        pub pop: Option<f64>,
+        pub visibility_k_m: Option<f64>,
+        pub sky: Option<f64>,
+        pub uvi: Option<f64>,
     }
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Response {
+        pub loc: Loc,
         pub periods: Vec<Periods>,
+        pub profile: Profile,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Loc {
+        pub lat: f64,
+        pub long: f64,
+    }
+
+    /// `tzoffset` is the location's offset from UTC in seconds
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Profile {
+        pub tzoffset: i32,
     }
 }
 
@@ -40,25 +67,33 @@ pub mod daily {
     pub struct Periods {
         #[serde(with = "chrono::serde::ts_seconds")]
         pub timestamp: chrono::DateTime<Utc>,
+        /// Start of the day on the location's wall clock
+        pub date_time_i_s_o: Option<chrono::DateTime<chrono::FixedOffset>>,
         pub wind_speed: WindSpeed,
         pub humidity: Humidity,
         pub pressure: Pressure,
         pub temp: Temp,
         pub weather: Weather,
+        pub feelslike: Option<Range>,
+        pub dewpoint: Option<Range>,
+        pub wind_gust: Option<WindGust>,
+        pub precip: Option<Precip>,
+        pub snow: Option<Snow>,
+        pub visibility: Option<Visibility>,
+        pub sky: Option<Sky>,
+        pub uvi: Option<Uvi>,
     }
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Weather {
         pub phrase: String,
-        pub primary: String,
+        pub primary_coded: Option<String>,
     }
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Humidity {
-        pub max: f64,
-        pub min: f64,
         pub avg: f64,
     }
 
@@ -66,41 +101,198 @@ pub mod daily {
     #[serde(rename_all = "camelCase")]
     pub struct WindSpeed {
         pub max_dir: String,
-        pub min_dir: String,
+        pub max_k_p_h: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct WindGust {
+        pub max_k_p_h: Option<f64>,
+    }
+
+    /// Daily range of a temperature-like value, e.g. the feels-like temperature or the dew point
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Range {
+        pub avg_c: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Precip {
+        pub total_m_m: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Snow {
+        pub total_c_m: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Visibility {
+        pub avg_k_m: Option<f64>,
+    }
+
+    /// Cloud cover in percent
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Sky {
+        pub avg: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Uvi {
+        pub max: Option<f64>,
     }
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Pressure {
-        pub max_m_b: f64,
-        pub min_m_b: f64,
         pub avg_m_b: f64,
-        pub max_i_n: f64,
-        pub min_i_n: f64,
-        pub avg_i_n: f64,
     }
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Temp {
         pub avg_c: f64,
-        pub avg_f: f64,
         pub min_c: f64,
-        pub min_f: f64,
         pub max_c: f64,
-        pub max_f: f64,
     }
 
     /// Root of Json response
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
+    pub struct Json {
+        pub response: Vec<Response>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Response {
+        pub periods: Vec<Periods>,
+    }
+}
+
+pub mod alerts {
+    use super::*;
+
+    /// Root of Json response
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Json {
+        pub response: Vec<Response>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Response {
+        pub details: Details,
+        pub timestamps: Timestamps,
+        pub place: Place,
+    }
+
+    /// `type` is a VTEC code, e.g. `FF.W` for a flash flood warning
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Details {
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub name: String,
+        pub emergency: bool,
+        pub body: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Timestamps {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        pub begins: chrono::DateTime<Utc>,
+        /// The start on the location's clock, which tells its offset from UTC
+        #[serde(rename = "beginsISO")]
+        pub begins_iso: Option<chrono::DateTime<chrono::FixedOffset>>,
+        #[serde(with = "chrono::serde::ts_seconds")]
+        pub expires: chrono::DateTime<Utc>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Place {
+        pub name: String,
+        pub state: String,
+        pub country: String,
+    }
+}
+
+pub mod air_quality {
+    use super::*;
+
+    /// Root of Json response
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Json {
+        pub response: Vec<Response>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Response {
+        pub periods: Vec<Periods>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Periods {
+        pub aqi: f64,
+        pub category: String,
+        pub pollutants: Vec<Pollutant>,
+    }
+
+    /// `type` is a short pollutant name, e.g. `pm2.5` or `o3`
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Pollutant {
+        #[serde(rename = "type")]
+        pub kind: String,
+        #[serde(rename = "valueUGM3")]
+        pub value_ugm3: Option<f64>,
+    }
+}
+
+pub mod tides {
+    use super::*;
+
+    /// Root of Json response, `error` is set when `success` isn't
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
     pub struct Json {
         pub success: bool,
+        pub error: Option<Error>,
         pub response: Vec<Response>,
     }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Error {
+        pub description: String,
+    }
+
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Response {
         pub periods: Vec<Periods>,
     }
+
+    /// `type` is either `h` for a high tide or `l` for a low one
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Periods {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        pub timestamp: chrono::DateTime<Utc>,
+        #[serde(rename = "type")]
+        pub kind: String,
+        #[serde(rename = "heightM")]
+        pub height_m: Option<f64>,
+    }
 }
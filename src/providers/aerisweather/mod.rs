@@ -1,11 +1,18 @@
 use crate::{
-    command::{DateOffsetRepresentation, WeatherCommand},
+    air_quality::AirQuality,
+    alerts::{self, Alerts},
+    astronomy::Astronomy,
+    capabilities::{Capabilities, LocationFormat},
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    conditions::{Conditions, HourlyForecast, View},
+    marine::Marine,
+    providers::RateLimit,
     report::Report,
     types::Provider,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
 use reqwest::Response;
 
 mod api;
@@ -14,11 +21,34 @@ mod tests;
 
 mod report;
 
+/// Most daily summaries a single request is asked for
+const HISTORY_CHUNK_DAYS: usize = 31;
+
+/// The conditions endpoints reach 15 days ahead and keep a year of archive
+pub const CAPABILITIES: Capabilities = Capabilities {
+    current: true,
+    hourly_forecast_hours: 15 * 24,
+    hourly_history_hours: 30 * 24,
+    daily_forecast_days: 15,
+    history_days: 365,
+    hourly: true,
+    alerts: true,
+    air_quality: true,
+    astronomy: true,
+    marine: true,
+    location_formats: &[
+        LocationFormat::Name,
+        LocationFormat::Coordinates,
+        LocationFormat::PostalCode,
+    ],
+};
+
 pub struct AerisWeatherProvider {
     base_url: String,
     client: reqwest::Client,
     client_id: String,
     client_secret: String,
+    rate_limit: Option<RateLimit>,
 }
 
 impl Default for AerisWeatherProvider {
@@ -34,6 +64,7 @@ impl AerisWeatherProvider {
             client_id: "".to_owned(),
             client_secret: "".to_owned(),
             client: reqwest::Client::new(),
+            rate_limit: None,
         }
     }
 
@@ -47,6 +78,12 @@ impl AerisWeatherProvider {
         self.client_secret = client_secret.into();
         self
     }
+
+    /// Space out the requests, as a plan's or a batch's rate allows
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
 }
 
 impl AerisWeatherProvider {
@@ -57,6 +94,18 @@ impl AerisWeatherProvider {
     fn url_hourly(&self, location: &str) -> String {
         format!("{}/{}/{}", self.base_url, "conditions", location)
     }
+
+    fn url_air_quality(&self, location: &str) -> String {
+        format!("{}/{}/{}", self.base_url, "airquality", location)
+    }
+
+    fn url_tides(&self, location: &str) -> String {
+        format!("{}/{}/{}", self.base_url, "tides", location)
+    }
+
+    fn url_alerts(&self, location: &str) -> String {
+        format!("{}/{}/{}", self.base_url, "alerts", location)
+    }
 }
 
 async fn parse_hourly(response: Response) -> Result<api::hourly::Json> {
@@ -73,8 +122,34 @@ async fn parse_daily(response: Response) -> Result<api::daily::Json> {
         .context("Couldn't parse the hourly status")
 }
 
+async fn parse_air_quality(response: Response) -> Result<api::air_quality::Json> {
+    response
+        .json::<api::air_quality::Json>()
+        .await
+        .context("Couldn't parse the air quality")
+}
+
+/// Unlike other endpoints, tides are only known near tide stations, so errors are surfaced
+async fn parse_tides(response: Response) -> Result<api::tides::Json> {
+    let parsed = response
+        .json::<api::tides::Json>()
+        .await
+        .context("Couldn't parse the tides")?;
+    match (parsed.success, &parsed.error) {
+        (false, Some(error)) => bail!("{}", error.description),
+        _ => Ok(parsed),
+    }
+}
+
+async fn parse_alerts(response: Response) -> Result<api::alerts::Json> {
+    response
+        .json::<api::alerts::Json>()
+        .await
+        .context("Couldn't parse the weather alerts")
+}
+
 impl AerisWeatherProvider {
-    async fn branch_hourly(&self, location: &str, hours: isize) -> Result<Report> {
+    async fn branch_hourly(&self, location: &str, hours: isize, view: &View) -> Result<Report> {
         let hour_offset_to_str = || {
             if hours > 0 {
                 format!("+{}hours", hours)
@@ -84,85 +159,121 @@ impl AerisWeatherProvider {
         };
         match hours {
             hours if hours > 0 => {
-                let mut r = Report::new("AerisWeather - forecast(hourly)");
+                let mut r = Report::new(format!(
+                    "AerisWeather - {}({})",
+                    view.lang.tr("forecast"),
+                    view.lang.tr("hourly")
+                ));
                 let res = self
                     .request_hourly(location, &hour_offset_to_str())
                     .await
                     .context("Failed to make hourly API request")?;
                 let parsed = parse_hourly(res).await?;
-                report::report_hourly(&mut r, parsed);
+                report::report_hourly(&mut r, parsed, view);
                 Ok(r)
             }
             hours if hours < 0 => {
-                let mut r = Report::new("AerisWeather - history(hourly)");
+                let mut r = Report::new(format!(
+                    "AerisWeather - {}({})",
+                    view.lang.tr("history"),
+                    view.lang.tr("hourly")
+                ));
                 let res = self
                     .request_hourly(location, &hour_offset_to_str())
                     .await
                     .context("Failed to make hourly API request")?;
                 let parsed = parse_hourly(res).await?;
-                report::report_hourly(&mut r, parsed);
+                report::report_hourly(&mut r, parsed, view);
                 Ok(r)
             }
             _ => {
-                let mut r = Report::new("AerisWeather - current");
+                let mut r = Report::new(format!("AerisWeather - {}", view.lang.tr("current")));
                 let res = self
                     .request_hourly(location, "now")
                     .await
                     .context("Failed to make hourly API request")?;
                 let parsed = parse_hourly(res).await?;
-                report::report_hourly(&mut r, parsed);
+                report::report_hourly(&mut r, parsed, view);
                 Ok(r)
             }
         }
     }
 
-    async fn branch_daily(&self, location: &str, days: isize) -> Result<Report> {
-        let day_offset_to_str = || {
-            DateOffsetRepresentation::DayOffset(days)
-                .to_chrono(Utc::now().into())
-                .format("%Y/%m/%d")
-                .to_string()
-        };
-        let precalculated_offset = day_offset_to_str();
+    /// Days are given relative to today, which AerisWeather counts on the location's calendar
+    async fn branch_daily(&self, location: &str, days: isize, view: &View) -> Result<Report> {
+        let unit = if days.abs() == 1 { "day" } else { "days" };
+        let precalculated_offset = format!("{:+}{}", days, unit);
         if days > 0 {
-            let mut r = Report::new("AerisWeather - forecast(daily)");
+            let mut r = Report::new(format!(
+                "AerisWeather - {}({})",
+                view.lang.tr("forecast"),
+                view.lang.tr("daily")
+            ));
             let res = self
                 .request_daily(location, &precalculated_offset)
                 .await
                 .context("Failed to make daily API request")?;
             let parsed = parse_daily(res).await?;
-            report::report_daily(&mut r, parsed);
+            report::report_daily(&mut r, parsed, view);
             Ok(r)
         } else {
-            let mut r = Report::new("AerisWeather - history(daily)");
+            let mut r = Report::new(format!(
+                "AerisWeather - {}({})",
+                view.lang.tr("history"),
+                view.lang.tr("daily")
+            ));
             let res = self
                 .request_daily(location, &precalculated_offset)
                 .await
                 .context("Failed to make daily API request")?;
             let parsed = parse_daily(res).await?;
-            report::report_daily(&mut r, parsed);
+            report::report_daily(&mut r, parsed, view);
             Ok(r)
         }
     }
 
-    async fn branch_current(&self, location: &str) -> Result<Report> {
-        let mut r = Report::new("AerisWeather - current");
+    /// Unlike WeatherAPI's history, summaries of a whole range come in a single response
+    async fn branch_range(&self, location: &str, range: DayRange, view: &View) -> Result<Report> {
+        let mut r = Report::new(format!(
+            "AerisWeather - {}({})",
+            view.lang.tr("range"),
+            view.lang.tr("daily")
+        ));
+        let res = self
+            .request_daily_range(location, range)
+            .await
+            .context("Failed to make daily API request")?;
+        let parsed = parse_daily(res).await?;
+        report::report_range(&mut r, parsed, view);
+        Ok(r)
+    }
+
+    async fn branch_current(&self, location: &str, view: &View) -> Result<Report> {
+        let mut r = Report::new(format!("AerisWeather - {}", view.lang.tr("current")));
         let res = self
             .request_hourly(location, "now")
             .await
             .context("Failed to make hourly API request")?;
         let parsed = parse_hourly(res).await?;
-        report::report_hourly(&mut r, parsed);
+        report::report_hourly(&mut r, parsed, view);
         Ok(r)
     }
 }
 
 impl AerisWeatherProvider {
+    /// Waits until the rate limit, if there is one, lets a request go
+    async fn throttle(&self) {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire().await;
+        }
+    }
+
     async fn request_hourly(
         &self,
         location: &str,
         for_param: &str,
     ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
         self.client
             .get(self.url_hourly(location))
             .query(&[
@@ -175,11 +286,27 @@ impl AerisWeatherProvider {
             .await
     }
 
+    /// Hourly periods from the start of the location's day to the start of the next one
+    async fn request_day_hours(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_hourly(location))
+            .query(&[
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+            ])
+            .query(&[("plimit", "24"), ("format", "json"), ("filter", "1hr")])
+            .query(&[("from", "today"), ("to", "tomorrow")])
+            .send()
+            .await
+    }
+
     async fn request_daily(
         &self,
         location: &str,
         for_param: &str,
     ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
         self.client
             .get(self.url_daily(location))
             .query(&[
@@ -191,27 +318,184 @@ impl AerisWeatherProvider {
             .send()
             .await
     }
+
+    /// A daily summary per day of the range, both dates are inclusive
+    async fn request_daily_range(
+        &self,
+        location: &str,
+        range: DayRange,
+    ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        let limit = range.days().count().to_string();
+        self.client
+            .get(self.url_daily(location))
+            .query(&[
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+            ])
+            .query(&[("format", "json"), ("plimit", limit.as_str())])
+            .query(&[
+                ("from", range.from.format("%Y/%m/%d").to_string()),
+                ("to", range.to.format("%Y/%m/%d").to_string()),
+            ])
+            .send()
+            .await
+    }
+
+    async fn request_air_quality(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_air_quality(location))
+            .query(&[
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+            ])
+            .query(&[("format", "json")])
+            .send()
+            .await
+    }
+
+    async fn request_tides(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_tides(location))
+            .query(&[
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+            ])
+            .query(&[("format", "json"), ("from", "today"), ("to", "+2days")])
+            .send()
+            .await
+    }
+
+    async fn request_alerts(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_alerts(location))
+            .query(&[
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+            ])
+            .query(&[("format", "json")])
+            .send()
+            .await
+    }
 }
 
 #[async_trait]
 impl Provider for AerisWeatherProvider {
+    fn capabilities(&self) -> Capabilities {
+        CAPABILITIES
+    }
+
+    async fn location_now(&self, location: &str) -> Result<DateTime<FixedOffset>> {
+        let res = self
+            .request_hourly(location, "now")
+            .await
+            .context("Failed to make hourly API request")?;
+        let parsed = parse_hourly(res).await?;
+        let offset = report::location_offset(&parsed)
+            .context("Couldn't tell the location's offset from UTC")?;
+        Ok(Utc::now().with_timezone(&offset))
+    }
+
+    /// Alerts have an endpoint of their own, they are asked for alongside the conditions
     async fn run(&self, wc: WeatherCommand) -> Result<Report> {
-        match wc.date {
-            DateOffsetRepresentation::DayOffset(days) => {
-                if days == 0 {
-                    self.branch_current(&wc.location).await
-                } else {
-                    self.branch_daily(&wc.location, days).await
+        let view = wc.view();
+        let report = async {
+            match wc.date {
+                DateOffsetRepresentation::DayOffset(days) => {
+                    if days == 0 {
+                        self.branch_current(&wc.location, &view).await
+                    } else {
+                        self.branch_daily(&wc.location, days, &view).await
+                    }
                 }
-            }
-            DateOffsetRepresentation::HourOffset(hours) => {
-                if hours == 0 {
-                    self.branch_current(&wc.location).await
-                } else {
-                    self.branch_hourly(&wc.location, hours).await
+                DateOffsetRepresentation::HourOffset(hours) => {
+                    if hours == 0 {
+                        self.branch_current(&wc.location, &view).await
+                    } else {
+                        self.branch_hourly(&wc.location, hours, &view).await
+                    }
                 }
+                DateOffsetRepresentation::Now => self.branch_hourly(&wc.location, 0, &view).await,
+                DateOffsetRepresentation::DayRange(range) => {
+                    self.branch_range(&wc.location, range, &view).await
+                }
+            }
+        };
+        let alerts = async {
+            if wc.alerts {
+                Some(self.alerts(&wc.location).await)
+            } else {
+                None
             }
-            DateOffsetRepresentation::Now => self.branch_hourly(&wc.location, 0).await,
+        };
+        let (report, alerts) = futures::join!(report, alerts);
+        let mut report = report?;
+        match alerts {
+            Some(Ok(alerts)) => alerts::add_to_report(&mut report, alerts, &view.clock(None)),
+            Some(Err(e)) => log::warn!("Couldn't obtain weather alerts: {:#}", e),
+            None => {}
         }
+        Ok(report)
+    }
+
+    async fn alerts(&self, location: &str) -> Result<Alerts> {
+        let res = self
+            .request_alerts(location)
+            .await
+            .context("Failed to make alerts API request")?;
+        let parsed = parse_alerts(res).await?;
+        Ok(report::normalize_alerts(parsed))
+    }
+
+    async fn air_quality(&self, location: &str) -> Result<AirQuality> {
+        let res = self
+            .request_air_quality(location)
+            .await
+            .context("Failed to make air quality API request")?;
+        let parsed = parse_air_quality(res).await?;
+        report::normalize_air_quality(parsed).context("No air quality data for the location")
+    }
+
+    async fn astronomy(&self, location: &str, date: NaiveDate) -> Result<Astronomy> {
+        let res = self
+            .request_hourly(location, "now")
+            .await
+            .context("Failed to make hourly API request")?;
+        let parsed = parse_hourly(res).await?;
+        report::calculate_astronomy(parsed, date).context("No coordinates for the location")
+    }
+
+    async fn marine(&self, location: &str) -> Result<Marine> {
+        let res = self
+            .request_tides(location)
+            .await
+            .context("Failed to make tides API request")?;
+        let parsed = parse_tides(res).await?;
+        Ok(report::normalize_tides(parsed))
+    }
+
+    async fn history(&self, location: &str, range: DayRange) -> Result<Vec<Conditions>> {
+        let mut days = Vec::new();
+        for chunk in range.chunks(HISTORY_CHUNK_DAYS) {
+            let res = self
+                .request_daily_range(location, chunk)
+                .await
+                .context("Failed to make daily API request")?;
+            let parsed = parse_daily(res).await?;
+            days.extend(report::normalize_daily(parsed));
+        }
+        Ok(days)
+    }
+
+    async fn hourly(&self, location: &str) -> Result<HourlyForecast> {
+        let res = self
+            .request_day_hours(location)
+            .await
+            .context("Failed to make hourly API request")?;
+        let parsed = parse_hourly(res).await?;
+        report::hourly_forecast(parsed).context("No hourly data for the location")
     }
 }
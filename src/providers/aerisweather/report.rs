@@ -1,45 +1,237 @@
 use super::api;
-use crate::report::{Report, ReportSection, SectionRepr};
-
-pub fn report_hourly(report: &mut Report, hourly: api::hourly::Json) {
-    hourly.response.into_iter().for_each(|r| {
-        r.periods.into_iter().for_each(|p| {
-            let mut details = SectionRepr::new();
-            details.push(("Condition".to_owned(), p.weather));
-            details.push(("Wind direction".to_owned(), p.wind_dir));
-            details.push(("Temperature, C".to_owned(), format!("{}°", p.temp_c)));
-            details.push(("Temperature, F".to_owned(), format!("{}°", p.temp_f)));
-            details.push(("Humidity".to_owned(), format!("{}%", p.humidity)));
-            let rs = ReportSection::new(
-                p.timestamp.format("%d/%m/%Y %I:%M %p (UTC)").to_string(),
-                details,
-            );
-            report.add_section(rs);
+use crate::{
+    air_quality::AirQuality,
+    alerts::{Alert, Alerts, Severity},
+    astronomy::{self, Astronomy},
+    conditions::{Conditions, HourlyForecast, Sky, View},
+    marine::{Marine, Tide, TideKind},
+    report::{Report, ReportSection},
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+
+/// Kind of sky of a coded weather, the weather part is a cloud code when there is no precipitation
+/// Codes are listed at https://www.aerisweather.com/support/docs/api/reference/weather-codes/
+fn sky(coded: &str) -> Option<Sky> {
+    let sky = match coded.rsplit(':').next()? {
+        "CL" | "FW" => Sky::Clear,
+        "SC" => Sky::PartlyCloudy,
+        "BK" | "OV" => Sky::Cloudy,
+        "F" | "BR" | "H" | "K" | "IF" | "ZF" | "BD" | "BN" | "VA" => Sky::Fog,
+        "T" | "A" | "WP" => Sky::Storm,
+        "S" | "SW" | "BS" | "IC" | "IP" | "RS" | "SI" | "WM" => Sky::Snow,
+        "R" | "RW" | "L" | "ZL" | "ZR" | "UP" => Sky::Rain,
+        _ => return None,
+    };
+    Some(sky)
+}
+
+pub fn normalize_hourly(hourly: api::hourly::Json) -> Vec<Conditions> {
+    hourly
+        .response
+        .into_iter()
+        .flat_map(|r| r.periods)
+        .map(|p| Conditions {
+            time: p.timestamp,
+            sky: p.weather_primary_coded.as_deref().and_then(sky),
+            condition: Some(p.weather),
+            temp_c: Some(p.temp_c),
+            feels_like_c: p.feelslike_c,
+            dew_point_c: p.dewpoint_c,
+            humidity: Some(p.humidity as f64),
+            pressure_mb: p.pressure_m_b,
+            precip_mm: p.precip_m_m,
+            precip_chance: p.pop,
+            uv: p.uvi,
+            visibility_km: p.visibility_k_m,
+            wind_kph: p.wind_speed_k_p_h,
+            gust_kph: p.wind_gust_k_p_h,
+            wind_dir: Some(p.wind_dir),
+            cloud_cover: p.sky,
+            snow_cm: p.snow_c_m,
+            ..Default::default()
         })
+        .collect()
+}
+
+/// Offset from UTC of the location the periods are for
+pub fn location_offset(hourly: &api::hourly::Json) -> Option<FixedOffset> {
+    hourly
+        .response
+        .first()
+        .and_then(|r| FixedOffset::east_opt(r.profile.tzoffset))
+}
+
+/// Hourly periods along with the location's offset from UTC
+pub fn hourly_forecast(hourly: api::hourly::Json) -> Option<HourlyForecast> {
+    let utc_offset = location_offset(&hourly)?;
+    Some(HourlyForecast {
+        utc_offset,
+        hours: normalize_hourly(hourly),
     })
 }
 
-pub fn report_daily(report: &mut Report, daily: api::daily::Json) {
-    daily.response.into_iter().for_each(|r| {
-        r.periods.into_iter().for_each(|p| {
-            let mut details = SectionRepr::new();
-            details.push(("Condition".to_owned(), p.weather.phrase));
-            details.push(("Average temp., C".to_owned(), format!("{}°", p.temp.avg_c)));
-            details.push(("Average temp., F".to_owned(), format!("{}°", p.temp.avg_f)));
-            details.push(("Min. temp., C".to_owned(), format!("{}°", p.temp.min_c)));
-            details.push(("Max. temp., C".to_owned(), format!("{}°", p.temp.max_c)));
-            details.push(("Min. temp., F".to_owned(), format!("{}°", p.temp.min_f)));
-            details.push(("Max. temp., F".to_owned(), format!("{}°", p.temp.max_f)));
-            details.push((
-                "Average humidity".to_owned(),
-                format!("{}%", p.humidity.avg),
-            ));
-            details.push(("Wind direction".to_owned(), p.wind_speed.max_dir));
-            let rs = ReportSection::new(
-                p.timestamp.format("%d/%m/%Y %I:%M %p (UTC)").to_string(),
-                details,
-            );
-            report.add_section(rs);
+/// Daily periods start at the location's midnight, the date on its wall clock is what is kept
+fn day_of(period: &api::daily::Periods) -> DateTime<Utc> {
+    let date = period
+        .date_time_i_s_o
+        .map(|t| t.date_naive())
+        .unwrap_or_else(|| period.timestamp.date_naive());
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("Midnight is a valid time"))
+}
+
+/// Daily periods are summarized with averages, apart from the peak wind and UV index
+pub fn normalize_daily(daily: api::daily::Json) -> Vec<Conditions> {
+    daily
+        .response
+        .into_iter()
+        .flat_map(|r| r.periods)
+        .map(|p| Conditions {
+            time: day_of(&p),
+            sky: p.weather.primary_coded.as_deref().and_then(sky),
+            condition: Some(p.weather.phrase),
+            temp_c: Some(p.temp.avg_c),
+            temp_min_c: Some(p.temp.min_c),
+            temp_max_c: Some(p.temp.max_c),
+            feels_like_c: p.feelslike.and_then(|f| f.avg_c),
+            dew_point_c: p.dewpoint.and_then(|d| d.avg_c),
+            humidity: Some(p.humidity.avg),
+            pressure_mb: Some(p.pressure.avg_m_b),
+            precip_mm: p.precip.and_then(|p| p.total_m_m),
+            uv: p.uvi.and_then(|u| u.max),
+            visibility_km: p.visibility.and_then(|v| v.avg_k_m),
+            wind_kph: p.wind_speed.max_k_p_h,
+            gust_kph: p.wind_gust.and_then(|g| g.max_k_p_h),
+            wind_dir: Some(p.wind_speed.max_dir),
+            cloud_cover: p.sky.and_then(|s| s.avg),
+            snow_cm: p.snow.and_then(|s| s.total_c_m),
+            ..Default::default()
         })
+        .collect()
+}
+
+/// Hourly periods are titled with their time on the view's clock
+pub fn report_hourly(report: &mut Report, hourly: api::hourly::Json, view: &View) {
+    let clock = view.clock(location_offset(&hourly));
+    normalize_hourly(hourly).into_iter().for_each(|p| {
+        let title = clock.format(p.time);
+        report.add_section(ReportSection::from_conditions(title, p, view));
+    })
+}
+
+fn add_days(report: &mut Report, days: Vec<Conditions>, view: &View) {
+    days.into_iter().for_each(|p| {
+        let title = p.time.format(view.lang.date_format()).to_string();
+        report.add_section(ReportSection::from_conditions(title, p, view));
     })
 }
+
+pub fn report_daily(report: &mut Report, daily: api::daily::Json, view: &View) {
+    add_days(report, normalize_daily(daily), view)
+}
+
+/// Daily periods in chronological order, regardless of the order they were returned in
+pub fn report_range(report: &mut Report, daily: api::daily::Json, view: &View) {
+    let mut days = normalize_daily(daily);
+    days.sort_by_key(|d| d.time);
+    add_days(report, days, view)
+}
+
+/// Severity is derived from the VTEC significance code: warnings, watches and advisories
+fn alert_severity(details: &api::alerts::Details) -> Severity {
+    if details.emergency {
+        return Severity::Extreme;
+    }
+    match details.kind.rsplit('.').next() {
+        Some("W") => Severity::Severe,
+        Some("A") => Severity::Moderate,
+        Some("Y") | Some("S") => Severity::Minor,
+        _ => Severity::Unknown,
+    }
+}
+
+pub fn normalize_alerts(alerts: api::alerts::Json) -> Alerts {
+    let utc_offset = alerts
+        .response
+        .iter()
+        .find_map(|r| r.timestamps.begins_iso.map(|begins| *begins.offset()));
+    let alerts = alerts
+        .response
+        .into_iter()
+        .map(|r| Alert {
+            severity: alert_severity(&r.details),
+            event: r.details.name,
+            area: Some(format!(
+                "{}, {}, {}",
+                r.place.name, r.place.state, r.place.country
+            )),
+            start: Some(r.timestamps.begins),
+            end: Some(r.timestamps.expires),
+            description: r.details.body,
+        })
+        .collect();
+    Alerts { alerts, utc_offset }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn normalize_air_quality(air: api::air_quality::Json) -> Option<AirQuality> {
+    let period = air.response.into_iter().flat_map(|r| r.periods).next()?;
+    let pollutant = |kind: &str| {
+        period
+            .pollutants
+            .iter()
+            .find(|p| p.kind == kind)
+            .and_then(|p| p.value_ugm3)
+    };
+    Some(AirQuality {
+        aqi: Some(period.aqi),
+        category: Some(capitalize(&period.category)),
+        us_epa_index: None,
+        gb_defra_index: None,
+        pm2_5: pollutant("pm2.5"),
+        pm10: pollutant("pm10"),
+        o3: pollutant("o3"),
+        no2: pollutant("no2"),
+    })
+}
+
+/// AerisWeather isn't asked for sun and moon data, it is calculated from the location instead
+pub fn calculate_astronomy(hourly: api::hourly::Json, date: NaiveDate) -> Option<Astronomy> {
+    let response = hourly.response.into_iter().next()?;
+    let utc_offset = FixedOffset::east_opt(response.profile.tzoffset)?;
+    Some(astronomy::calculate(
+        date,
+        response.loc.lat,
+        response.loc.long,
+        utc_offset,
+    ))
+}
+
+/// Tides are the only marine data that is requested from AerisWeather
+pub fn normalize_tides(tides: api::tides::Json) -> Marine {
+    let tides = tides
+        .response
+        .into_iter()
+        .flat_map(|r| r.periods)
+        .map(|p| Tide {
+            time: p.timestamp,
+            kind: if p.kind == "h" {
+                TideKind::High
+            } else {
+                TideKind::Low
+            },
+            height_m: p.height_m,
+        })
+        .collect();
+    Marine {
+        conditions: Vec::new(),
+        tides,
+        utc_offset: None,
+    }
+}
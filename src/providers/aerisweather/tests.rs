@@ -1,7 +1,23 @@
-use super::api;
+use super::{api, report, AerisWeatherProvider};
+use crate::{
+    alerts::Severity,
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    conditions::{Sky, View},
+    marine::TideKind,
+    report::Report,
+    types::{Provider, TimeZoneChoice},
+};
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
 
-const HOURLY_MOCK: &'static str = include_str!("./fixtures/hourly.json");
-const DAILY_MOCK: &'static str = include_str!("./fixtures/daily.json");
+const HOURLY_MOCK: &str = include_str!("./fixtures/hourly.json");
+const DAILY_MOCK: &str = include_str!("./fixtures/daily.json");
+const ALERTS_MOCK: &str = include_str!("./fixtures/alerts.json");
+const AIR_QUALITY_MOCK: &str = include_str!("./fixtures/air_quality.json");
+const TIDES_MOCK: &str = include_str!("./fixtures/tides.json");
+const TIDES_ERROR_MOCK: &str = include_str!("./fixtures/tides_error.json");
 
 #[test]
 fn test_parse_hourly() {
@@ -12,3 +28,160 @@ fn test_parse_hourly() {
 fn test_parse_daily() {
     let _daily = serde_json::from_str::<api::daily::Json>(DAILY_MOCK).expect("Failed to parse");
 }
+
+#[test]
+fn test_normalize_periods() {
+    let hourly = serde_json::from_str::<api::hourly::Json>(HOURLY_MOCK).expect("Failed to parse");
+    let hours = report::normalize_hourly(hourly);
+    assert_eq!(hours[0].dew_point_c, Some(-1.65));
+    assert_eq!(hours[0].gust_kph, Some(25.93));
+    assert_eq!(hours[0].cloud_cover, Some(88.0));
+    assert_eq!(hours[0].sky, Some(Sky::Cloudy));
+
+    let daily = serde_json::from_str::<api::daily::Json>(DAILY_MOCK).expect("Failed to parse");
+    let days = report::normalize_daily(daily);
+    assert_eq!(days[0].pressure_mb, Some(1011.8));
+    assert_eq!(days[0].feels_like_c, Some(3.81));
+    assert_eq!(days[0].wind_kph, Some(24.08));
+    assert_eq!(days[0].uv, Some(3.0));
+    assert_eq!(days[0].sky, Some(Sky::PartlyCloudy));
+    // the day starts at 21:00 UTC of the previous day, it is still the 31st at the location
+    assert_eq!(days[0].time.date_naive().to_string(), "2023-03-31");
+}
+
+#[test]
+fn test_hourly_titles_on_clock() {
+    let hourly = serde_json::from_str::<api::hourly::Json>(HOURLY_MOCK).expect("Failed to parse");
+    let mut r = Report::new("AerisWeather - current");
+    report::report_hourly(&mut r, hourly, &View::default());
+    assert_eq!(r.sections()[0].title(), "31/03/2023 03:47 PM (UTC+03:00)");
+
+    let hourly = serde_json::from_str::<api::hourly::Json>(HOURLY_MOCK).expect("Failed to parse");
+    let mut r = Report::new("AerisWeather - current");
+    report::report_hourly(
+        &mut r,
+        hourly,
+        &View::default().with_tz(TimeZoneChoice::Utc),
+    );
+    assert_eq!(r.sections()[0].title(), "31/03/2023 12:47 PM (UTC)");
+}
+
+#[test]
+fn test_normalize_alerts() {
+    let alerts = serde_json::from_str::<api::alerts::Json>(ALERTS_MOCK).expect("Failed to parse");
+    let alerts = report::normalize_alerts(alerts);
+    assert_eq!(alerts.utc_offset, chrono::FixedOffset::west_opt(5 * 3600));
+    let alerts = alerts.alerts;
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0].event, "FLASH FLOOD WARNING");
+    assert_eq!(alerts[0].severity, Severity::Severe);
+    assert_eq!(alerts[1].severity, Severity::Minor);
+    assert_eq!(alerts[1].area, Some("houston, tx, us".to_owned()));
+}
+
+#[test]
+fn test_normalize_air_quality() {
+    let air =
+        serde_json::from_str::<api::air_quality::Json>(AIR_QUALITY_MOCK).expect("Failed to parse");
+    let air = report::normalize_air_quality(air).expect("No air quality period");
+    assert_eq!(air.aqi, Some(57.0));
+    assert_eq!(air.category, Some("Moderate".to_owned()));
+    assert_eq!(air.pm2_5, Some(15.1));
+    assert_eq!(air.no2, Some(11.3));
+}
+
+#[test]
+fn test_calculate_astronomy() {
+    let hourly = serde_json::from_str::<api::hourly::Json>(HOURLY_MOCK).expect("Failed to parse");
+    let date = chrono::NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+    let astronomy = report::calculate_astronomy(hourly, date).expect("No astronomy data");
+    assert_eq!(
+        astronomy.sunrise.map(|t| t.format("%H").to_string()),
+        Some("06".to_owned())
+    );
+    assert_eq!(astronomy.moon_phase.as_deref(), Some("Waxing Gibbous"));
+}
+
+#[tokio::test]
+async fn test_tides() {
+    let mock_server = MockServer::start().await;
+
+    let tides_mock = Mock::given(method("GET"))
+        .and(path("/tides/94109"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(TIDES_MOCK),
+        );
+    let error_mock = Mock::given(method("GET"))
+        .and(path("/tides/nowhere"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(TIDES_ERROR_MOCK),
+        );
+    mock_server.register(tides_mock).await;
+    mock_server.register(error_mock).await;
+    let aerisweather = AerisWeatherProvider::new()
+        .with_base_url(format!("http://{}", mock_server.address()))
+        .with_credentials("id", "secret");
+
+    let marine = aerisweather
+        .marine("94109")
+        .await
+        .expect("Couldn't obtain the tides");
+    assert_eq!(marine.tides.len(), 3);
+    assert_eq!(marine.tides[1].kind, TideKind::Low);
+    assert_eq!(marine.tides[1].height_m, Some(0.24));
+    assert!(marine.conditions.is_empty());
+
+    let error = aerisweather
+        .marine("nowhere")
+        .await
+        .expect_err("Tides shouldn't be found");
+    assert_eq!(error.to_string(), "The requested location was not found.");
+}
+
+#[tokio::test]
+async fn test_range() {
+    // the summaries of the range come back newest first
+    let mut daily: serde_json::Value = serde_json::from_str(DAILY_MOCK).expect("Failed to parse");
+    let periods = daily["response"][0]["periods"]
+        .as_array_mut()
+        .expect("No periods");
+    let mut next_day = periods[0].clone();
+    next_day["timestamp"] = serde_json::json!(1680296400);
+    next_day["dateTimeISO"] = serde_json::json!("2023-04-01T00:00:00+03:00");
+    periods.insert(0, next_day);
+
+    let mock_server = MockServer::start().await;
+    let range_mock = Mock::given(method("GET"))
+        .and(path("/conditions/summary/Zaporizhzhia"))
+        .and(query_param("from", "2023/03/31"))
+        .and(query_param("to", "2023/04/01"))
+        .and(query_param("plimit", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_json(daily),
+        )
+        .expect(1);
+    mock_server.register(range_mock).await;
+    let aerisweather = AerisWeatherProvider::new()
+        .with_base_url(format!("http://{}", mock_server.address()))
+        .with_credentials("id", "secret");
+
+    let range = DayRange::new(
+        chrono::NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date"),
+        chrono::NaiveDate::from_ymd_opt(2023, 4, 1).expect("Bad date"),
+    )
+    .expect("Bad range");
+    let wc = WeatherCommand::new("Zaporizhzhia", DateOffsetRepresentation::DayRange(range));
+    let report = aerisweather
+        .run(wc)
+        .await
+        .expect("Couldn't obtain the range");
+    assert_eq!(report.title(), "AerisWeather - range(daily)");
+    let titles: Vec<&str> = report.sections().iter().map(|s| s.title()).collect();
+    assert_eq!(titles, vec!["31/03/2023", "01/04/2023"]);
+}
@@ -1,3 +1,434 @@
+use crate::{
+    capabilities::Capabilities,
+    command::WeatherCommand,
+    configuration::Profile,
+    types::{AvailableProviders, Provider, Unsupported},
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
 pub mod accuweather;
 pub mod aerisweather;
 pub mod weatherapi;
+
+/// Spaces out the requests of an adapter so that no more than a number are sent per minute
+#[derive(Debug)]
+pub struct RateLimit {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Waits for the next free slot, slots are handed out in the order they are asked for
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            let now = Instant::now();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + self.interval);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// Construct a provider adapter with the credentials a profile holds for it, optionally rate limited
+pub fn build_provider(
+    profile: &Profile,
+    provider: &AvailableProviders,
+    rate_limit: Option<RateLimit>,
+) -> Result<Box<dyn Provider>> {
+    match provider {
+        // AvailableProviders::AccuWeather => {
+        // let api_key = profile
+        // .accuweather_api_key
+        // .clone()
+        // .context("You haven't set AccuWeather API key")?;
+        // Ok(Box::new(accuweather::AccuWeatherProvider::new().with_api_key(api_key)))
+        // }
+        AvailableProviders::WeatherAPI => {
+            let api_key = profile
+                .weatherapi_api_key
+                .clone()
+                .context("You haven't set WeatherAPI API key")?;
+            let adapter = weatherapi::WeatherAPIProvider::default()
+                .with_api_key(api_key)
+                .with_history_days(capabilities(profile, provider).history_days);
+            Ok(Box::new(match rate_limit {
+                Some(rate_limit) => adapter.with_rate_limit(rate_limit),
+                None => adapter,
+            }))
+        }
+        AvailableProviders::AerisWeather => {
+            let client_id = profile
+                .aerisweather_client_id
+                .clone()
+                .context("You haven't set AerisWeather client id")?;
+            let client_secret = profile
+                .aerisweather_client_secret
+                .clone()
+                .context("You haven't set AerisWeather client secret")?;
+            let adapter = aerisweather::AerisWeatherProvider::default()
+                .with_credentials(client_id, client_secret);
+            Ok(Box::new(match rate_limit {
+                Some(rate_limit) => adapter.with_rate_limit(rate_limit),
+                None => adapter,
+            }))
+        }
+    }
+}
+
+/// What the adapter of a provider can be asked for with a profile's plan, known without credentials
+pub fn capabilities(profile: &Profile, provider: &AvailableProviders) -> Capabilities {
+    match provider {
+        AvailableProviders::WeatherAPI => Capabilities {
+            history_days: profile
+                .weatherapi_history_days
+                .unwrap_or(weatherapi::CAPABILITIES.history_days),
+            ..weatherapi::CAPABILITIES
+        },
+        AvailableProviders::AerisWeather => aerisweather::CAPABILITIES,
+    }
+}
+
+/// Whether a profile holds every credential a provider needs
+pub fn has_credentials(profile: &Profile, provider: &AvailableProviders) -> bool {
+    match provider {
+        AvailableProviders::WeatherAPI => profile.weatherapi_api_key.is_some(),
+        AvailableProviders::AerisWeather => {
+            profile.aerisweather_client_id.is_some() && profile.aerisweather_client_secret.is_some()
+        }
+    }
+}
+
+/// An adapter ready to be routed to, or why it couldn't be built
+type Built = Result<Arc<dyn Provider>, String>;
+
+/// Adapters of the providers a profile is configured for, in the order they are preferred
+/// They are built once, so that long-running commands can route every request among them
+pub struct Adapters {
+    adapters: Vec<(AvailableProviders, Built)>,
+}
+
+impl Adapters {
+    /// Providers without credentials are left out, the ones that fail to build are kept with the reason
+    pub fn new(profile: &Profile) -> Self {
+        Self::build(profile, None)
+    }
+
+    /// Like `new`, but every adapter sends at most a number of requests per minute
+    pub fn rate_limited(profile: &Profile, per_minute: u32) -> Self {
+        Self::build(profile, Some(per_minute))
+    }
+
+    fn build(profile: &Profile, per_minute: Option<u32>) -> Self {
+        let adapters = profile
+            .provider_preference()
+            .into_iter()
+            .filter(|provider| has_credentials(profile, provider))
+            .map(|provider| {
+                let rate_limit = per_minute.map(RateLimit::per_minute);
+                let adapter = build_provider(profile, &provider, rate_limit)
+                    .map(Arc::from)
+                    .map_err(|e| format!("{:#}", e));
+                (provider, adapter)
+            })
+            .collect();
+        Self { adapters }
+    }
+
+    /// The adapter of a provider, whatever its place in the order of preference
+    pub fn get(&self, provider: &AvailableProviders) -> Result<Arc<dyn Provider>> {
+        match self.adapters.iter().find(|(p, _)| p == provider) {
+            Some((_, Ok(adapter))) => Ok(adapter.clone()),
+            Some((_, Err(e))) => bail!("{:?} couldn't be set up: {}", provider, e),
+            None => bail!(
+                "You haven't configured {:?} yet, please run >weather configure <provider> first",
+                provider
+            ),
+        }
+    }
+
+    /// Pick the most preferred adapter whose capabilities pass a check
+    /// The choice and the reason for it are logged at the info level, as `--verbose` shows them
+    pub fn route<F>(&self, check: F) -> Result<(AvailableProviders, Arc<dyn Provider>)>
+    where
+        F: Fn(&Capabilities) -> Result<(), String>,
+    {
+        let mut rejected = Vec::new();
+        for (provider, adapter) in &self.adapters {
+            let adapter = match adapter {
+                Ok(adapter) => adapter,
+                Err(e) => {
+                    rejected.push(format!("{:?} couldn't be set up: {}", provider, e));
+                    continue;
+                }
+            };
+            match check(&adapter.capabilities()) {
+                Ok(()) => {
+                    if rejected.is_empty() {
+                        log::info!(
+                            "Using {:?}, the most preferred configured provider",
+                            provider
+                        );
+                    } else {
+                        log::info!("Using {:?}, as {}", provider, rejected.join("; "));
+                    }
+                    return Ok((provider.clone(), adapter.clone()));
+                }
+                Err(reason) => rejected.push(format!(
+                    "{:?} can't serve this request: {}",
+                    provider, reason
+                )),
+            }
+        }
+        if rejected.is_empty() {
+            bail!(
+                "You haven't configured a provider yet, please run >weather configure <provider> first"
+            );
+        }
+        bail!(
+            "No configured provider can serve this request: {}",
+            rejected.join("; ")
+        )
+    }
+
+    /// Route a command whose days are relative to today, counting them on the location's calendar
+    /// It's built on UTC's calendar to pick an adapter that tells the location's time, then built and routed again
+    pub async fn route_dated<B, F>(
+        &self,
+        build: B,
+        check: F,
+    ) -> Result<(WeatherCommand, DateTime<FixedOffset>, Arc<dyn Provider>)>
+    where
+        B: Fn(DateTime<FixedOffset>) -> Result<WeatherCommand>,
+        F: Fn(&Capabilities, &WeatherCommand, NaiveDate) -> Result<(), String>,
+    {
+        let utc_now = Utc::now().fixed_offset();
+        let command = build(utc_now)?;
+        let (_, adapter) = self.route(|c| check(c, &command, utc_now.date_naive()))?;
+        let now = location_now(adapter.as_ref(), &command.location).await;
+        let command = build(now)?;
+        let (_, adapter) = self.route(|c| check(c, &command, now.date_naive()))?;
+        Ok((command, now, adapter))
+    }
+}
+
+/// Adapters built elsewhere, e.g. against mock servers, in the order they are preferred
+impl From<Vec<(AvailableProviders, Arc<dyn Provider>)>> for Adapters {
+    fn from(adapters: Vec<(AvailableProviders, Arc<dyn Provider>)>) -> Self {
+        Self {
+            adapters: adapters
+                .into_iter()
+                .map(|(provider, adapter)| (provider, Ok(adapter)))
+                .collect(),
+        }
+    }
+}
+
+/// Current time at a location, or in UTC when the adapter can't tell it
+pub async fn location_now(adapter: &dyn Provider, location: &str) -> DateTime<FixedOffset> {
+    match adapter.location_now(location).await {
+        Ok(now) => now,
+        Err(e) => {
+            if !Unsupported::is_unsupported(&e) {
+                log::warn!(
+                    "Counting days in UTC, as the location's time is unknown: {:#}",
+                    e
+                );
+            }
+            Utc::now().fixed_offset()
+        }
+    }
+}
+
+/// Pick the most preferred configured provider whose capabilities pass a check
+pub fn route<F>(profile: &Profile, check: F) -> Result<(AvailableProviders, Arc<dyn Provider>)>
+where
+    F: Fn(&Capabilities) -> Result<(), String>,
+{
+    Adapters::new(profile).route(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capabilities::Feature,
+        command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+        report::Report,
+    };
+    use async_trait::async_trait;
+    use chrono::{NaiveDate, TimeZone};
+    use std::sync::Mutex;
+
+    /// Tells a fixed time for every location and builds no reports
+    struct Clocked(DateTime<FixedOffset>);
+
+    #[async_trait]
+    impl Provider for Clocked {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::NONE
+        }
+
+        async fn run(&self, _command: WeatherCommand) -> Result<Report> {
+            bail!("Reports aren't built")
+        }
+
+        async fn location_now(&self, _location: &str) -> Result<DateTime<FixedOffset>> {
+            Ok(self.0)
+        }
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            default_provider: Some(AvailableProviders::WeatherAPI),
+            weatherapi_api_key: Some("22222".to_owned()),
+            aerisweather_client_id: Some("id".to_owned()),
+            aerisweather_client_secret: Some("secret".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn route_command(
+        profile: &Profile,
+        date: DateOffsetRepresentation,
+    ) -> Result<AvailableProviders> {
+        let today = NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+        let command = WeatherCommand::new("Kyiv", date);
+        route(profile, |c| c.check_command(&command, today)).map(|(provider, _)| provider)
+    }
+
+    #[test]
+    fn test_route_by_capabilities() {
+        let profile = profile();
+        assert_eq!(
+            route_command(&profile, DateOffsetRepresentation::DayOffset(10))
+                .expect("No provider for 10 days ahead"),
+            AvailableProviders::WeatherAPI
+        );
+        // WeatherAPI doesn't serve hourly offsets
+        assert_eq!(
+            route_command(&profile, DateOffsetRepresentation::HourOffset(-5))
+                .expect("No provider for 5 hours back"),
+            AvailableProviders::AerisWeather
+        );
+        // only AerisWeather reaches 15 days ahead
+        assert_eq!(
+            route_command(&profile, DateOffsetRepresentation::DayOffset(15))
+                .expect("No provider for 15 days ahead"),
+            AvailableProviders::AerisWeather
+        );
+        assert!(route_command(&profile, DateOffsetRepresentation::DayOffset(30)).is_err());
+    }
+
+    #[test]
+    fn test_route_by_history_depth() {
+        let mut profile = profile();
+        // the free plan keeps a week of WeatherAPI history
+        assert_eq!(
+            route_command(&profile, DateOffsetRepresentation::DayOffset(-30))
+                .expect("No provider for a month back"),
+            AvailableProviders::AerisWeather
+        );
+        profile.set_weatherapi_history_days(Some(365));
+        assert_eq!(
+            route_command(&profile, DateOffsetRepresentation::DayOffset(-30))
+                .expect("No provider for a month back"),
+            AvailableProviders::WeatherAPI
+        );
+    }
+
+    #[test]
+    fn test_route_by_preference() {
+        let mut profile = profile();
+        profile.set_provider_order(vec![AvailableProviders::AerisWeather]);
+        assert_eq!(
+            route(&profile, |c| c.check_feature(Feature::Alerts))
+                .expect("No provider for alerts")
+                .0,
+            AvailableProviders::AerisWeather
+        );
+        // unconfigured providers are skipped
+        profile.set_aerisweather_client_secret(None, None);
+        assert_eq!(
+            route(&profile, |c| c.check_feature(Feature::Alerts))
+                .expect("No provider for alerts")
+                .0,
+            AvailableProviders::WeatherAPI
+        );
+        profile.set_weatherapi_api_key(None);
+        assert!(route(&profile, |_| Ok(())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_dated() {
+        // early on the 1st in Kyiv, while it's still the 31st in UTC
+        let kyiv = FixedOffset::east_opt(3 * 3600)
+            .expect("Bad offset")
+            .with_ymd_and_hms(2023, 4, 1, 1, 30, 0)
+            .single()
+            .expect("Bad time");
+        let adapters = Adapters::from(vec![(
+            AvailableProviders::WeatherAPI,
+            Arc::new(Clocked(kyiv)) as Arc<dyn Provider>,
+        )]);
+        let checked = Mutex::new(Vec::new());
+        let (command, now, _) = adapters
+            .route_dated(
+                |now| {
+                    let today = now.date_naive();
+                    let range = DayRange::new(today, today).context("Bad range")?;
+                    Ok(WeatherCommand::new(
+                        "Kyiv",
+                        DateOffsetRepresentation::DayRange(range),
+                    ))
+                },
+                |_, _, today| {
+                    checked.lock().expect("Poisoned").push(today);
+                    Ok(())
+                },
+            )
+            .await
+            .expect("Couldn't route");
+        let first = NaiveDate::from_ymd_opt(2023, 4, 1).expect("Bad date");
+        assert_eq!(now, kyiv);
+        assert_eq!(
+            command.date,
+            DateOffsetRepresentation::DayRange(DayRange::new(first, first).expect("Bad range"))
+        );
+        assert_eq!(checked.lock().expect("Poisoned").last(), Some(&first));
+    }
+
+    #[test]
+    fn test_get() {
+        let mut profile = profile();
+        profile.set_aerisweather_client_secret(None, None);
+        let adapters = Adapters::rate_limited(&profile, 60);
+        assert!(adapters.get(&AvailableProviders::WeatherAPI).is_ok());
+        assert!(adapters.get(&AvailableProviders::AerisWeather).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let rate_limit = RateLimit::per_minute(6000);
+        let started = Instant::now();
+        for _ in 0..3 {
+            rate_limit.acquire().await;
+        }
+        // three requests 10ms apart
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -14,21 +14,29 @@ pub mod current {
         #[serde(with = "chrono::serde::ts_seconds")]
         pub last_updated_epoch: chrono::DateTime<Utc>,
         pub temp_c: f64,
-        pub temp_f: f64,
         pub wind_kph: f64,
-        pub wind_mph: f64,
         pub wind_dir: String,
         pub humidity: usize,
         pub condition: Condition,
+        pub feelslike_c: Option<f64>,
+        pub pressure_mb: Option<f64>,
+        pub precip_mm: Option<f64>,
+        pub cloud: Option<f64>,
+        pub vis_km: Option<f64>,
+        pub uv: Option<f64>,
+        pub gust_kph: Option<f64>,
     }
 
     #[derive(Deserialize, Debug)]
     pub struct Condition {
         pub text: String,
+        pub code: u32,
     }
+    /// `localtime` is the location's wall clock at `localtime_epoch`
     #[derive(Deserialize, Debug)]
     pub struct Location {
-        pub name: String,
+        pub localtime_epoch: i64,
+        pub localtime: String,
     }
 }
 
@@ -37,24 +45,18 @@ pub mod forecast {
     #[derive(Deserialize, Debug)]
     pub struct Json {
         pub location: Location,
-        pub current: Current,
         pub forecast: Forecast,
-    }
-    #[derive(Deserialize, Debug)]
-    pub struct Current {
-        #[serde(with = "chrono::serde::ts_seconds")]
-        pub last_updated_epoch: chrono::DateTime<Utc>,
-        pub temp_c: f64,
-        pub temp_f: f64,
-        pub wind_kph: f64,
-        pub wind_mph: f64,
-        pub wind_dir: String,
-        pub humidity: usize,
-        pub condition: Condition,
+        /// Comes along with the forecast, it's only used when alerts are asked for too
+        #[serde(default)]
+        pub current: Option<super::current::Current>,
+        /// Only present when asked for with `alerts=yes`
+        #[serde(default)]
+        pub alerts: Option<super::alerts::Alerts>,
     }
     #[derive(Deserialize, Debug)]
     pub struct Condition {
         pub text: String,
+        pub code: u32,
     }
     #[derive(Deserialize, Debug)]
     pub struct Forecast {
@@ -66,22 +68,50 @@ pub mod forecast {
         #[serde(with = "chrono::serde::ts_seconds")]
         pub date_epoch: chrono::DateTime<Utc>,
         pub day: Day,
+        #[serde(default)]
+        pub hour: Vec<Hour>,
     }
     #[derive(Deserialize, Debug)]
     pub struct Location {
-        pub name: String,
+        pub localtime_epoch: i64,
+        pub localtime: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Hour {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        pub time_epoch: chrono::DateTime<Utc>,
+        pub temp_c: f64,
+        pub condition: Condition,
+        pub wind_kph: Option<f64>,
+        pub wind_dir: Option<String>,
+        pub pressure_mb: Option<f64>,
+        pub precip_mm: Option<f64>,
+        pub humidity: Option<f64>,
+        pub cloud: Option<f64>,
+        pub feelslike_c: Option<f64>,
+        pub dewpoint_c: Option<f64>,
+        pub chance_of_rain: Option<f64>,
+        pub chance_of_snow: Option<f64>,
+        pub vis_km: Option<f64>,
+        pub gust_kph: Option<f64>,
+        pub uv: Option<f64>,
     }
 
     #[derive(Deserialize, Debug)]
     pub struct Day {
-        pub maxtemp_f: f64,
-        pub mintemp_f: f64,
         pub maxtemp_c: f64,
         pub mintemp_c: f64,
-        pub maxwind_mph: f64,
         pub maxwind_kph: f64,
         pub avghumidity: f64,
         pub condition: Condition,
+        pub avgtemp_c: Option<f64>,
+        pub totalprecip_mm: Option<f64>,
+        pub totalsnow_cm: Option<f64>,
+        pub avgvis_km: Option<f64>,
+        pub daily_chance_of_rain: Option<f64>,
+        pub daily_chance_of_snow: Option<f64>,
+        pub uv: Option<f64>,
     }
 }
 
@@ -104,23 +134,174 @@ pub mod history {
     pub struct Day {
         pub mintemp_c: f64,
         pub maxtemp_c: f64,
-        pub mintemp_f: f64,
-        pub maxtemp_f: f64,
         pub avghumidity: f64,
         pub condition: Condition,
+        pub avgtemp_c: Option<f64>,
+        pub maxwind_kph: Option<f64>,
+        pub totalprecip_mm: Option<f64>,
+        pub avgvis_km: Option<f64>,
+        pub uv: Option<f64>,
     }
     #[derive(Deserialize, Debug)]
     pub struct Condition {
         pub text: String,
+        pub code: u32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Json {
+        pub location: Location,
+        pub forecast: Forecast,
+    }
+
+    /// `localtime` is the location's wall clock at `localtime_epoch`
+    #[derive(Deserialize, Debug)]
+    pub struct Location {
+        pub localtime_epoch: i64,
+        pub localtime: String,
+    }
+}
+
+pub mod alerts {
+    use super::*;
+
+    /// Part of a forecast asked for with `alerts=yes`
+    #[derive(Deserialize, Debug)]
+    pub struct Alerts {
+        pub alert: Vec<Alert>,
+    }
+
+    /// Timestamps are RFC 3339 strings, which might as well be empty
+    #[derive(Deserialize, Debug)]
+    pub struct Alert {
+        pub severity: String,
+        pub areas: String,
+        pub event: String,
+        pub effective: String,
+        pub expires: String,
+        pub desc: String,
+    }
+}
+
+pub mod air_quality {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    pub struct Json {
+        pub current: Current,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Current {
+        pub air_quality: AirQuality,
+    }
+
+    /// Concentrations are in µg/m³
+    #[derive(Deserialize, Debug)]
+    pub struct AirQuality {
+        pub no2: f64,
+        pub o3: f64,
+        pub pm2_5: f64,
+        pub pm10: f64,
+        #[serde(rename = "us-epa-index")]
+        pub us_epa_index: u8,
+        #[serde(rename = "gb-defra-index")]
+        pub gb_defra_index: u8,
     }
+}
+
+pub mod astronomy {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    pub struct Json {
+        pub astronomy: Astronomy,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Astronomy {
+        pub astro: Astro,
+    }
+
+    /// Times are local, e.g. `06:21 AM`, or a `No moonrise` kind of note
+    #[derive(Deserialize, Debug)]
+    pub struct Astro {
+        pub sunrise: String,
+        pub sunset: String,
+        pub moonrise: String,
+        pub moonset: String,
+        pub moon_phase: String,
+        pub moon_illumination: Illumination,
+    }
+
+    /// Illumination used to be sent as a string and is a number in newer responses
+    #[derive(Deserialize, Debug)]
+    #[serde(untagged)]
+    pub enum Illumination {
+        Number(f64),
+        Text(String),
+    }
+}
+
+pub mod marine {
+    use super::*;
 
     #[derive(Deserialize, Debug)]
     pub struct Json {
         pub location: Location,
         pub forecast: Forecast,
     }
+
+    /// `localtime` is the location's wall clock at `localtime_epoch`
     #[derive(Deserialize, Debug)]
     pub struct Location {
-        pub name: String,
+        pub localtime_epoch: i64,
+        pub localtime: String,
     }
+    #[derive(Deserialize, Debug)]
+    pub struct Forecast {
+        pub forecastday: Vec<ForecastDay>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct ForecastDay {
+        pub day: Day,
+        pub hour: Vec<Hour>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Day {
+        #[serde(default)]
+        pub tides: Vec<Tides>,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Tides {
+        pub tide: Vec<Tide>,
+    }
+
+    /// Time is local to the location, height is sent as a string
+    #[derive(Deserialize, Debug)]
+    pub struct Tide {
+        pub tide_time: String,
+        pub tide_height_mt: String,
+        pub tide_type: String,
+    }
+    #[derive(Deserialize, Debug)]
+    pub struct Hour {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        pub time_epoch: chrono::DateTime<Utc>,
+        pub sig_ht_mt: Option<f64>,
+        pub swell_ht_mt: Option<f64>,
+        pub swell_dir_16_point: Option<String>,
+        pub swell_period_secs: Option<f64>,
+        pub water_temp_c: Option<f64>,
+    }
+}
+
+/// Body of a failed request
+#[derive(Deserialize, Debug)]
+pub struct Error {
+    pub error: ErrorDetails,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorDetails {
+    pub code: usize,
+    pub message: String,
 }
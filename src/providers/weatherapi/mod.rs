@@ -1,22 +1,80 @@
 use crate::{
-    command::{DateOffsetRepresentation, WeatherCommand},
+    air_quality::AirQuality,
+    alerts::Alerts,
+    astronomy::Astronomy,
+    capabilities::{Capabilities, LocationFormat},
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    conditions::{Conditions, HourlyForecast, View},
+    i18n::Lang,
+    marine::Marine,
+    providers::RateLimit,
     report::Report,
-    types::Provider,
+    types::{Provider, Unsupported},
 };
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use reqwest::Response;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use futures::{future::try_join_all, stream, StreamExt, TryStreamExt};
+use reqwest::{Response, StatusCode};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 mod api;
 mod report;
 
 #[cfg(test)]
 mod tests;
 
+/// Condition texts are in English unless another language is asked for
+fn language(lang: Lang) -> Vec<(&'static str, &'static str)> {
+    match lang {
+        Lang::En => Vec::new(),
+        lang => vec![("lang", lang.code())],
+    }
+}
+
+/// Error code of a key whose plan doesn't include the requested resource
+const NO_ACCESS_CODE: usize = 2009;
+
+/// How long a location's offset from UTC is relied on, offsets change with daylight saving time
+const OFFSET_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Most history days requested at once, every plan takes one day per history request
+const HISTORY_CONCURRENCY: usize = 8;
+
+/// Forecasts reach 14 days, history reaches as far back as the free plan does, hourly offsets aren't requested
+/// Paid plans reach further back, see `WeatherAPIProvider::with_history_days`
+pub const CAPABILITIES: Capabilities = Capabilities {
+    current: true,
+    hourly_forecast_hours: 0,
+    hourly_history_hours: 0,
+    daily_forecast_days: 14,
+    history_days: 7,
+    hourly: true,
+    alerts: true,
+    air_quality: true,
+    astronomy: true,
+    marine: true,
+    location_formats: &[
+        LocationFormat::Name,
+        LocationFormat::Coordinates,
+        LocationFormat::PostalCode,
+        LocationFormat::Airport,
+        LocationFormat::Ip,
+    ],
+};
+
 /// WeatherAPI REST API adapter
 pub struct WeatherAPIProvider {
     base_url: String,
     api_key: String,
+    history_days: u32,
     client: reqwest::Client,
+    rate_limit: Option<RateLimit>,
+    /// Offsets from UTC of the locations responses have been received for, and when
+    offsets: Mutex<HashMap<String, (FixedOffset, Instant)>>,
 }
 
 impl Default for WeatherAPIProvider {
@@ -25,7 +83,10 @@ impl Default for WeatherAPIProvider {
         Self {
             base_url: "https://api.weatherapi.com".to_owned(),
             api_key: "".to_owned(),
+            history_days: CAPABILITIES.history_days,
             client: reqwest::Client::new(),
+            rate_limit: None,
+            offsets: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -43,6 +104,18 @@ impl WeatherAPIProvider {
         self
     }
 
+    /// Days of history the key's plan covers, e.g. 365 on paid plans
+    pub fn with_history_days(mut self, history_days: u32) -> Self {
+        self.history_days = history_days;
+        self
+    }
+
+    /// Space out the requests, as a plan's or a batch's rate allows
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
     fn url_current(&self) -> String {
         format!("{}/{}", self.base_url, "v1/current.json")
     }
@@ -54,85 +127,278 @@ impl WeatherAPIProvider {
     fn url_forecast(&self) -> String {
         format!("{}/{}", self.base_url, "v1/forecast.json")
     }
+
+    fn url_marine(&self) -> String {
+        format!("{}/{}", self.base_url, "v1/marine.json")
+    }
+
+    fn url_astronomy(&self) -> String {
+        format!("{}/{}", self.base_url, "v1/astronomy.json")
+    }
 }
 
 impl WeatherAPIProvider {
-    async fn request_current(&self, location: &String) -> Result<Response, reqwest::Error> {
+    /// Waits until the rate limit, if there is one, lets a request go
+    async fn throttle(&self) {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire().await;
+        }
+    }
+
+    async fn request_current(
+        &self,
+        location: &String,
+        lang: Lang,
+    ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
         self.client
             .get(self.url_current())
             .query(&[("key", &self.api_key), ("q", location)])
             .query(&[("aqi", "no")])
+            .query(&language(lang))
             .send()
             .await
     }
 
     async fn request_forecast(
         &self,
-        location: &String,
+        location: &str,
         days: isize,
+        lang: Lang,
+        alerts: bool,
     ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
         self.client
             .get(self.url_forecast())
-            .query(&[("key", &self.api_key), ("q", location)])
-            .query(&[("aqi", "no"), ("alerts", "no")])
+            .query(&[("key", self.api_key.as_str()), ("q", location)])
+            .query(&[("aqi", "no"), ("alerts", if alerts { "yes" } else { "no" })])
             .query(&[("days", days)])
+            .query(&language(lang))
+            .send()
+            .await
+    }
+
+    async fn request_air_quality(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_current())
+            .query(&[("key", self.api_key.as_str()), ("q", location)])
+            .query(&[("aqi", "yes")])
+            .send()
+            .await
+    }
+
+    async fn request_astronomy(
+        &self,
+        location: &str,
+        date: NaiveDate,
+    ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_astronomy())
+            .query(&[("key", self.api_key.as_str()), ("q", location)])
+            .query(&[("dt", date.format("%Y-%m-%d").to_string())])
+            .send()
+            .await
+    }
+
+    async fn request_marine(&self, location: &str) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
+        self.client
+            .get(self.url_marine())
+            .query(&[("key", self.api_key.as_str()), ("q", location)])
+            .query(&[("days", "1"), ("tides", "yes")])
             .send()
             .await
     }
 
     async fn request_history(
         &self,
-        location: &String,
-        dt: &String,
+        location: &str,
+        dt: &str,
+        lang: Lang,
     ) -> Result<Response, reqwest::Error> {
+        self.throttle().await;
         self.client
             .get(self.url_history())
-            .query(&[("key", &self.api_key), ("q", location)])
+            .query(&[("key", self.api_key.as_str()), ("q", location)])
             .query(&[("aqi", "no"), ("alerts", "no")])
             .query(&[("dt", dt)])
+            .query(&language(lang))
             .send()
             .await
     }
 }
 
 impl WeatherAPIProvider {
-    async fn branch_current(&self, location: String) -> Result<Report> {
+    /// Keeps the offset of a location's wall clock, as a response tells it, for later queries
+    fn remember_offset(&self, location: &str, localtime_epoch: i64, localtime: &str) {
+        if let Some(offset) = report::location_offset(localtime_epoch, localtime) {
+            self.offsets
+                .lock()
+                .expect("The offsets lock is poisoned")
+                .insert(location.to_owned(), (offset, Instant::now()));
+        }
+    }
+
+    /// Forecast of a number of days, the location's alerts come along when asked for
+    async fn forecast(
+        &self,
+        location: &str,
+        days: isize,
+        lang: Lang,
+        alerts: bool,
+    ) -> Result<api::forecast::Json> {
+        let res = self
+            .request_forecast(location, days, lang, alerts)
+            .await
+            .context("Failed the forecast weather request")?;
+        let parsed = parse_forecast(res)
+            .await
+            .context("Failed to parse the forecast weather response")?;
+        self.remember_offset(
+            location,
+            parsed.location.localtime_epoch,
+            &parsed.location.localtime,
+        );
+        Ok(parsed)
+    }
+
+    /// Current conditions come along with a day of forecast when alerts are asked for, so that one call does
+    async fn branch_current(&self, location: String, view: &View, alerts: bool) -> Result<Report> {
         log::debug!("branch_current");
+        if alerts {
+            let mut parsed = self.forecast(&location, 1, view.lang, true).await?;
+            let alerts = report::take_alerts(&mut parsed);
+            let current = parsed
+                .current
+                .context("The forecast has no current conditions")?;
+            let mut report = report::report_current(current, view);
+            report::add_alerts(&mut report, alerts, view);
+            return Ok(report);
+        }
         let res = self
-            .request_current(&location)
+            .request_current(&location, view.lang)
             .await
             .context("Failed the current weather request")?;
         let parsed = parse_current(res)
             .await
             .context("Failed to parse the current weather response")?;
-        let report = report::report_current(parsed);
+        self.remember_offset(
+            &location,
+            parsed.location.localtime_epoch,
+            &parsed.location.localtime,
+        );
+        let report = report::report_current(parsed.current, view);
         Ok(report)
     }
 
-    async fn branch_forecast(&self, location: &String, days: isize) -> Result<Report> {
+    async fn branch_forecast(
+        &self,
+        location: &str,
+        days: isize,
+        view: &View,
+        alerts: bool,
+    ) -> Result<Report> {
         log::debug!("branch_forecast");
+        let mut parsed = self.forecast(location, days, view.lang, alerts).await?;
+        let alerts = report::take_alerts(&mut parsed);
+        let mut report = report::report_forecast(parsed, view);
+        report::add_alerts(&mut report, alerts, view);
+        Ok(report)
+    }
+
+    async fn branch_history(&self, location: &str, dt: &str, view: &View) -> Result<Report> {
+        log::debug!("branch_history");
+        log::debug!("date: {}", dt);
         let res = self
-            .request_forecast(location, days)
+            .request_history(location, dt, view.lang)
             .await
-            .context("Failed the forecast weather request")?;
-        let parsed = parse_forecast(res)
+            .context("Failed the history weather request")?;
+        let parsed = parse_history(res)
             .await
             .context("Failed to parse the current weather response")?;
-        let report = report::report_forecast(parsed);
+        self.remember_offset(
+            location,
+            parsed.location.localtime_epoch,
+            &parsed.location.localtime,
+        );
+        let report = report::report_history(parsed, view);
         Ok(report)
     }
 
-    async fn branch_history(&self, location: &String, dt: &String) -> Result<Report> {
-        log::debug!("branch_history");
-        log::debug!("date: {}", dt);
+    async fn history_day(
+        &self,
+        location: &str,
+        day: NaiveDate,
+        lang: Lang,
+    ) -> Result<Vec<Conditions>> {
+        log::debug!("history_day: {}", day);
         let res = self
-            .request_history(location, dt)
+            .request_history(location, &day.format("%Y-%m-%d").to_string(), lang)
             .await
             .context("Failed the history weather request")?;
         let parsed = parse_history(res)
             .await
-            .context("Failed to parse the current weather response")?;
-        let report = report::report_history(parsed);
+            .context("Failed to parse the history weather response")?;
+        self.remember_offset(
+            location,
+            parsed.location.localtime_epoch,
+            &parsed.location.localtime,
+        );
+        Ok(report::normalize_history(parsed))
+    }
+
+    /// The forecast always starts today, so the days before the range are skipped
+    async fn forecast_days(
+        &self,
+        location: &str,
+        today: NaiveDate,
+        range: DayRange,
+        lang: Lang,
+        alerts: bool,
+    ) -> Result<(Vec<Conditions>, Option<Alerts>)> {
+        let days = (range.to - today).num_days() + 1;
+        let mut parsed = self.forecast(location, days as isize, lang, alerts).await?;
+        let alerts = report::take_alerts(&mut parsed);
+        let skipped = (range.from - today).num_days() as usize;
+        let days = report::normalize_forecast(parsed)
+            .into_iter()
+            .skip(skipped)
+            .collect();
+        Ok((days, alerts))
+    }
+
+    /// History is requested concurrently with a call per day, the rest of the range is a single forecast
+    /// Alerts are only asked for along with the forecast, a range of past days has none
+    async fn branch_range(
+        &self,
+        location: &str,
+        range: DayRange,
+        view: &View,
+        alerts: bool,
+    ) -> Result<Report> {
+        log::debug!("branch_range: {:?}", range);
+        let today = self.location_now(location).await?.date_naive();
+        let history = try_join_all(
+            range
+                .past_days(today)
+                .into_iter()
+                .map(|day| self.history_day(location, day, view.lang)),
+        );
+        let forecast = async {
+            match range.upcoming(today) {
+                Some(upcoming) => {
+                    self.forecast_days(location, today, upcoming, view.lang, alerts)
+                        .await
+                }
+                None => Ok((Vec::new(), None)),
+            }
+        };
+        let (history, (forecast, alerts)) = futures::try_join!(history, forecast)?;
+        let days = history.into_iter().flatten().chain(forecast).collect();
+        let mut report = report::report_range(days, view);
+        report::add_alerts(&mut report, alerts, view);
         Ok(report)
     }
 }
@@ -149,6 +415,38 @@ async fn parse_forecast(response: Response) -> Result<api::forecast::Json> {
         .await
         .context("Couldn't parse the forecast weather status")
 }
+async fn parse_air_quality(response: Response) -> Result<api::air_quality::Json> {
+    response
+        .json::<api::air_quality::Json>()
+        .await
+        .context("Couldn't parse the air quality")
+}
+async fn parse_astronomy(response: Response) -> Result<api::astronomy::Json> {
+    response
+        .json::<api::astronomy::Json>()
+        .await
+        .context("Couldn't parse the astronomy data")
+}
+/// Marine data isn't available on every plan, which is told apart from other failures
+/// such as an exceeded quota or a disabled key, that are forbidden as well
+async fn parse_marine(response: Response) -> Result<api::marine::Json> {
+    match response.status() {
+        status if !status.is_success() => {
+            let error = response
+                .json::<api::Error>()
+                .await
+                .context("Couldn't parse the marine data error")?;
+            if status == StatusCode::FORBIDDEN && error.error.code == NO_ACCESS_CODE {
+                return Err(Unsupported::new("Marine data").into());
+            }
+            bail!("{}", error.error.message)
+        }
+        _ => response
+            .json::<api::marine::Json>()
+            .await
+            .context("Couldn't parse the marine data"),
+    }
+}
 async fn parse_history(response: Response) -> Result<api::history::Json> {
     response
         .json::<api::history::Json>()
@@ -158,25 +456,129 @@ async fn parse_history(response: Response) -> Result<api::history::Json> {
 
 #[async_trait]
 impl Provider for WeatherAPIProvider {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            history_days: self.history_days,
+            ..CAPABILITIES
+        }
+    }
+
+    /// The offset of a recent response is reused, the current conditions are only asked for without one
+    async fn location_now(&self, location: &str) -> Result<DateTime<FixedOffset>> {
+        let remembered = self
+            .offsets
+            .lock()
+            .expect("The offsets lock is poisoned")
+            .get(location)
+            .filter(|(_, received)| received.elapsed() < OFFSET_TTL)
+            .map(|(offset, _)| *offset);
+        let offset = match remembered {
+            Some(offset) => offset,
+            None => {
+                let res = self
+                    .request_current(&location.to_owned(), Lang::default())
+                    .await
+                    .context("Failed the current weather request")?;
+                let parsed = parse_current(res)
+                    .await
+                    .context("Failed to parse the current weather response")?;
+                self.remember_offset(
+                    location,
+                    parsed.location.localtime_epoch,
+                    &parsed.location.localtime,
+                );
+                report::location_offset(parsed.location.localtime_epoch, &parsed.location.localtime)
+                    .context("Couldn't tell the location's offset from UTC")?
+            }
+        };
+        Ok(Utc::now().with_timezone(&offset))
+    }
+
     async fn run(&self, wc: WeatherCommand) -> Result<Report> {
         log::debug!("{:?}", wc.location);
+        let view = wc.view();
         match wc.date {
-            DateOffsetRepresentation::Now => self.branch_current(wc.location).await,
+            DateOffsetRepresentation::Now => {
+                self.branch_current(wc.location, &view, wc.alerts).await
+            }
             DateOffsetRepresentation::DayOffset(days) => {
                 if days > 0 {
-                    self.branch_forecast(&wc.location, days).await
+                    self.branch_forecast(&wc.location, days, &view, wc.alerts)
+                        .await
                 } else {
-                    let dt = wc
-                        .date
-                        .to_chrono(chrono::Utc::now().into())
-                        .format("%Y-%m-%d")
-                        .to_string();
-                    self.branch_history(&wc.location, &dt).await
+                    let now = self.location_now(&wc.location).await?;
+                    let dt = wc.date.to_chrono(now).format("%Y-%m-%d").to_string();
+                    self.branch_history(&wc.location, &dt, &view).await
                 }
             }
             DateOffsetRepresentation::HourOffset(_h) => {
                 bail!("Hourly offsets are not supported yet")
             }
+            DateOffsetRepresentation::DayRange(range) => {
+                self.branch_range(&wc.location, range, &view, wc.alerts)
+                    .await
+            }
         }
     }
+
+    async fn alerts(&self, location: &str) -> Result<Alerts> {
+        log::debug!("alerts");
+        let mut parsed = self.forecast(location, 1, Lang::default(), true).await?;
+        Ok(report::take_alerts(&mut parsed).unwrap_or_default())
+    }
+
+    async fn air_quality(&self, location: &str) -> Result<AirQuality> {
+        log::debug!("air_quality");
+        let res = self
+            .request_air_quality(location)
+            .await
+            .context("Failed the air quality request")?;
+        let parsed = parse_air_quality(res)
+            .await
+            .context("Failed to parse the air quality response")?;
+        Ok(report::normalize_air_quality(parsed))
+    }
+
+    async fn astronomy(&self, location: &str, date: NaiveDate) -> Result<Astronomy> {
+        log::debug!("astronomy");
+        let res = self
+            .request_astronomy(location, date)
+            .await
+            .context("Failed the astronomy request")?;
+        let parsed = parse_astronomy(res)
+            .await
+            .context("Failed to parse the astronomy response")?;
+        Ok(report::normalize_astronomy(parsed, date))
+    }
+
+    async fn marine(&self, location: &str) -> Result<Marine> {
+        log::debug!("marine");
+        let res = self
+            .request_marine(location)
+            .await
+            .context("Failed the marine data request")?;
+        let parsed = parse_marine(res).await?;
+        self.remember_offset(
+            location,
+            parsed.location.localtime_epoch,
+            &parsed.location.localtime,
+        );
+        Ok(report::normalize_marine(parsed))
+    }
+
+    async fn history(&self, location: &str, range: DayRange) -> Result<Vec<Conditions>> {
+        log::debug!("history: {:?}", range);
+        let days: Vec<Vec<Conditions>> = stream::iter(range.days())
+            .map(|day| self.history_day(location, day, Lang::default()))
+            .buffered(HISTORY_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(days.into_iter().flatten().collect())
+    }
+
+    async fn hourly(&self, location: &str) -> Result<HourlyForecast> {
+        log::debug!("hourly");
+        let parsed = self.forecast(location, 1, Lang::default(), false).await?;
+        Ok(report::normalize_hourly(parsed))
+    }
 }
@@ -1,83 +1,303 @@
 use super::api;
-use crate::report::{Report, ReportSection, SectionRepr};
-
-pub fn report_current(current: api::current::Json) -> Report {
-    let mut r = Report::new("WeatherAPI - current");
-    let mut details = SectionRepr::new();
-    details.push(("Condition".to_owned(), current.current.condition.text));
-    details.push((
-        "Temperature".to_owned(),
-        format!("{}°", current.current.temp_c),
-    ));
+use crate::{
+    air_quality::{self, AirQuality},
+    alerts::{self, Alert, Alerts, Severity},
+    astronomy::Astronomy,
+    conditions::{Conditions, HourlyForecast, Sky, View},
+    marine::{Marine, MarineConditions, Tide, TideKind},
+    report::{Report, ReportSection},
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 
-    details.push((
-        "Humidity".to_owned(),
-        format!("{}%", current.current.humidity),
-    ));
-    details.push(("Wind direction".to_owned(), current.current.wind_dir));
-    details.push((
-        "Wind speed km/h".to_owned(),
-        format!("{}", current.current.wind_kph),
-    ));
-    details.push((
-        "Wind speed ml/h".to_owned(),
-        format!("{}", current.current.wind_mph),
+/// Kind of sky of a condition code, the codes are listed at https://www.weatherapi.com/docs/weather_conditions.json
+pub fn sky(code: u32) -> Option<Sky> {
+    let sky = match code {
+        1000 => Sky::Clear,
+        1003 => Sky::PartlyCloudy,
+        1006 | 1009 => Sky::Cloudy,
+        1030 | 1135 | 1147 => Sky::Fog,
+        1087 | 1273..=1282 => Sky::Storm,
+        1066 | 1069 | 1114 | 1117 | 1204..=1237 | 1249..=1264 => Sky::Snow,
+        1063 | 1072 | 1150..=1201 | 1240..=1246 => Sky::Rain,
+        _ => return None,
+    };
+    Some(sky)
+}
+
+pub fn normalize_current(current: api::current::Current) -> Conditions {
+    Conditions {
+        time: current.last_updated_epoch,
+        sky: sky(current.condition.code),
+        condition: Some(current.condition.text),
+        temp_c: Some(current.temp_c),
+        feels_like_c: current.feelslike_c,
+        humidity: Some(current.humidity as f64),
+        pressure_mb: current.pressure_mb,
+        precip_mm: current.precip_mm,
+        uv: current.uv,
+        visibility_km: current.vis_km,
+        wind_kph: Some(current.wind_kph),
+        gust_kph: current.gust_kph,
+        wind_dir: Some(current.wind_dir),
+        cloud_cover: current.cloud,
+        ..Default::default()
+    }
+}
+
+pub fn report_current(current: api::current::Current, view: &View) -> Report {
+    let mut r = Report::new(format!("WeatherAPI - {}", view.lang.tr("current")));
+    let conditions = normalize_current(current);
+    r.add_section(ReportSection::from_conditions(
+        view.lang.tr("Status").to_owned(),
+        conditions,
+        view,
     ));
-    let rs = ReportSection::new("Status".to_owned(), details);
-    r.add_section(rs);
     r
 }
 
-pub fn report_forecast(forecast: api::forecast::Json) -> Report {
-    let mut r = Report::new("WeatherAPI - forecast");
-    forecast.forecast.forecastday.into_iter().for_each(|day| {
-        let mut day_report = SectionRepr::new();
-        day_report.push(("Condition".to_owned(), day.day.condition.text));
-        day_report.push((
-            "Minimum temp., C".to_owned(),
-            format!("{}°", day.day.mintemp_c),
-        ));
-        day_report.push((
-            "Maximum temp., C".to_owned(),
-            format!("{}°", day.day.maxtemp_c),
-        ));
-        day_report.push((
-            "Minimum temp., F".to_owned(),
-            format!("{}°", day.day.mintemp_f),
-        ));
-        day_report.push((
-            "Maximum temp., F".to_owned(),
-            format!("{}°", day.day.maxtemp_f),
-        ));
-        let rs = ReportSection::new(day.date_epoch.format("%d/%m/%Y").to_string(), day_report);
-        r.add_section(rs);
+/// The larger of the rain and snow chances is the chance of precipitation
+fn precipitation_chance(rain: Option<f64>, snow: Option<f64>) -> Option<f64> {
+    match (rain, snow) {
+        (Some(rain), Some(snow)) => Some(rain.max(snow)),
+        (rain, snow) => rain.or(snow),
+    }
+}
+
+pub fn normalize_forecast(forecast: api::forecast::Json) -> Vec<Conditions> {
+    forecast
+        .forecast
+        .forecastday
+        .into_iter()
+        .map(|day| Conditions {
+            time: day.date_epoch,
+            sky: sky(day.day.condition.code),
+            condition: Some(day.day.condition.text),
+            temp_c: day.day.avgtemp_c,
+            temp_min_c: Some(day.day.mintemp_c),
+            temp_max_c: Some(day.day.maxtemp_c),
+            humidity: Some(day.day.avghumidity),
+            precip_mm: day.day.totalprecip_mm,
+            precip_chance: precipitation_chance(
+                day.day.daily_chance_of_rain,
+                day.day.daily_chance_of_snow,
+            ),
+            uv: day.day.uv,
+            visibility_km: day.day.avgvis_km,
+            wind_kph: Some(day.day.maxwind_kph),
+            snow_cm: day.day.totalsnow_cm,
+            ..Default::default()
+        })
+        .collect()
+}
+
+pub fn normalize_history(history: api::history::Json) -> Vec<Conditions> {
+    history
+        .forecast
+        .forecastday
+        .into_iter()
+        .map(|day| Conditions {
+            time: day.date_epoch,
+            sky: sky(day.day.condition.code),
+            condition: Some(day.day.condition.text),
+            temp_c: day.day.avgtemp_c,
+            temp_min_c: Some(day.day.mintemp_c),
+            temp_max_c: Some(day.day.maxtemp_c),
+            humidity: Some(day.day.avghumidity),
+            precip_mm: day.day.totalprecip_mm,
+            uv: day.day.uv,
+            visibility_km: day.day.avgvis_km,
+            wind_kph: day.day.maxwind_kph,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Hours of the first forecast day, which is the location's current day
+pub fn normalize_hourly(forecast: api::forecast::Json) -> HourlyForecast {
+    let utc_offset = location_offset(
+        forecast.location.localtime_epoch,
+        &forecast.location.localtime,
+    )
+    .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+    let hours = forecast
+        .forecast
+        .forecastday
+        .into_iter()
+        .take(1)
+        .flat_map(|day| day.hour)
+        .map(|hour| Conditions {
+            time: hour.time_epoch,
+            sky: sky(hour.condition.code),
+            condition: Some(hour.condition.text),
+            temp_c: Some(hour.temp_c),
+            feels_like_c: hour.feelslike_c,
+            dew_point_c: hour.dewpoint_c,
+            humidity: hour.humidity,
+            pressure_mb: hour.pressure_mb,
+            precip_mm: hour.precip_mm,
+            precip_chance: precipitation_chance(hour.chance_of_rain, hour.chance_of_snow),
+            uv: hour.uv,
+            visibility_km: hour.vis_km,
+            wind_kph: hour.wind_kph,
+            gust_kph: hour.gust_kph,
+            wind_dir: hour.wind_dir,
+            cloud_cover: hour.cloud,
+            ..Default::default()
+        })
+        .collect();
+    HourlyForecast { utc_offset, hours }
+}
+
+fn add_days(report: &mut Report, days: Vec<Conditions>, view: &View) {
+    days.into_iter().for_each(|day| {
+        let title = day.time.format(view.lang.date_format()).to_string();
+        report.add_section(ReportSection::from_conditions(title, day, view));
     });
+}
+
+pub fn report_forecast(forecast: api::forecast::Json, view: &View) -> Report {
+    let mut r = Report::new(format!("WeatherAPI - {}", view.lang.tr("forecast")));
+    add_days(&mut r, normalize_forecast(forecast), view);
     r
 }
 
-pub fn report_history(history: api::history::Json) -> Report {
-    let mut r = Report::new("WeatherAPI - history");
-    history.forecast.forecastday.into_iter().for_each(|day| {
-        let mut day_report = SectionRepr::new();
-        day_report.push(("Condition".to_owned(), day.day.condition.text));
-        day_report.push((
-            "Minimum temp., C".to_owned(),
-            format!("{}°", day.day.mintemp_c),
-        ));
-        day_report.push((
-            "Maximum temp., C".to_owned(),
-            format!("{}°", day.day.maxtemp_c),
-        ));
-        day_report.push((
-            "Minimum temp., F".to_owned(),
-            format!("{}°", day.day.mintemp_f),
-        ));
-        day_report.push((
-            "Maximum temp., F".to_owned(),
-            format!("{}°", day.day.maxtemp_f),
-        ));
-        let rs = ReportSection::new(day.date_epoch.format("%d/%m/%Y").to_string(), day_report);
-        r.add_section(rs);
-    });
+pub fn report_history(history: api::history::Json, view: &View) -> Report {
+    let mut r = Report::new(format!("WeatherAPI - {}", view.lang.tr("history")));
+    add_days(&mut r, normalize_history(history), view);
+    r
+}
+
+/// Merges history and forecast days into one chronological report
+pub fn report_range(mut days: Vec<Conditions>, view: &View) -> Report {
+    let mut r = Report::new(format!("WeatherAPI - {}", view.lang.tr("range")));
+    days.sort_by_key(|d| d.time);
+    add_days(&mut r, days, view);
     r
 }
+
+fn parse_alert_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+pub fn normalize_alerts(alerts: api::alerts::Alerts, utc_offset: Option<FixedOffset>) -> Alerts {
+    let alerts = alerts
+        .alert
+        .into_iter()
+        .map(|alert| Alert {
+            severity: Severity::from_cap(&alert.severity),
+            event: alert.event,
+            area: Some(alert.areas).filter(|areas| !areas.is_empty()),
+            start: parse_alert_time(&alert.effective),
+            end: parse_alert_time(&alert.expires),
+            description: alert.desc,
+        })
+        .collect();
+    Alerts { alerts, utc_offset }
+}
+
+/// Alerts a forecast was asked for along with, they keep the location's offset
+pub fn take_alerts(forecast: &mut api::forecast::Json) -> Option<Alerts> {
+    let utc_offset = location_offset(
+        forecast.location.localtime_epoch,
+        &forecast.location.localtime,
+    );
+    forecast
+        .alerts
+        .take()
+        .map(|alerts| normalize_alerts(alerts, utc_offset))
+}
+
+/// Appends the alerts a forecast came with, if it was asked for any
+pub fn add_alerts(report: &mut Report, alerts: Option<Alerts>, view: &View) {
+    if let Some(alerts) = alerts {
+        alerts::add_to_report(report, alerts, &view.clock(None));
+    }
+}
+
+pub fn normalize_air_quality(air: api::air_quality::Json) -> AirQuality {
+    let measurements = air.current.air_quality;
+    AirQuality {
+        aqi: None,
+        category: air_quality::us_epa_category(measurements.us_epa_index).map(str::to_owned),
+        us_epa_index: Some(measurements.us_epa_index),
+        gb_defra_index: Some(measurements.gb_defra_index),
+        pm2_5: Some(measurements.pm2_5),
+        pm10: Some(measurements.pm10),
+        o3: Some(measurements.o3),
+        no2: Some(measurements.no2),
+    }
+}
+
+fn parse_astro_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%I:%M %p").ok()
+}
+
+pub fn normalize_astronomy(astronomy: api::astronomy::Json, date: NaiveDate) -> Astronomy {
+    let astro = astronomy.astronomy.astro;
+    let moon_illumination = match astro.moon_illumination {
+        api::astronomy::Illumination::Number(n) => Some(n),
+        api::astronomy::Illumination::Text(t) => t.trim().parse().ok(),
+    };
+    Astronomy {
+        date,
+        sunrise: parse_astro_time(&astro.sunrise),
+        sunset: parse_astro_time(&astro.sunset),
+        moonrise: parse_astro_time(&astro.moonrise),
+        moonset: parse_astro_time(&astro.moonset),
+        moon_phase: Some(astro.moon_phase),
+        moon_illumination,
+    }
+}
+
+/// Offset of the location from UTC, derived from its wall clock time at a given moment
+pub fn location_offset(localtime_epoch: i64, localtime: &str) -> Option<FixedOffset> {
+    let local = NaiveDateTime::parse_from_str(localtime, "%Y-%m-%d %H:%M").ok()?;
+    let utc = Utc.timestamp_opt(localtime_epoch, 0).single()?.naive_utc();
+    // the wall clock is only given with minute precision
+    let seconds = (local - utc).num_seconds() as f64 / 900.0;
+    FixedOffset::east_opt(seconds.round() as i32 * 900)
+}
+
+pub fn normalize_marine(marine: api::marine::Json) -> Marine {
+    let offset = location_offset(marine.location.localtime_epoch, &marine.location.localtime)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+    let mut normalized = Marine {
+        utc_offset: Some(offset),
+        ..Default::default()
+    };
+    marine.forecast.forecastday.into_iter().for_each(|day| {
+        day.day
+            .tides
+            .into_iter()
+            .flat_map(|tides| tides.tide)
+            .for_each(|tide| {
+                let time = NaiveDateTime::parse_from_str(&tide.tide_time, "%Y-%m-%d %H:%M")
+                    .ok()
+                    .and_then(|t| offset.from_local_datetime(&t).single());
+                let kind = match tide.tide_type.as_str() {
+                    "HIGH" => TideKind::High,
+                    _ => TideKind::Low,
+                };
+                if let Some(time) = time {
+                    normalized.tides.push(Tide {
+                        time: time.with_timezone(&Utc),
+                        kind,
+                        height_m: tide.tide_height_mt.trim().parse().ok(),
+                    });
+                }
+            });
+        day.hour.into_iter().for_each(|hour| {
+            normalized.conditions.push(MarineConditions {
+                time: hour.time_epoch,
+                wave_height_m: hour.sig_ht_mt,
+                swell_height_m: hour.swell_ht_mt,
+                swell_direction: hour.swell_dir_16_point,
+                swell_period_s: hour.swell_period_secs,
+                water_temp_c: hour.water_temp_c,
+            })
+        });
+    });
+    normalized
+}
@@ -1,13 +1,20 @@
 use super::{api::*, *};
-use crate::command::WeatherCommand;
+use crate::{
+    alerts::Severity, command::WeatherCommand, conditions::Sky, marine::TideKind,
+    types::Unsupported,
+};
 use wiremock::{
     matchers::{method, path, query_param},
     Mock, MockServer, ResponseTemplate,
 };
 
-const CURRENT_MOCK: &'static str = include_str!("./fixtures/current.json");
-const FORECAST_MOCK: &'static str = include_str!("./fixtures/forecast.json");
-const HISTORY_MOCK: &'static str = include_str!("./fixtures/history.json");
+const CURRENT_MOCK: &str = include_str!("./fixtures/current.json");
+const FORECAST_MOCK: &str = include_str!("./fixtures/forecast.json");
+const HISTORY_MOCK: &str = include_str!("./fixtures/history.json");
+const ALERTS_MOCK: &str = include_str!("./fixtures/alerts.json");
+const AIR_QUALITY_MOCK: &str = include_str!("./fixtures/air_quality.json");
+const ASTRONOMY_MOCK: &str = include_str!("./fixtures/astronomy.json");
+const MARINE_MOCK: &str = include_str!("./fixtures/marine.json");
 
 fn make_weatherapi<S: Into<String>>(base_url: S, api_key: S) -> WeatherAPIProvider {
     WeatherAPIProvider::default()
@@ -30,14 +37,11 @@ async fn test_current_request() {
         );
 
     mock_server.register(search_mock).await;
-    let weatherapi = make_weatherapi(
-        format!("http://{}", mock_server.address().to_string()),
-        "22222".into(),
-    );
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
 
     let wc = WeatherCommand::new("Zaporizhzhia", DateOffsetRepresentation::Now);
 
-    let req_loc_search_res = weatherapi.request_current(&wc.location).await;
+    let req_loc_search_res = weatherapi.request_current(&wc.location, wc.lang).await;
     let req_loc_search = req_loc_search_res.expect("Location search request completely failed");
 
     let _demarshaled_response = parse_current(req_loc_search)
@@ -45,11 +49,37 @@ async fn test_current_request() {
         .expect("Couldn't parse the response at all");
 }
 
+#[tokio::test]
+async fn test_localized_report() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/current.json"))
+        .and(query_param("lang", "de"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(CURRENT_MOCK),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let wc = WeatherCommand::new("Zaporizhzhia", DateOffsetRepresentation::Now)
+        .with_units(Some(crate::types::Units::Metric))
+        .with_lang(Lang::De);
+    let report = weatherapi.run(wc).await.expect("Localized request failed");
+    assert_eq!(report.title(), "WeatherAPI - aktuell");
+    assert_eq!(report.sections()[0].title(), "Aktuell");
+    assert!(report.sections()[0]
+        .contents()
+        .contains(&("Temperatur, C".to_owned(), "7.5°".to_owned())));
+}
+
 #[test]
 fn test_parse_current() {
-    let current =
-        serde_json::from_str::<current::Json>(CURRENT_MOCK.into()).expect("Couldn't parse");
-    assert_eq!(current.location.name, "Zaporizhzhya");
+    let current = serde_json::from_str::<current::Json>(CURRENT_MOCK).expect("Couldn't parse");
+    assert_eq!(current.location.localtime, "2023-03-31 21:03");
     assert_eq!(current.current.wind_kph, 23.4);
     assert_eq!(current.current.temp_c, 7.5);
     assert_eq!(current.current.condition.text, "Overcast".to_owned());
@@ -57,19 +87,379 @@ fn test_parse_current() {
 
 #[test]
 fn test_parse_forecast() {
-    let forecast =
-        serde_json::from_str::<forecast::Json>(FORECAST_MOCK.into()).expect("Couldn't parse");
-    assert_eq!(forecast.location.name, "Zaporizhzhya");
+    let forecast = serde_json::from_str::<forecast::Json>(FORECAST_MOCK).expect("Couldn't parse");
+    assert_eq!(forecast.location.localtime, "2023-03-31 21:03");
     assert_eq!(forecast.forecast.forecastday[0].day.maxwind_kph, 27.4);
-    assert_eq!(forecast.forecast.forecastday[0].day.maxwind_mph, 17.0);
     assert_eq!(forecast.forecast.forecastday[0].day.avghumidity, 50.0);
 }
 
+#[test]
+fn test_normalize_current_and_forecast() {
+    let current = serde_json::from_str::<current::Json>(CURRENT_MOCK).expect("Couldn't parse");
+    let current = report::normalize_current(current.current);
+    assert_eq!(current.pressure_mb, Some(1010.0));
+    assert_eq!(current.gust_kph, Some(34.2));
+    assert_eq!(current.feels_like_c, Some(3.9));
+    assert_eq!(current.sky, Some(Sky::Cloudy));
+    assert_eq!(report::sky(1195), Some(Sky::Rain));
+    assert_eq!(report::sky(1276), Some(Sky::Storm));
+    assert_eq!(report::sky(1213), Some(Sky::Snow));
+
+    let forecast = serde_json::from_str::<forecast::Json>(FORECAST_MOCK).expect("Couldn't parse");
+    let days = report::normalize_forecast(forecast);
+    assert_eq!(days[0].temp_c, Some(6.4));
+    assert_eq!(days[0].temp_min_c, Some(-0.3));
+    assert_eq!(days[0].precip_chance, Some(0.0));
+    assert_eq!(days[0].uv, Some(3.0));
+}
+
+#[test]
+fn test_normalize_hourly() {
+    let forecast = serde_json::from_str::<forecast::Json>(FORECAST_MOCK).expect("Couldn't parse");
+    let hourly = report::normalize_hourly(forecast);
+    assert_eq!(hourly.hours.len(), 24);
+    assert_eq!(hourly.utc_offset.local_minus_utc(), 3 * 3600);
+    assert_eq!(hourly.hours[12].precip_chance, Some(0.0));
+    assert_eq!(hourly.hours[12].dew_point_c, Some(-5.0));
+}
+
+#[tokio::test]
+async fn test_day_range() {
+    let mock_server = MockServer::start().await;
+
+    let history_mock = Mock::given(method("GET"))
+        .and(path("/v1/history.json"))
+        .and(query_param("q", "Zaporizhzhia"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(HISTORY_MOCK),
+        )
+        .expect(2);
+    let forecast_mock = Mock::given(method("GET"))
+        .and(path("/v1/forecast.json"))
+        .and(query_param("days", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(FORECAST_MOCK),
+        )
+        .expect(1);
+
+    let current_mock = Mock::given(method("GET"))
+        .and(path("/v1/current.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(CURRENT_MOCK),
+        )
+        .expect(1);
+
+    mock_server.register(history_mock).await;
+    mock_server.register(forecast_mock).await;
+    mock_server.register(current_mock).await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    // days are counted on the location's calendar, which is at +03:00
+    let offset = chrono::FixedOffset::east_opt(3 * 3600).expect("Bad offset");
+    let today = chrono::Utc::now().with_timezone(&offset).date_naive();
+    let range = DayRange::new(
+        today - chrono::Duration::days(2),
+        today + chrono::Duration::days(1),
+    )
+    .expect("Bad range");
+    let wc = WeatherCommand::new("Zaporizhzhia", DateOffsetRepresentation::DayRange(range));
+    let report = weatherapi
+        .run(wc)
+        .await
+        .expect("Couldn't obtain the range")
+        .to_string();
+    // the history days go first, even though the forecast was requested alongside
+    assert_eq!(report.matches("01/03/2023").count(), 2);
+    let last_history = report.rfind("01/03/2023").expect("No history days");
+    let first_forecast = report.find("31/03/2023").expect("No forecast days");
+    assert!(last_history < first_forecast);
+}
+
+#[tokio::test]
+async fn test_offset_is_reused() {
+    let mock_server = MockServer::start().await;
+
+    let current_mock = Mock::given(method("GET"))
+        .and(path("/v1/current.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(CURRENT_MOCK),
+        )
+        .expect(1);
+    let history_mock = Mock::given(method("GET"))
+        .and(path("/v1/history.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(HISTORY_MOCK),
+        )
+        .expect(2);
+    mock_server.register(current_mock).await;
+    mock_server.register(history_mock).await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    // only the first query has to ask for the location's wall clock
+    for _ in 0..2 {
+        let wc = WeatherCommand::new("Zaporizhzhia", DateOffsetRepresentation::DayOffset(-1));
+        weatherapi
+            .run(wc)
+            .await
+            .expect("Couldn't obtain the history");
+    }
+}
+
+#[tokio::test]
+async fn test_history_days() {
+    let mock_server = MockServer::start().await;
+
+    // `end_dt` needs a paid plan, so every day is a request of its own
+    for dt in ["2023-01-30", "2023-01-31", "2023-02-01"] {
+        let day_mock = Mock::given(method("GET"))
+            .and(path("/v1/history.json"))
+            .and(query_param("dt", dt))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/json")
+                    .set_body_string(HISTORY_MOCK),
+            )
+            .expect(1);
+        mock_server.register(day_mock).await;
+    }
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let day = |m, d| chrono::NaiveDate::from_ymd_opt(2023, m, d).expect("Bad date");
+    let range = DayRange::new(day(1, 30), day(2, 1)).expect("Bad range");
+    let days = weatherapi
+        .history("Zaporizhzhia", range)
+        .await
+        .expect("Couldn't obtain the history");
+    assert_eq!(days.len(), 3);
+}
+
+#[tokio::test]
+async fn test_rate_limit_counts_requests() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/history.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(HISTORY_MOCK),
+        )
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into())
+        .with_rate_limit(RateLimit::per_minute(6000));
+
+    let day = |m, d| chrono::NaiveDate::from_ymd_opt(2023, m, d).expect("Bad date");
+    let range = DayRange::new(day(1, 30), day(2, 1)).expect("Bad range");
+    let started = Instant::now();
+    weatherapi
+        .history("Zaporizhzhia", range)
+        .await
+        .expect("Couldn't obtain the history");
+    // a request per day, 10ms apart, although it's a single query
+    assert!(started.elapsed() >= Duration::from_millis(20));
+}
+
 #[test]
 fn test_parse_history() {
-    let history =
-        serde_json::from_str::<history::Json>(HISTORY_MOCK.into()).expect("Couldn't parse");
-    assert_eq!(history.location.name, "Zaporizhzhya");
+    let history = serde_json::from_str::<history::Json>(HISTORY_MOCK).expect("Couldn't parse");
+    assert_eq!(history.forecast.forecastday[0].day.maxwind_kph, Some(24.5));
+}
+
+#[tokio::test]
+async fn test_alerts() {
+    let mock_server = MockServer::start().await;
+
+    let alerts_mock = Mock::given(method("GET"))
+        .and(path("/v1/forecast.json"))
+        .and(query_param("q", "Miami"))
+        .and(query_param("alerts", "yes"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(ALERTS_MOCK),
+        );
+
+    mock_server.register(alerts_mock).await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let alerts = weatherapi
+        .alerts("Miami")
+        .await
+        .expect("Couldn't obtain the alerts");
+    assert_eq!(alerts.utc_offset, FixedOffset::west_opt(4 * 3600));
+    let alerts = alerts.alerts;
+    assert_eq!(alerts.len(), 3);
+    assert_eq!(alerts[1].event, "Tornado Warning");
+    assert_eq!(alerts[1].severity, Severity::Extreme);
+    assert_eq!(
+        alerts[1].start.map(|t| t.to_rfc3339()),
+        Some("2023-03-31T17:12:00+00:00".to_owned())
+    );
+    assert_eq!(alerts[2].severity, Severity::Unknown);
+    assert_eq!(alerts[2].area, None);
+    assert_eq!(alerts[2].start, None);
+}
+
+#[tokio::test]
+async fn test_alerts_come_with_the_report() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/forecast.json"))
+        .and(query_param("q", "Miami"))
+        .and(query_param("alerts", "yes"))
+        .and(query_param("days", "1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(ALERTS_MOCK),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    // the current conditions come along with the forecast
+    Mock::given(method("GET"))
+        .and(path("/v1/current.json"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let wc = WeatherCommand::new("Miami", DateOffsetRepresentation::Now).with_alerts(true);
+    let report = weatherapi.run(wc).await.expect("Couldn't build a report");
+    let titles: Vec<&str> = report.sections().iter().map(|s| s.title()).collect();
+    assert_eq!(
+        titles,
+        vec![
+            "Status",
+            "Alert - Tornado Warning",
+            "Alert - Rip Current Statement",
+            "Alert - Special Weather Statement"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_air_quality() {
+    let mock_server = MockServer::start().await;
+
+    let air_mock = Mock::given(method("GET"))
+        .and(path("/v1/current.json"))
+        .and(query_param("q", "Zaporizhzhia"))
+        .and(query_param("aqi", "yes"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(AIR_QUALITY_MOCK),
+        );
+
+    mock_server.register(air_mock).await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let air = weatherapi
+        .air_quality("Zaporizhzhia")
+        .await
+        .expect("Couldn't obtain the air quality");
+    assert_eq!(air.us_epa_index, Some(1));
+    assert_eq!(air.category, Some("Good".to_owned()));
+    assert_eq!(air.gb_defra_index, Some(2));
+    assert_eq!(air.pm10, Some(18.9));
+}
+
+#[test]
+fn test_normalize_astronomy() {
+    let astronomy =
+        serde_json::from_str::<astronomy::Json>(ASTRONOMY_MOCK).expect("Couldn't parse");
+    let date = chrono::NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date");
+    let astronomy = report::normalize_astronomy(astronomy, date);
+    assert_eq!(
+        astronomy.sunset.map(|t| t.to_string()),
+        Some("19:07:00".to_owned())
+    );
+    assert_eq!(astronomy.moonset, None);
+    assert_eq!(astronomy.moon_illumination, Some(68.0));
+    assert_eq!(
+        astronomy.day_length().map(|d| d.num_minutes()),
+        Some(12 * 60 + 46)
+    );
+}
+
+#[tokio::test]
+async fn test_marine() {
+    let mock_server = MockServer::start().await;
+
+    let marine_mock = Mock::given(method("GET"))
+        .and(path("/v1/marine.json"))
+        .and(query_param("q", "Berdyansk"))
+        .and(query_param("tides", "yes"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/json")
+                .set_body_string(MARINE_MOCK),
+        );
+    let forbidden_mock = Mock::given(method("GET"))
+        .and(path("/v1/marine.json"))
+        .and(query_param("q", "Kyiv"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(
+            r#"{"error": {"code": 2009, "message": "API key does not have access to the resource."}}"#,
+        ));
+
+    let quota_mock = Mock::given(method("GET"))
+        .and(path("/v1/marine.json"))
+        .and(query_param("q", "Lviv"))
+        .respond_with(ResponseTemplate::new(403).set_body_string(
+            r#"{"error": {"code": 2007, "message": "API key has exceeded calls per month quota."}}"#,
+        ));
+
+    mock_server.register(marine_mock).await;
+    mock_server.register(forbidden_mock).await;
+    mock_server.register(quota_mock).await;
+    let weatherapi = make_weatherapi(format!("http://{}", mock_server.address()), "22222".into());
+
+    let marine = weatherapi
+        .marine("Berdyansk")
+        .await
+        .expect("Couldn't obtain the marine data");
+    assert_eq!(marine.tides.len(), 4);
+    assert_eq!(marine.tides[0].kind, TideKind::High);
+    assert_eq!(marine.tides[0].height_m, Some(0.58));
+    // 02:14 local time is 23:14 UTC of the previous day
+    assert_eq!(
+        marine.tides[0].time.to_rfc3339(),
+        "2023-03-30T23:14:00+00:00"
+    );
+    assert_eq!(marine.conditions.len(), 4);
+    assert_eq!(marine.conditions[0].swell_direction.as_deref(), Some("SSW"));
+
+    let unsupported = weatherapi
+        .marine("Kyiv")
+        .await
+        .expect_err("Marine data shouldn't be available");
+    assert!(Unsupported::is_unsupported(&unsupported));
+
+    let quota = weatherapi
+        .marine("Lviv")
+        .await
+        .expect_err("The quota is exceeded");
+    assert!(!Unsupported::is_unsupported(&quota));
+    assert!(quota.to_string().contains("quota"));
+}
+
+#[test]
+fn test_location_offset() {
+    let offset = report::location_offset(1680285784, "2023-03-31 21:03").expect("No offset");
+    assert_eq!(offset.local_minus_utc(), 10800);
 }
 
 // not really needed, but I had to check
@@ -0,0 +1,192 @@
+use crate::{
+    conditions::{Conditions, Field, HourlyForecast, View},
+    report::{Report, ReportSection},
+};
+use chrono::NaiveTime;
+
+/// Chance of precipitation in percent from which an hour counts as wet
+pub const CHANCE_THRESHOLD: f64 = 50.0;
+/// Expected precipitation from which an hour counts as wet, regardless of its chance
+pub const AMOUNT_THRESHOLD_MM: f64 = 0.1;
+
+/// Hours of the day on the location's wall clock, `to` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl Window {
+    pub fn new(from: NaiveTime, to: NaiveTime) -> Self {
+        Self { from, to }
+    }
+
+    /// Whether an hour starting at a local time falls into the window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        self.from <= time && time < self.to
+    }
+}
+
+/// Answer to "will it rain", along with the hour that is the most likely to be wet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub rain: bool,
+    pub wet_hours: usize,
+    pub wettest: Option<Conditions>,
+}
+
+fn is_wet(hour: &Conditions) -> bool {
+    hour.precip_chance.unwrap_or_default() >= CHANCE_THRESHOLD
+        || hour.precip_mm.unwrap_or_default() >= AMOUNT_THRESHOLD_MM
+}
+
+/// Looks for wet hours within a window, the wettest hour has the highest chance, then the most precipitation
+pub fn assess(forecast: &HourlyForecast, window: &Window) -> Verdict {
+    let hours: Vec<&Conditions> = forecast
+        .hours
+        .iter()
+        .filter(|h| window.contains(h.time.with_timezone(&forecast.utc_offset).time()))
+        .collect();
+    let wet_hours = hours.iter().filter(|h| is_wet(h)).count();
+    let wettest = hours
+        .into_iter()
+        .max_by(|a, b| {
+            let key = |h: &Conditions| {
+                (
+                    h.precip_chance.unwrap_or_default(),
+                    h.precip_mm.unwrap_or_default(),
+                )
+            };
+            key(a)
+                .partial_cmp(&key(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .cloned();
+    Verdict {
+        rain: wet_hours > 0,
+        wet_hours,
+        wettest,
+    }
+}
+
+/// Appends the verdict and the wettest hour to a report, in the view's units and language
+pub fn add_to_report(
+    report: &mut Report,
+    verdict: Verdict,
+    window: &Window,
+    forecast: &HourlyForecast,
+    view: &View,
+) {
+    let tr = |text| view.lang.tr(text).to_owned();
+    let answer = if verdict.rain {
+        tr("Yes, take an umbrella")
+    } else {
+        tr("No")
+    };
+    let details = vec![
+        (tr("Will it rain"), answer),
+        (tr("Wet hours"), format!("{}", verdict.wet_hours)),
+    ];
+    report.add_section(ReportSection::new(
+        format!(
+            "{} {} {} {}",
+            tr("Rain between"),
+            window.from.format("%H:%M"),
+            tr("and"),
+            window.to.format("%H:%M")
+        ),
+        details,
+    ));
+    if let Some(wettest) = verdict.wettest {
+        let title = format!(
+            "{} - {}",
+            tr("Wettest hour"),
+            wettest
+                .time
+                .with_timezone(&forecast.utc_offset)
+                .format("%H:%M")
+        );
+        let view = View::new(
+            vec![
+                Field::Condition,
+                Field::Precipitation,
+                Field::PrecipitationChance,
+            ],
+            view.units,
+        )
+        .with_lang(view.lang);
+        report.add_section(ReportSection::from_conditions(title, wettest, &view));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Lang;
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    fn forecast(hours: &[(u32, f64, f64)]) -> HourlyForecast {
+        // 2023-03-31 00:00 at +03:00
+        let midnight = Utc.timestamp_opt(1680210000, 0).single().expect("Bad time");
+        HourlyForecast {
+            utc_offset: FixedOffset::east_opt(3 * 3600).expect("Bad offset"),
+            hours: hours
+                .iter()
+                .map(|&(hour, chance, mm)| Conditions {
+                    time: midnight + chrono::Duration::hours(hour as i64),
+                    precip_chance: Some(chance),
+                    precip_mm: Some(mm),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn window(from: u32, to: u32) -> Window {
+        Window::new(
+            NaiveTime::from_hms_opt(from, 0, 0).expect("Bad time"),
+            NaiveTime::from_hms_opt(to, 0, 0).expect("Bad time"),
+        )
+    }
+
+    #[test]
+    fn test_assess_wet_window() {
+        let forecast = forecast(&[
+            (7, 90.0, 2.0),
+            (9, 20.0, 0.0),
+            (14, 60.0, 0.0),
+            (15, 40.0, 0.5),
+        ]);
+        let verdict = assess(&forecast, &window(8, 18));
+        assert!(verdict.rain);
+        assert_eq!(verdict.wet_hours, 2);
+        let wettest = verdict.wettest.expect("No wettest hour");
+        assert_eq!(wettest.precip_chance, Some(60.0));
+    }
+
+    #[test]
+    fn test_assess_dry_window() {
+        let forecast = forecast(&[(7, 90.0, 2.0), (9, 20.0, 0.0), (18, 80.0, 1.0)]);
+        let verdict = assess(&forecast, &window(8, 18));
+        assert!(!verdict.rain);
+        assert_eq!(verdict.wet_hours, 0);
+        assert_eq!(verdict.wettest.and_then(|w| w.precip_chance), Some(20.0));
+    }
+
+    #[test]
+    fn test_lang() {
+        let forecast = forecast(&[(9, 70.0, 1.0)]);
+        let window = window(8, 18);
+        let verdict = assess(&forecast, &window);
+        let mut report = Report::new("Rain");
+        let view = View::new(Vec::new(), None).with_lang(Lang::De);
+        add_to_report(&mut report, verdict, &window, &forecast, &view);
+        let section = &report.sections()[0];
+        assert_eq!(section.title(), "Regen zwischen 08:00 und 18:00");
+        assert!(section.contents().contains(&(
+            "Wird es regnen".to_owned(),
+            "Ja, nimm einen Schirm mit".to_owned()
+        )));
+        assert_eq!(report.sections()[1].title(), "Nasseste Stunde - 09:00");
+    }
+}
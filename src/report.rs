@@ -1,4 +1,8 @@
+use crate::conditions::{Conditions, View};
+use serde::Serialize;
+
 /// Assembled freeform weather report data that implements some rudimentary formatting
+#[derive(Serialize)]
 pub struct Report {
     title: String,
     sections: Vec<ReportSection>,
@@ -8,14 +12,43 @@ pub struct Report {
 pub type SectionRepr = Vec<(String, String)>;
 
 /// Part of the full report with its own title
+/// Sections built from weather conditions keep them around next to their rendered fields
+#[derive(Serialize)]
 pub struct ReportSection {
     title: String,
     contents: SectionRepr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conditions: Option<Conditions>,
 }
 
 impl ReportSection {
     pub fn new(title: String, contents: SectionRepr) -> Self {
-        Self { title, contents }
+        Self {
+            title,
+            contents,
+            conditions: None,
+        }
+    }
+
+    /// Renders the conditions of a period with the fields and units of a view
+    pub fn from_conditions(title: String, conditions: Conditions, view: &View) -> Self {
+        Self {
+            title,
+            contents: view.rows(&conditions),
+            conditions: Some(conditions),
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn contents(&self) -> &SectionRepr {
+        &self.contents
+    }
+
+    pub fn conditions(&self) -> Option<&Conditions> {
+        self.conditions.as_ref()
     }
 }
 
@@ -32,23 +65,37 @@ impl Report {
     pub fn add_section(&mut self, section: ReportSection) {
         self.sections.push(section);
     }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn sections(&self) -> &[ReportSection] {
+        &self.sections
+    }
+}
+
+/// A field as a row of a section, as reports are shown to users
+/// `wrap` surrounds the padded value, so that escape codes, e.g. highlights, don't upset the alignment
+pub fn format_row(label: &str, value: &str, wrap: (&str, &str)) -> String {
+    format!("{:<35} | {}{:<20}{}", label, wrap.0, value, wrap.1)
 }
 
 impl std::fmt::Display for Report {
     //// Formatting of a report as it is intended to be shown to a user
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let _ = writeln!(f, "{}", self.title);
-        self.sections.iter().fold(Ok(()), |result, section| {
-            result.and_then(|_| writeln!(f, "{}", section))
-        })
+        self.sections
+            .iter()
+            .try_for_each(|section| writeln!(f, "{}", section))
     }
 }
 
 impl std::fmt::Display for ReportSection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let _ = writeln!(f, "{}", &self.title);
-        self.contents.iter().fold(Ok(()), |result, (k, v)| {
-            result.and_then(|_| writeln!(f, "{:<35} | {:<20}", k, v))
-        })
+        self.contents
+            .iter()
+            .try_for_each(|(k, v)| writeln!(f, "{}", format_row(k, v, ("", ""))))
     }
 }
@@ -0,0 +1,478 @@
+use crate::{
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    conditions::Field,
+    configuration::Profile,
+    i18n::Lang,
+    providers::{self, Adapters},
+    types::{Provider, Units, Unsupported},
+    util,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::ValueEnum;
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+/// Query parameters the endpoints accept, each endpoint only looks at the ones it needs
+#[derive(Debug, Default, Deserialize)]
+struct Query {
+    location: Option<String>,
+    /// Comma-separated fields, as for `--fields`
+    fields: Option<String>,
+    /// Number of days to forecast
+    days: Option<isize>,
+    /// A past `YYYY-MM-DD` day
+    date: Option<String>,
+    /// First and last days of a range of past days
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// A finished response, cloneable so that coalesced requests can share it
+#[derive(Debug, Clone)]
+struct Reply {
+    status: StatusCode,
+    body: String,
+}
+
+impl Reply {
+    fn error<S: std::fmt::Display>(status: StatusCode, message: S) -> Self {
+        Self {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+
+    fn into_response(self) -> Response<Body> {
+        Response::builder()
+            .status(self.status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(self.body))
+            .expect("A response with valid parts couldn't be built")
+    }
+}
+
+type Pending = Shared<BoxFuture<'static, Reply>>;
+
+/// What makes two requests identical, everything that goes into the reply
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RequestKey {
+    path: String,
+    location: String,
+    date: DateOffsetRepresentation,
+    units: Option<Units>,
+    fields: Vec<Field>,
+    lang: Lang,
+}
+
+impl RequestKey {
+    fn new(path: &str, command: &WeatherCommand) -> Self {
+        Self {
+            path: path.to_string(),
+            location: command.location.clone(),
+            date: command.date.clone(),
+            units: command.units,
+            fields: command.fields.clone(),
+            lang: command.lang,
+        }
+    }
+}
+
+/// Shares the upstream work of identical requests that are in flight at the same time
+#[derive(Default)]
+struct Coalescer {
+    pending: Mutex<HashMap<RequestKey, Pending>>,
+}
+
+impl Coalescer {
+    /// Awaits a request already in flight under the same key, or starts a new one
+    async fn run<F>(&self, key: RequestKey, request: F) -> Reply
+    where
+        F: Future<Output = Reply> + Send + 'static,
+    {
+        let shared = self
+            .pending
+            .lock()
+            .expect("The coalescer lock is poisoned")
+            .entry(key.clone())
+            .or_insert_with(|| request.boxed().shared())
+            .clone();
+        let mut guard = PendingGuard {
+            coalescer: self,
+            key,
+            shared,
+        };
+        (&mut guard.shared).await
+    }
+}
+
+/// Forgets a pending request once it's done or its last waiter has gone away
+struct PendingGuard<'a> {
+    coalescer: &'a Coalescer,
+    key: RequestKey,
+    shared: Pending,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        // a poisoned lock is left alone, panicking in a drop would abort
+        if let Ok(mut pending) = self.coalescer.pending.lock() {
+            // the map holds one handle and this waiter the other, any more are still waiting
+            let unwaited = self.shared.strong_count().is_none_or(|count| count <= 2);
+            if unwaited
+                && pending
+                    .get(&self.key)
+                    .is_some_and(|p| p.ptr_eq(&self.shared))
+            {
+                pending.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// JSON API over the configured providers, so that their credentials stay in one place
+/// Each request goes to the most preferred provider that can serve it
+pub struct Server {
+    adapters: Adapters,
+    profile: Profile,
+    coalescer: Coalescer,
+}
+
+impl Server {
+    pub fn new(adapters: Adapters, profile: Profile) -> Self {
+        Self {
+            adapters,
+            profile,
+            coalescer: Coalescer::default(),
+        }
+    }
+
+    /// Serves requests on a bound listener until the shutdown future resolves
+    pub async fn serve<F>(self, listener: TcpListener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        listener
+            .set_nonblocking(true)
+            .context("Couldn't configure the listener")?;
+        let server = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await.into_response()) }
+                }))
+            }
+        });
+        hyper::Server::from_tcp(listener)
+            .context("Couldn't start the server")?
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .context("The server has failed")
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Reply {
+        if request.method() != Method::GET {
+            return Reply::error(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Only GET requests are served",
+            );
+        }
+        let query: Query = match serde_urlencoded::from_str(request.uri().query().unwrap_or("")) {
+            Ok(query) => query,
+            Err(e) => return Reply::error(StatusCode::BAD_REQUEST, e),
+        };
+        let path = request.uri().path();
+        let (mut command, mut provider) = match self.route(path, &query, Utc::now().fixed_offset())
+        {
+            Ok(routed) => routed,
+            Err(reply) => return reply,
+        };
+        // past days are counted on the location's calendar
+        if path == "/v1/history" {
+            let now = providers::location_now(provider.as_ref(), &command.location).await;
+            (command, provider) = match self.route(path, &query, now) {
+                Ok(routed) => routed,
+                Err(reply) => return reply,
+            };
+        }
+        log::debug!("Serving {:?}", command);
+        self.coalescer
+            .run(RequestKey::new(path, &command), async move {
+                match provider.run(command).await {
+                    Ok(report) => match serde_json::to_string(&report) {
+                        Ok(body) => Reply {
+                            status: StatusCode::OK,
+                            body,
+                        },
+                        Err(e) => Reply::error(StatusCode::INTERNAL_SERVER_ERROR, e),
+                    },
+                    Err(e) if Unsupported::is_unsupported(&e) => {
+                        Reply::error(StatusCode::NOT_IMPLEMENTED, format!("{:#}", e))
+                    }
+                    Err(e) => Reply::error(StatusCode::BAD_GATEWAY, format!("{:#}", e)),
+                }
+            })
+            .await
+    }
+
+    /// Builds the command of an endpoint with days counted from `now` and picks a provider for it
+    fn route(
+        &self,
+        path: &str,
+        query: &Query,
+        now: DateTime<FixedOffset>,
+    ) -> Result<(WeatherCommand, Arc<dyn Provider>), Reply> {
+        let command = match path {
+            "/v1/current" => self.command(query, |_| Ok(DateOffsetRepresentation::Now)),
+            "/v1/forecast" => self.command(query, forecast_date),
+            "/v1/history" => self.command(query, |query| history_date(query, now)),
+            path => {
+                return Err(Reply::error(
+                    StatusCode::NOT_FOUND,
+                    format!("No such endpoint {}", path),
+                ))
+            }
+        };
+        let command =
+            command.map_err(|e| Reply::error(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+        let (_, provider) = self
+            .adapters
+            .route(|c| c.check_command(&command, now.date_naive()))
+            .map_err(|e| Reply::error(StatusCode::NOT_IMPLEMENTED, format!("{:#}", e)))?;
+        Ok((command, provider))
+    }
+
+    /// Builds a command like `get` would, the location falls back to the profile's first one
+    fn command<D>(&self, query: &Query, date: D) -> Result<WeatherCommand>
+    where
+        D: FnOnce(&Query) -> Result<DateOffsetRepresentation>,
+    {
+        let date = date(query)?;
+        let fields = query
+            .fields
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(|f| Field::from_str(f, true).map_err(|e| anyhow::anyhow!(e)))
+            .collect::<Result<Vec<_>>>()
+            .context("Unknown field")?;
+        let location = util::resolve_location(query.location.clone(), &self.profile)?;
+        Ok(WeatherCommand::new(location, date)
+            .with_units(self.profile.units)
+            .with_fields(fields))
+    }
+}
+
+/// A number of days ahead, tomorrow by default, how far is up to the providers
+fn forecast_date(query: &Query) -> Result<DateOffsetRepresentation> {
+    let days = query.days.unwrap_or(1);
+    if days < 1 {
+        bail!("Forecasts start tomorrow, ask for 1 day or more");
+    }
+    Ok(DateOffsetRepresentation::DayOffset(days))
+}
+
+/// A past day, yesterday by default, or a range of past days, counted back from `now`
+fn history_date(query: &Query, now: DateTime<FixedOffset>) -> Result<DateOffsetRepresentation> {
+    let today = now.date_naive();
+    let date = match (&query.from, &query.to) {
+        (None, None) => {
+            let day = match &query.date {
+                Some(date) => util::parse_day_arg(Some(date), now)?,
+                None => today.pred_opt().context("There is no yesterday")?,
+            };
+            if day >= today {
+                bail!("History is only available for past days");
+            }
+            DateOffsetRepresentation::DayOffset((day - today).num_days() as isize)
+        }
+        (from, to) => {
+            let from = util::parse_day_arg(from.as_deref(), now)?;
+            let to = util::parse_day_arg(to.as_deref(), now)?;
+            let range = DayRange::new(from, to).context("The range has to start before it ends")?;
+            if range.to >= today {
+                bail!("History is only available for past days");
+            }
+            DateOffsetRepresentation::DayRange(range)
+        }
+    };
+    Ok(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        providers::{aerisweather::AerisWeatherProvider, weatherapi::WeatherAPIProvider},
+        types::AvailableProviders,
+    };
+    use std::{net::SocketAddr, time::Duration};
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const CURRENT_MOCK: &str = include_str!("./providers/weatherapi/fixtures/current.json");
+    const DAILY_MOCK: &str = include_str!("./providers/aerisweather/fixtures/daily.json");
+
+    /// Starts a server in the background backed by WeatherAPI and then AerisWeather at a mock address
+    fn start(upstream: &MockServer) -> SocketAddr {
+        let weatherapi = WeatherAPIProvider::default()
+            .with_base_url(format!("http://{}", upstream.address()))
+            .with_api_key("22222");
+        let aerisweather = AerisWeatherProvider::default()
+            .with_base_url(format!("http://{}", upstream.address()))
+            .with_credentials("id", "secret");
+        let adapters = Adapters::from(vec![
+            (
+                AvailableProviders::WeatherAPI,
+                Arc::new(weatherapi) as Arc<dyn crate::types::Provider>,
+            ),
+            (AvailableProviders::AerisWeather, Arc::new(aerisweather)),
+        ]);
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind a listener");
+        let address = listener.local_addr().expect("No local address");
+        let server = Server::new(adapters, Profile::default());
+        tokio::spawn(server.serve(listener, futures::future::pending()));
+        address
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_coalesced() {
+        let upstream = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/current.json"))
+            .and(query_param("q", "Zaporizhzhia"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/json")
+                    .set_body_string(CURRENT_MOCK)
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .expect(1)
+            .mount(&upstream)
+            .await;
+        let address = start(&upstream);
+        let url = format!(
+            "http://{}/v1/current?location=Zaporizhzhia&fields=temperature",
+            address
+        );
+        let client = reqwest::Client::new();
+        let responses = futures::future::join_all((0..3).map(|_| client.get(&url).send())).await;
+        for response in responses {
+            let response = response.expect("The request has failed");
+            assert_eq!(response.status(), 200);
+            let report: serde_json::Value = response.json().await.expect("Not a JSON response");
+            let contents = &report["sections"][0]["contents"];
+            assert_eq!(contents[0][0], "Temperature, C");
+            assert!(report["sections"][0]["conditions"]["temp_c"].is_number());
+        }
+    }
+
+    #[test]
+    fn test_cancelled_requests_are_forgotten() {
+        let coalescer = Coalescer::default();
+        let key = RequestKey::new(
+            "/v1/current",
+            &WeatherCommand::new("Kyiv", DateOffsetRepresentation::Now),
+        );
+        let pending = |coalescer: &Coalescer| coalescer.pending.lock().expect("Poisoned").len();
+        let mut leader = coalescer
+            .run(key.clone(), futures::future::pending())
+            .boxed();
+        let mut follower = coalescer.run(key.clone(), async { unreachable!() }).boxed();
+        assert!((&mut leader).now_or_never().is_none());
+        assert!((&mut follower).now_or_never().is_none());
+        // the follower is still waiting on the leader's request
+        drop(leader);
+        assert_eq!(pending(&coalescer), 1);
+        drop(follower);
+        assert_eq!(pending(&coalescer), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests() {
+        let upstream = MockServer::start().await;
+        let address = start(&upstream);
+        let client = reqwest::Client::new();
+        let status = |path: &'static str| {
+            let request = client.get(format!("http://{}{}", address, path)).send();
+            async move { request.await.expect("The request has failed").status() }
+        };
+        // there is no location to fall back to
+        assert_eq!(status("/v1/current").await, 400);
+        assert_eq!(status("/v1/forecast?location=Kyiv&days=0").await, 400);
+        assert_eq!(status("/v1/history?location=Kyiv&date=f1d").await, 400);
+        // typos aren't taken for today
+        let typo = client
+            .get(format!(
+                "http://{}/v1/history?location=Kyiv&from=h3d&to=yesterdy",
+                address
+            ))
+            .send()
+            .await
+            .expect("The request has failed");
+        assert_eq!(typo.status(), 400);
+        assert!(typo
+            .text()
+            .await
+            .expect("No body")
+            .contains("Invalid day 'yesterdy'"));
+        assert_eq!(
+            status("/v1/current?location=Kyiv&fields=altitude").await,
+            400
+        );
+        assert_eq!(status("/v2/current?location=Kyiv").await, 404);
+        // beyond what any provider reaches, rejected without a request
+        assert_eq!(status("/v1/forecast?location=Kyiv&days=30").await, 501);
+        assert_eq!(status("/v1/history?location=Kyiv&date=h400d").await, 501);
+        // upstream doesn't know the location
+        assert_eq!(status("/v1/current?location=Kyiv").await, 502);
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_routed() {
+        let upstream = MockServer::start().await;
+        // WeatherAPI is preferred, only AerisWeather forecasts 15 days ahead
+        Mock::given(method("GET"))
+            .and(path("/conditions/summary/Kyiv"))
+            .and(query_param("for", "+15days"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "application/json")
+                    .set_body_string(DAILY_MOCK),
+            )
+            .expect(1)
+            .mount(&upstream)
+            .await;
+        let address = start(&upstream);
+        let response = reqwest::get(format!(
+            "http://{}/v1/forecast?location=Kyiv&days=15",
+            address
+        ))
+        .await
+        .expect("The request has failed");
+        assert_eq!(response.status(), 200);
+        let report: serde_json::Value = response.json().await.expect("Not a JSON response");
+        assert_eq!(report["title"], "AerisWeather - forecast(daily)");
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    command::DayRange,
+    conditions::{celsius_to_fahrenheit, mm_to_in, Conditions},
+    i18n::Lang,
+    report::{Report, ReportSection, SectionRepr},
+    types::Units,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Daily precipitation from which a day counts as rainy, as it is commonly defined in climatology
+pub const RAINY_DAY_MM: f64 = 1.0;
+
+/// A temperature along with the day it was recorded on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DayValue {
+    pub date: NaiveDate,
+    pub value: f64,
+}
+
+/// Summary of the daily history of a range, days without a value are left out of each statistic
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub range: DayRange,
+    pub days: usize,
+    pub min_temp_c: Option<f64>,
+    pub max_temp_c: Option<f64>,
+    pub mean_temp_c: Option<f64>,
+    pub total_precip_mm: Option<f64>,
+    pub rainy_days: usize,
+    pub hottest_day: Option<DayValue>,
+    pub coldest_day: Option<DayValue>,
+}
+
+/// Mean temperature of a day, the midrange stands in when a provider has no average
+fn day_mean(day: &Conditions) -> Option<f64> {
+    day.temp_c.or(match (day.temp_min_c, day.temp_max_c) {
+        (Some(min), Some(max)) => Some((min + max) / 2.0),
+        _ => None,
+    })
+}
+
+fn extreme<F, C>(days: &[Conditions], value: F, better: C) -> Option<DayValue>
+where
+    F: Fn(&Conditions) -> Option<f64>,
+    C: Fn(f64, f64) -> bool,
+{
+    days.iter()
+        .filter_map(|day| {
+            value(day).map(|value| DayValue {
+                date: day.time.date_naive(),
+                value,
+            })
+        })
+        .fold(None, |found: Option<DayValue>, day| match found {
+            Some(found) if !better(day.value, found.value) => Some(found),
+            _ => Some(day),
+        })
+}
+
+/// Summarizes daily conditions, the hottest and the coldest days are the first ones to reach the extremes
+pub fn summarize(range: DayRange, days: &[Conditions]) -> Summary {
+    let hottest_day = extreme(days, |d| d.temp_max_c.or(d.temp_c), |a, b| a > b);
+    let coldest_day = extreme(days, |d| d.temp_min_c.or(d.temp_c), |a, b| a < b);
+    let means: Vec<f64> = days.iter().filter_map(day_mean).collect();
+    let precipitation: Vec<f64> = days.iter().filter_map(|d| d.precip_mm).collect();
+    Summary {
+        range,
+        days: days.len(),
+        min_temp_c: coldest_day.map(|d| d.value),
+        max_temp_c: hottest_day.map(|d| d.value),
+        mean_temp_c: (!means.is_empty()).then(|| means.iter().sum::<f64>() / means.len() as f64),
+        total_precip_mm: (!precipitation.is_empty()).then(|| precipitation.iter().sum()),
+        rainy_days: precipitation.iter().filter(|p| **p >= RAINY_DAY_MM).count(),
+        hottest_day,
+        coldest_day,
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn format_temp(value: Option<f64>, units: Option<Units>) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return "-".to_owned(),
+    };
+    match units {
+        Some(Units::Imperial) => format!("{}°F", round(celsius_to_fahrenheit(value))),
+        Some(Units::Metric) => format!("{}°C", round(value)),
+        None => format!(
+            "{}°C / {}°F",
+            round(value),
+            round(celsius_to_fahrenheit(value))
+        ),
+    }
+}
+
+fn format_precip(value: Option<f64>, units: Option<Units>) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return "-".to_owned(),
+    };
+    match units {
+        Some(Units::Imperial) => format!("{} in", round(mm_to_in(value))),
+        Some(Units::Metric) => format!("{} mm", round(value)),
+        None => format!("{} mm / {} in", round(value), round(mm_to_in(value))),
+    }
+}
+
+fn format_day(day: Option<DayValue>, units: Option<Units>, lang: Lang) -> String {
+    day.map(|d| {
+        format!(
+            "{} ({})",
+            d.date.format(lang.date_format()),
+            format_temp(Some(d.value), units)
+        )
+    })
+    .unwrap_or_else(|| "-".to_owned())
+}
+
+/// Appends the summary as a single section to a report
+pub fn add_to_report(report: &mut Report, summary: &Summary, units: Option<Units>, lang: Lang) {
+    let tr = |text| lang.tr(text).to_owned();
+    let mut details = SectionRepr::new();
+    details.push((tr("Days"), format!("{}", summary.days)));
+    details.push((tr("Min. temp."), format_temp(summary.min_temp_c, units)));
+    details.push((tr("Max. temp."), format_temp(summary.max_temp_c, units)));
+    details.push((tr("Mean temp."), format_temp(summary.mean_temp_c, units)));
+    details.push((
+        tr("Total precipitation"),
+        format_precip(summary.total_precip_mm, units),
+    ));
+    details.push((
+        format!("{} (>= {} mm)", tr("Rainy days"), RAINY_DAY_MM),
+        format!("{}", summary.rainy_days),
+    ));
+    details.push((
+        tr("Hottest day"),
+        format_day(summary.hottest_day, units, lang),
+    ));
+    details.push((
+        tr("Coldest day"),
+        format_day(summary.coldest_day, units, lang),
+    ));
+    let title = format!(
+        "{} - {} {} {}",
+        tr("Statistics"),
+        summary.range.from.format(lang.date_format()),
+        tr("to"),
+        summary.range.to.format(lang.date_format())
+    );
+    report.add_section(ReportSection::new(title, details));
+}
+
+/// The summary as a CSV header and a single record, values are metric and empty when unknown
+pub fn to_csv(location: &str, summary: &Summary) -> String {
+    let number = |value: Option<f64>| value.map(|v| round(v).to_string()).unwrap_or_default();
+    let date = |day: Option<DayValue>| {
+        day.map(|d| d.date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+    let record = [
+        csv_field(location),
+        summary.range.from.format("%Y-%m-%d").to_string(),
+        summary.range.to.format("%Y-%m-%d").to_string(),
+        summary.days.to_string(),
+        number(summary.min_temp_c),
+        number(summary.max_temp_c),
+        number(summary.mean_temp_c),
+        number(summary.total_precip_mm),
+        summary.rainy_days.to_string(),
+        date(summary.hottest_day),
+        date(summary.coldest_day),
+    ];
+    format!(
+        "location,from,to,days,min_temp_c,max_temp_c,mean_temp_c,total_precip_mm,rainy_days,hottest_day,coldest_day\n{}\n",
+        record.join(",")
+    )
+}
+
+/// Quotes a CSV field when it has to be
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn day(d: u32, min: f64, max: f64, precip: f64) -> Conditions {
+        let date = NaiveDate::from_ymd_opt(2023, 3, d).expect("Bad date");
+        Conditions {
+            time: Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("Bad time")),
+            temp_min_c: Some(min),
+            temp_max_c: Some(max),
+            precip_mm: Some(precip),
+            ..Default::default()
+        }
+    }
+
+    fn range() -> DayRange {
+        DayRange::new(
+            NaiveDate::from_ymd_opt(2023, 3, 1).expect("Bad date"),
+            NaiveDate::from_ymd_opt(2023, 3, 4).expect("Bad date"),
+        )
+        .expect("Bad range")
+    }
+
+    #[test]
+    fn test_summarize() {
+        let days = vec![
+            day(1, -2.0, 4.0, 0.0),
+            day(2, 1.0, 12.0, 3.5),
+            day(3, -4.0, 6.0, 1.0),
+            day(4, 2.0, 12.0, 0.4),
+        ];
+        let summary = summarize(range(), &days);
+        assert_eq!(summary.days, 4);
+        assert_eq!(summary.min_temp_c, Some(-4.0));
+        assert_eq!(summary.max_temp_c, Some(12.0));
+        assert_eq!(summary.mean_temp_c, Some(3.875));
+        assert_eq!(summary.total_precip_mm, Some(4.9));
+        assert_eq!(summary.rainy_days, 2);
+        assert_eq!(
+            summary.hottest_day.map(|d| d.date.to_string()).as_deref(),
+            Some("2023-03-02")
+        );
+        assert_eq!(
+            summary.coldest_day.map(|d| d.date.to_string()).as_deref(),
+            Some("2023-03-03")
+        );
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let summary = summarize(range(), &[day(1, -2.0, 4.0, 0.0)]);
+        let csv = to_csv("Paris, France", &summary);
+        let record = csv.lines().nth(1).expect("No record");
+        assert_eq!(
+            record,
+            "\"Paris, France\",2023-03-01,2023-03-04,1,-2,4,1,0,0,2023-03-01,2023-03-01"
+        );
+    }
+}
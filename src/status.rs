@@ -0,0 +1,338 @@
+use crate::{
+    conditions::{celsius_to_fahrenheit, km_to_mi, mb_to_inhg, mm_to_in, Conditions},
+    configuration::write_config_file,
+    terminal::{self, Icons},
+    types::Units,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Template used for waybar when no `--format` is given
+pub const DEFAULT_TEMPLATE: &str = "{icon} {temp}°{unit}";
+/// Cached results are dropped once they are this old, however long they may be reused for
+const CACHE_RETENTION_HOURS: i64 = 24;
+
+/// A value a template can show, named as in `{temp}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    Icon,
+    Condition,
+    Temp,
+    TempMin,
+    TempMax,
+    FeelsLike,
+    DewPoint,
+    Humidity,
+    Pressure,
+    Precip,
+    PrecipChance,
+    Uv,
+    Visibility,
+    Wind,
+    Gust,
+    WindDir,
+    WindArrow,
+    CloudCover,
+    Snow,
+    Unit,
+    SpeedUnit,
+    Location,
+}
+
+impl Placeholder {
+    pub const ALL: [(&'static str, Placeholder); 22] = [
+        ("icon", Placeholder::Icon),
+        ("condition", Placeholder::Condition),
+        ("temp", Placeholder::Temp),
+        ("temp_min", Placeholder::TempMin),
+        ("temp_max", Placeholder::TempMax),
+        ("feels_like", Placeholder::FeelsLike),
+        ("dew_point", Placeholder::DewPoint),
+        ("humidity", Placeholder::Humidity),
+        ("pressure", Placeholder::Pressure),
+        ("precip", Placeholder::Precip),
+        ("precip_chance", Placeholder::PrecipChance),
+        ("uv", Placeholder::Uv),
+        ("visibility", Placeholder::Visibility),
+        ("wind", Placeholder::Wind),
+        ("gust", Placeholder::Gust),
+        ("wind_dir", Placeholder::WindDir),
+        ("wind_arrow", Placeholder::WindArrow),
+        ("cloud_cover", Placeholder::CloudCover),
+        ("snow", Placeholder::Snow),
+        ("unit", Placeholder::Unit),
+        ("speed_unit", Placeholder::SpeedUnit),
+        ("location", Placeholder::Location),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, placeholder)| *placeholder)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Value(Placeholder),
+}
+
+/// A one-line template such as `{icon} {temp}°{unit} {condition}`, `{{` and `}}` are literal braces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Unmatched {{ in the template, write {{{{ for a brace"),
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(name.trim()).with_context(|| {
+                        format!(
+                            "Unknown placeholder {{{}}}, known ones are {}",
+                            name,
+                            Placeholder::ALL
+                                .iter()
+                                .map(|(n, _)| *n)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Value(placeholder));
+                }
+                '}' => bail!("Unmatched }} in the template, write }}}} for a brace"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Fills the template in, values that are unknown are left empty and the result is kept on one line
+    pub fn render(
+        &self,
+        location: &str,
+        conditions: &Conditions,
+        units: Option<Units>,
+        icons: Icons,
+    ) -> String {
+        let imperial = units == Some(Units::Imperial);
+        let number = |value: Option<f64>, convert: fn(f64) -> f64| {
+            value
+                .map(|v| if imperial { convert(v) } else { v })
+                .map(|v| format!("{}", (v * 10.0).round() / 10.0))
+                .unwrap_or_default()
+        };
+        let same = |v: f64| v;
+        let line: String = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Value(placeholder) => match placeholder {
+                    Placeholder::Icon => conditions
+                        .sky
+                        .and_then(|sky| terminal::icon(sky, icons))
+                        .unwrap_or_default()
+                        .to_owned(),
+                    Placeholder::Condition => conditions.condition.clone().unwrap_or_default(),
+                    Placeholder::Temp => number(conditions.temp_c, celsius_to_fahrenheit),
+                    Placeholder::TempMin => number(conditions.temp_min_c, celsius_to_fahrenheit),
+                    Placeholder::TempMax => number(conditions.temp_max_c, celsius_to_fahrenheit),
+                    Placeholder::FeelsLike => {
+                        number(conditions.feels_like_c, celsius_to_fahrenheit)
+                    }
+                    Placeholder::DewPoint => number(conditions.dew_point_c, celsius_to_fahrenheit),
+                    Placeholder::Humidity => number(conditions.humidity, same),
+                    Placeholder::Pressure => number(conditions.pressure_mb, mb_to_inhg),
+                    Placeholder::Precip => number(conditions.precip_mm, mm_to_in),
+                    Placeholder::PrecipChance => number(conditions.precip_chance, same),
+                    Placeholder::Uv => number(conditions.uv, same),
+                    Placeholder::Visibility => number(conditions.visibility_km, km_to_mi),
+                    Placeholder::Wind => number(conditions.wind_kph, km_to_mi),
+                    Placeholder::Gust => number(conditions.gust_kph, km_to_mi),
+                    Placeholder::WindDir => conditions.wind_dir.clone().unwrap_or_default(),
+                    Placeholder::WindArrow => conditions
+                        .wind_dir
+                        .as_deref()
+                        .and_then(terminal::wind_arrow)
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    Placeholder::CloudCover => number(conditions.cloud_cover, same),
+                    Placeholder::Snow => number(conditions.snow_cm, |cm| mm_to_in(cm * 10.0)),
+                    Placeholder::Unit => if imperial { "F" } else { "C" }.to_owned(),
+                    Placeholder::SpeedUnit => if imperial { "mph" } else { "km/h" }.to_owned(),
+                    Placeholder::Location => location.to_owned(),
+                },
+            })
+            .collect();
+        line.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Output of a waybar custom module with `"return-type": "json"`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Waybar {
+    pub text: String,
+    pub tooltip: String,
+    /// Kind of sky, e.g. `rain` or `clear`, for styling the module
+    pub class: String,
+}
+
+impl Waybar {
+    pub fn new(text: String, tooltip: &str, conditions: &Conditions) -> Self {
+        Self {
+            text,
+            tooltip: tooltip.trim_end().to_owned(),
+            class: conditions
+                .sky
+                .map(|sky| sky.name())
+                .unwrap_or("unknown")
+                .to_owned(),
+        }
+    }
+}
+
+/// A result fetched for a status line, with the full report for tooltips
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub fetched: DateTime<Utc>,
+    pub conditions: Conditions,
+    pub report: String,
+}
+
+/// Recent results, so that status bars refreshing every few seconds don't hit the provider each time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cache {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Cache {
+    /// A missing or unreadable cache is an empty one, it only ever saves requests
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache atomically, dropping results older than the retention period
+    pub fn save(&mut self, path: &Path, now: DateTime<Utc>) -> Result<()> {
+        let cutoff = now - Duration::hours(CACHE_RETENTION_HOURS);
+        self.entries.retain(|_, entry| entry.fetched > cutoff);
+        write_config_file(path, &serde_json::to_string(self)?)
+    }
+
+    /// A result that isn't older than the given age
+    pub fn get(&self, key: &str, now: DateTime<Utc>, max_age: Duration) -> Option<&Entry> {
+        self.entries
+            .get(key)
+            .filter(|entry| now - entry.fetched <= max_age)
+    }
+
+    pub fn insert(&mut self, key: String, entry: Entry) {
+        self.entries.insert(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::Sky;
+    use chrono::TimeZone;
+
+    fn conditions() -> Conditions {
+        Conditions {
+            time: Utc
+                .with_ymd_and_hms(2023, 3, 31, 12, 0, 0)
+                .single()
+                .expect("Bad time"),
+            condition: Some("Light rain".to_owned()),
+            sky: Some(Sky::Rain),
+            temp_c: Some(7.5),
+            wind_kph: Some(23.4),
+            wind_dir: Some("N".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse("{icon} {temp}°{unit} {condition}").expect("Bad template");
+        assert_eq!(
+            template.render("Kyiv", &conditions(), None, Icons::Unicode),
+            "🌧 7.5°C Light rain"
+        );
+        let template = Template::parse("{{{location}}} {wind_arrow}{wind} {speed_unit}")
+            .expect("Bad template");
+        assert_eq!(
+            template.render("Kyiv", &conditions(), Some(Units::Imperial), Icons::None),
+            "{Kyiv} ↓14.5 mph"
+        );
+        // unknown values leave no gaps
+        let template = Template::parse("{uv} {temp}\n{humidity}").expect("Bad template");
+        assert_eq!(
+            template.render("Kyiv", &conditions(), None, Icons::None),
+            "7.5"
+        );
+        assert!(Template::parse("{altitude}").is_err());
+        assert!(Template::parse("temp}").is_err());
+        assert!(Template::parse("{icon} {temp").is_err());
+    }
+
+    #[test]
+    fn test_waybar_and_cache() {
+        let waybar = Waybar::new("🌧 7.5°C".to_owned(), "Report\n\n", &conditions());
+        assert_eq!(
+            serde_json::to_string(&waybar).expect("Couldn't serialize"),
+            r#"{"text":"🌧 7.5°C","tooltip":"Report","class":"rain"}"#
+        );
+
+        let now = Utc
+            .with_ymd_and_hms(2023, 3, 31, 12, 0, 0)
+            .single()
+            .expect("Bad time");
+        let mut cache = Cache::default();
+        cache.insert(
+            "Kyiv".to_owned(),
+            Entry {
+                fetched: now,
+                conditions: conditions(),
+                report: String::new(),
+            },
+        );
+        let later = now + Duration::minutes(6);
+        assert!(cache.get("Kyiv", later, Duration::minutes(10)).is_some());
+        assert!(cache.get("Kyiv", later, Duration::minutes(5)).is_none());
+        assert!(cache.get("Lviv", now, Duration::minutes(10)).is_none());
+    }
+}
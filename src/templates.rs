@@ -0,0 +1,148 @@
+use crate::{
+    conditions::Conditions,
+    report::{Report, ReportSection},
+};
+use anyhow::{Context, Result};
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extension of template files in the templates directory
+pub const EXTENSION: &str = "jinja";
+
+/// Templates that are always available, a file of the same name takes their place
+pub const BUILT_IN: [(&str, &str); 1] = [(
+    "table",
+    "{{ title }}\n\
+     {% for section in sections %}{{ section.title }}\n\
+     {% for row in section.contents %}{{ row[0] | pad(35) }} | {{ row[1] | pad(20) }}\n\
+     {% endfor %}\n\
+     {% endfor %}",
+)];
+
+/// Everything a template can refer to
+/// `sections` are as in the JSON API, `periods` are the conditions of the sections that have them
+#[derive(Serialize)]
+struct Scope<'a> {
+    location: &'a str,
+    title: &'a str,
+    sections: &'a [ReportSection],
+    periods: Vec<&'a Conditions>,
+}
+
+/// Pads a value with spaces on the right, like `{:<width}` does
+fn pad(value: String, width: usize) -> String {
+    format!("{:<width$}", value, width = width)
+}
+
+/// Where a user template of a name is looked up
+pub fn path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, EXTENSION))
+}
+
+/// Source of a template, from the templates directory or the built-in ones
+fn source(dir: &Path, name: &str) -> Result<String> {
+    let file = path(dir, name);
+    if file.exists() {
+        return fs::read_to_string(&file)
+            .with_context(|| format!("Couldn't read the template {}", file.display()));
+    }
+    BUILT_IN
+        .iter()
+        .find(|(built_in, _)| *built_in == name)
+        .map(|(_, source)| source.to_string())
+        .with_context(|| {
+            format!(
+                "There is no template {}, put one at {} or use a built-in one: {}",
+                name,
+                file.display(),
+                BUILT_IN
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Renders a report with a named template
+pub fn render(dir: &Path, name: &str, location: &str, report: &Report) -> Result<String> {
+    let source = source(dir, name)?;
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env.add_filter("pad", pad);
+    env.add_template_owned(name.to_owned(), source)
+        .with_context(|| format!("Couldn't parse the template {}", name))?;
+    let scope = Scope {
+        location,
+        title: report.title(),
+        sections: report.sections(),
+        periods: report
+            .sections()
+            .iter()
+            .filter_map(|s| s.conditions())
+            .collect(),
+    };
+    env.get_template(name)?
+        .render(scope)
+        .with_context(|| format!("Couldn't render the template {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::View;
+    use chrono::{TimeZone, Utc};
+
+    fn report() -> Report {
+        let mut report = Report::new("WeatherAPI - Kyiv");
+        let conditions = Conditions {
+            time: Utc
+                .with_ymd_and_hms(2023, 3, 31, 12, 0, 0)
+                .single()
+                .expect("Bad time"),
+            condition: Some("Overcast".to_owned()),
+            temp_c: Some(7.5),
+            ..Default::default()
+        };
+        report.add_section(ReportSection::from_conditions(
+            "Status".to_owned(),
+            conditions,
+            &View::default(),
+        ));
+        report.add_section(ReportSection::new(
+            "Air quality".to_owned(),
+            vec![("PM2.5".to_owned(), "3.1".to_owned())],
+        ));
+        report
+    }
+
+    #[test]
+    fn test_built_in_table_matches_display() {
+        let dir = std::env::temp_dir().join("weather-no-templates");
+        let report = report();
+        assert_eq!(
+            render(&dir, "table", "Kyiv", &report).expect("Couldn't render"),
+            report.to_string()
+        );
+        assert!(render(&dir, "missing", "Kyiv", &report).is_err());
+    }
+
+    #[test]
+    fn test_user_template() {
+        let dir = std::env::temp_dir().join(format!("weather-templates-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("Couldn't create the directory");
+        fs::write(
+            path(&dir, "brief"),
+            "{{ location }}:{% for p in periods %} {{ p.condition }} {{ p.temp_c }}°C{% endfor %}",
+        )
+        .expect("Couldn't write the template");
+        let rendered = render(&dir, "brief", "Kyiv", &report());
+        fs::remove_dir_all(&dir).expect("Couldn't remove the directory");
+        assert_eq!(rendered.expect("Couldn't render"), "Kyiv: Overcast 7.5°C");
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    conditions::{celsius_to_fahrenheit, round, Conditions, Sky, View},
+    report::Report,
+    types::Units,
+};
+use clap::ValueEnum;
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment, Term};
+use std::fmt::Write;
+
+/// Label columns are never wider than the plain table's one
+const MAX_LABEL_WIDTH: usize = 35;
+/// Levels of a sparkline, from the lowest value to the highest one
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Compass points in the order of their bearings, 22.5° apart
+const COMPASS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+/// Where the wind blows to, for wind coming from N, NE, E and so on
+const ARROWS: [char; 8] = ['↓', '↙', '←', '↖', '↑', '↗', '→', '↘'];
+
+/// Glyphs that stand for weather conditions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Icons {
+    /// Emoji and symbols every terminal font has
+    Unicode,
+    /// Weather icons of a patched Nerd Font
+    Nerd,
+    /// No icons at all
+    None,
+}
+
+/// What the terminal a report is shown on can do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terminal {
+    pub color: bool,
+    pub icons: Icons,
+    pub width: usize,
+}
+
+impl Terminal {
+    /// The terminal stdout is attached to, if it is one; colors follow the NO_COLOR convention
+    pub fn detect(icons: Icons) -> Option<Self> {
+        let term = Term::stdout();
+        if !term.is_term() {
+            return None;
+        }
+        let color = std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+        let width = term
+            .size_checked()
+            .map(|(_, cols)| cols as usize)
+            .unwrap_or(80);
+        Some(Self {
+            color,
+            icons,
+            width,
+        })
+    }
+}
+
+/// Glyph for a kind of sky
+pub fn icon(sky: Sky, icons: Icons) -> Option<&'static str> {
+    let (unicode, nerd) = match sky {
+        Sky::Storm => ("⛈", "\u{e31d}"),
+        Sky::Snow => ("❄", "\u{e31a}"),
+        Sky::Rain => ("🌧", "\u{e318}"),
+        Sky::Fog => ("🌫", "\u{e313}"),
+        Sky::PartlyCloudy => ("⛅", "\u{e302}"),
+        Sky::Cloudy => ("☁", "\u{e312}"),
+        Sky::Clear => ("☀", "\u{e30d}"),
+    };
+    match icons {
+        Icons::Unicode => Some(unicode),
+        Icons::Nerd => Some(nerd),
+        Icons::None => None,
+    }
+}
+
+/// Arrow for the direction the wind blows to, from a compass point it comes from such as `NNE`
+pub fn wind_arrow(wind_dir: &str) -> Option<char> {
+    let point = COMPASS
+        .iter()
+        .position(|p| p.eq_ignore_ascii_case(wind_dir.trim()))?;
+    // every other compass point falls between two arrows, round towards the next one
+    Some(ARROWS[point.div_ceil(2) % ARROWS.len()])
+}
+
+/// A line of bars as high as the values are relative to each other
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * (SPARKS.len() - 1) as f64).round() as usize
+            } else {
+                SPARKS.len() / 2
+            };
+            SPARKS[level.min(SPARKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// 256-color palette entry for a temperature in Celsius, from blue to red
+fn temperature_color(celsius: f64) -> u8 {
+    match celsius {
+        c if c < -10.0 => 21,
+        c if c < 0.0 => 39,
+        c if c < 10.0 => 51,
+        c if c < 20.0 => 48,
+        c if c < 25.0 => 226,
+        c if c < 30.0 => 208,
+        _ => 196,
+    }
+}
+
+/// Temperature in Celsius of a row such as `Temperature, F | 45.5°`
+fn row_celsius(label: &str, value: &str) -> Option<f64> {
+    let number: f64 = value.strip_suffix('°')?.parse().ok()?;
+    if label.ends_with(", C") {
+        Some(number)
+    } else if label.ends_with(", F") {
+        Some((number - 32.0) * 5.0 / 9.0)
+    } else {
+        None
+    }
+}
+
+/// Shortens text wider than a column, ending it with an ellipsis
+fn fit(text: &str, width: usize) -> String {
+    if measure_text_width(text) <= width {
+        text.to_owned()
+    } else {
+        truncate_str(text, width, "…").into_owned()
+    }
+}
+
+/// Renders a report for a terminal: icons next to conditions, temperatures colored by value,
+/// wind arrows and a temperature sparkline when the report spans several periods
+pub fn render(report: &Report, terminal: &Terminal, view: &View) -> String {
+    let label_width = report
+        .sections()
+        .iter()
+        .flat_map(|s| s.contents().iter().map(|(k, _)| measure_text_width(k)))
+        .max()
+        .unwrap_or_default()
+        .min(MAX_LABEL_WIDTH);
+    let value_width = terminal.width.saturating_sub(label_width + 3).max(10);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}",
+        style(report.title()).bold().force_styling(terminal.color)
+    );
+    report.sections().iter().for_each(|section| {
+        let icon = section
+            .conditions()
+            .and_then(|c| c.sky)
+            .and_then(|sky| icon(sky, terminal.icons));
+        let title = match icon {
+            Some(icon) => format!("{} {}", icon, section.title()),
+            None => section.title().to_owned(),
+        };
+        let _ = writeln!(
+            out,
+            "{}",
+            style(title).cyan().bold().force_styling(terminal.color)
+        );
+        let wind_dir = section.conditions().and_then(|c| c.wind_dir.as_deref());
+        section.contents().iter().for_each(|(label, value)| {
+            let mut value = fit(value, value_width);
+            // matched by value, as the label may be translated
+            if Some(value.as_str()) == wind_dir {
+                if let Some(arrow) = wind_arrow(&value) {
+                    value = format!("{} {}", arrow, value);
+                }
+            }
+            let value = match row_celsius(label, &value) {
+                Some(celsius) => style(value)
+                    .color256(temperature_color(celsius))
+                    .force_styling(terminal.color)
+                    .to_string(),
+                None => value,
+            };
+            let label = fit(label, label_width);
+            let _ = writeln!(
+                out,
+                "{} | {}",
+                pad_str(&label, label_width, Alignment::Left, None),
+                value
+            );
+        });
+        out.push('\n');
+    });
+    if let Some(trend) = temperature_trend(report, terminal.width, view) {
+        let _ = writeln!(out, "{}\n", trend);
+    }
+    out
+}
+
+/// Sparkline of the temperatures of every period, for reports with more than one
+/// Temperatures are in Fahrenheit when the view only shows imperial units
+fn temperature_trend(report: &Report, width: usize, view: &View) -> Option<String> {
+    let (unit, convert): (&str, fn(f64) -> f64) = if Units::shows_metric(view.units) {
+        ("C", |celsius| celsius)
+    } else {
+        ("F", celsius_to_fahrenheit)
+    };
+    let temperatures: Vec<f64> = report
+        .sections()
+        .iter()
+        .filter_map(|s| s.conditions().and_then(|c: &Conditions| c.temp_c))
+        .map(|celsius| round(convert(celsius)))
+        .collect();
+    if temperatures.len() < 2 {
+        return None;
+    }
+    let label = format!("{}, {}", view.lang.tr("Temperature trend"), unit);
+    // the range is of the bars that fit, cutting them may narrow it and leave room for more
+    let mut shown = &temperatures[..];
+    loop {
+        let min = shown.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = shown.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let range = format!("{}° … {}°", min, max);
+        let room = width
+            .saturating_sub(measure_text_width(&label) + measure_text_width(&range) + 5)
+            .max(1);
+        if shown.len() <= room {
+            return Some(format!("{} | {} {}", label, sparkline(shown), range));
+        }
+        shown = &shown[..room];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conditions::Field, i18n::Lang, report::ReportSection};
+    use chrono::{TimeZone, Utc};
+
+    fn section(hour: u32, condition: &str, sky: Sky, temp_c: f64) -> ReportSection {
+        let conditions = Conditions {
+            time: Utc
+                .with_ymd_and_hms(2023, 3, 31, hour, 0, 0)
+                .single()
+                .expect("Bad time"),
+            condition: Some(condition.to_owned()),
+            sky: Some(sky),
+            temp_c: Some(temp_c),
+            wind_dir: Some("NE".to_owned()),
+            ..Default::default()
+        };
+        let view = View::new(
+            vec![Field::Condition, Field::Temperature, Field::WindDirection],
+            None,
+        );
+        ReportSection::from_conditions(format!("{}:00", hour), conditions, &view)
+    }
+
+    #[test]
+    fn test_glyphs() {
+        assert_eq!(icon(Sky::Rain, Icons::Unicode), Some("🌧"));
+        assert_eq!(icon(Sky::Storm, Icons::Nerd), Some("\u{e31d}"));
+        assert_eq!(icon(Sky::PartlyCloudy, Icons::Unicode), Some("⛅"));
+        assert_eq!(icon(Sky::Clear, Icons::None), None);
+        assert_eq!(wind_arrow("N"), Some('↓'));
+        assert_eq!(wind_arrow("sw"), Some('↗'));
+        assert_eq!(wind_arrow("NNE"), Some('↙'));
+        assert_eq!(wind_arrow("Variable"), None);
+        assert_eq!(sparkline(&[1.0, 4.5, 8.0]), "▁▅█");
+        assert_eq!(sparkline(&[3.0, 3.0]), "▅▅");
+    }
+
+    #[test]
+    fn test_render_without_color() {
+        let mut report = Report::new("WeatherAPI - Kyiv");
+        report.add_section(section(9, "Sunny", Sky::Clear, 4.0));
+        // icons don't depend on the language of the description
+        report.add_section(section(12, "Pluie légère", Sky::Rain, 9.5));
+        let terminal = Terminal {
+            color: false,
+            icons: Icons::Unicode,
+            width: 80,
+        };
+        let rendered = render(&report, &terminal, &View::default());
+        assert!(!rendered.contains('\x1b'));
+        assert!(rendered.contains("☀ 9:00\n"));
+        assert!(rendered.contains("🌧 12:00\n"));
+        assert!(rendered.contains("Wind direction | ↙ NE\n"));
+        assert!(rendered.contains("Temperature, F | 49.1°\n"));
+        assert!(rendered.contains("Temperature trend, C | ▁█ 4° … 9.5°\n"));
+
+        let colored = render(
+            &report,
+            &Terminal {
+                color: true,
+                ..terminal
+            },
+            &View::default(),
+        );
+        assert!(colored.contains("\x1b[38;5;51m4°\x1b[0m"));
+
+        // the trend follows the units and the language of the report
+        let view = View::new(Vec::new(), Some(Units::Imperial)).with_lang(Lang::De);
+        let rendered = render(&report, &terminal, &view);
+        assert!(rendered.contains("Temperaturverlauf, F | ▁█ 39.2° … 49.1°\n"));
+    }
+
+    #[test]
+    fn test_trend_range_of_shown_bars() {
+        let mut report = Report::new("WeatherAPI - Kyiv");
+        [4.0, 6.0, 9.5, 12.5]
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, temp)| report.add_section(section(i as u32, "Sunny", Sky::Clear, temp)));
+        // room for two bars only, the later hours' temperatures aren't drawn
+        let trend = temperature_trend(&report, 37, &View::default()).expect("No trend");
+        assert_eq!(trend, "Temperature trend, C | ▁█ 4° … 6°");
+    }
+}
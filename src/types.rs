@@ -1,6 +1,18 @@
-use crate::{command::WeatherCommand, report::Report};
+use crate::{
+    air_quality::AirQuality,
+    alerts::Alerts,
+    astronomy::Astronomy,
+    capabilities::Capabilities,
+    command::DayRange,
+    command::WeatherCommand,
+    conditions::{Conditions, HourlyForecast},
+    i18n::Lang,
+    marine::Marine,
+    report::Report,
+};
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Offset, TimeZone, Utc};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
@@ -8,10 +20,74 @@ use serde::{Deserialize, Serialize};
 /// At the moment is functionally useless as we're not relying on a trait to avoid repeating code
 //TODO: Maybe either get rid of AvailableProviders enum and use this trait to register providers at runtime
 #[async_trait]
-pub trait Provider {
+pub trait Provider: Send + Sync {
     async fn run(&self, command: WeatherCommand) -> Result<Report>;
+
+    /// What the adapter can be asked for, requests are checked against it before anything is sent
+    fn capabilities(&self) -> Capabilities;
+
+    /// Current time on the location's wall clock, so that days are counted on its calendar
+    async fn location_now(&self, _location: &str) -> Result<DateTime<FixedOffset>> {
+        Err(Unsupported::new("Local times").into())
+    }
+
+    /// Active weather alerts for a location
+    async fn alerts(&self, _location: &str) -> Result<Alerts> {
+        Err(Unsupported::new("Weather alerts").into())
+    }
+
+    /// Current air quality for a location
+    async fn air_quality(&self, _location: &str) -> Result<AirQuality> {
+        Err(Unsupported::new("Air quality reports").into())
+    }
+
+    /// Sun and moon data for a location on a given day
+    async fn astronomy(&self, _location: &str, _date: NaiveDate) -> Result<Astronomy> {
+        Err(Unsupported::new("Astronomy reports").into())
+    }
+
+    /// Sea state and tides near a location
+    async fn marine(&self, _location: &str) -> Result<Marine> {
+        Err(Unsupported::new("Marine data").into())
+    }
+
+    /// Daily conditions of past days, requested in as many calls as the provider needs
+    async fn history(&self, _location: &str, _range: DayRange) -> Result<Vec<Conditions>> {
+        Err(Unsupported::new("Daily history queries").into())
+    }
+
+    /// Hourly conditions of the location's current day
+    async fn hourly(&self, _location: &str) -> Result<HourlyForecast> {
+        Err(Unsupported::new("Hourly forecasts").into())
+    }
+}
+
+/// Error for a feature a provider doesn't offer
+/// Can be told apart from a failed request by downcasting an `anyhow::Error`
+#[derive(Debug)]
+pub struct Unsupported {
+    pub feature: &'static str,
+}
+
+impl Unsupported {
+    pub fn new(feature: &'static str) -> Self {
+        Self { feature }
+    }
+
+    /// Whether an error has been caused by an unsupported feature
+    pub fn is_unsupported(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<Unsupported>().is_some()
+    }
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} are not supported by this provider", self.feature)
+    }
 }
 
+impl std::error::Error for Unsupported {}
+
 /// Enumeration of supported weather providers
 /// Used by configuration struct and argument parser at runtime
 #[derive(Serialize, Deserialize, Clone, ValueEnum, Debug, PartialEq)]
@@ -32,3 +108,82 @@ impl AvailableProviders {
         }
     }
 }
+
+/// Measurement system preference, when it isn't set both metric and imperial values are shown
+#[derive(Serialize, Deserialize, Clone, Copy, ValueEnum, Debug, PartialEq, Eq, Hash)]
+pub enum Units {
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    /// Whether metric values should be shown for an optional preference
+    pub fn shows_metric(units: Option<Units>) -> bool {
+        units != Some(Units::Imperial)
+    }
+
+    /// Whether imperial values should be shown for an optional preference
+    pub fn shows_imperial(units: Option<Units>) -> bool {
+        units != Some(Units::Metric)
+    }
+}
+
+/// Clock timestamps are displayed on
+#[derive(Serialize, Deserialize, Clone, Copy, ValueEnum, Debug, Default, PartialEq)]
+pub enum TimeZoneChoice {
+    /// The location's wall clock, UTC when a provider doesn't tell the location's offset
+    #[default]
+    Location,
+    /// The user's wall clock
+    Local,
+    Utc,
+}
+
+/// Displays timestamps in a chosen time zone, along with the offset they are shown at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clock {
+    pub location: Option<FixedOffset>,
+    pub tz: TimeZoneChoice,
+    pub lang: Lang,
+}
+
+impl Clock {
+    pub fn new(location: Option<FixedOffset>, tz: TimeZoneChoice) -> Self {
+        Self {
+            location,
+            tz,
+            lang: Lang::default(),
+        }
+    }
+
+    /// Format dates the way a language does
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Offset a moment is displayed at
+    pub fn offset_at(&self, time: DateTime<Utc>) -> FixedOffset {
+        match (self.tz, self.location) {
+            (TimeZoneChoice::Location, Some(offset)) => offset,
+            (TimeZoneChoice::Local, _) => Local.offset_from_utc_datetime(&time.naive_utc()).fix(),
+            _ => Utc.fix(),
+        }
+    }
+
+    /// A moment as a date and a time of day, e.g. `31/03/2023 09:00 PM (UTC+03:00)`
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        let offset = self.offset_at(time);
+        let zone = if offset.local_minus_utc() == 0 {
+            "UTC".to_owned()
+        } else {
+            format!("UTC{}", offset)
+        };
+        format!(
+            "{} ({})",
+            time.with_timezone(&offset)
+                .format(self.lang.datetime_format()),
+            zone
+        )
+    }
+}
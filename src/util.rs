@@ -1,8 +1,10 @@
 use crate::{
     args,
-    command::{DateOffsetRepresentation, WeatherCommand},
+    command::{DateOffsetRepresentation, DayRange, WeatherCommand},
+    configuration::Profile,
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
 
 /// Parses a custom argument format
 // TODO: way too nested for anyone's taste
@@ -31,20 +33,80 @@ pub fn parse_date_arg(date_str: &str) -> Result<DateOffsetRepresentation> {
     Ok(DateOffsetRepresentation::Now)
 }
 
+/// Whether a text is in the offset format of [`parse_date_arg`], e.g. `f1d` or `h12h`
+/// Unlike the parser, which takes anything else for now, it tells typos apart
+pub fn is_offset_arg(date_str: &str) -> bool {
+    let lower_date_str = date_str.to_ascii_lowercase();
+    lower_date_str.len() > 2
+        && matches!(lower_date_str.chars().next(), Some('h' | 'f'))
+        && matches!(lower_date_str.chars().last(), Some('h' | 'd'))
+        && lower_date_str[1..lower_date_str.len() - 1]
+            .parse::<isize>()
+            .is_ok()
+}
+
+/// Parses a day given either as a `YYYY-MM-DD` date or in the offset format of [`parse_date_arg`]
+/// Offsets are counted from `now`, which should be the location's time so that they land on its calendar
+pub fn parse_day_arg(day_str: Option<&str>, now: DateTime<FixedOffset>) -> Result<NaiveDate> {
+    let offset = match day_str {
+        Some(day) => match NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+            Ok(date) => return Ok(date),
+            Err(_) if is_offset_arg(day) => parse_date_arg(day)?,
+            Err(_) => bail!(
+                "Invalid day '{}', expected YYYY-MM-DD or a day offset such as h1d",
+                day
+            ),
+        },
+        None => DateOffsetRepresentation::Now,
+    };
+    Ok(offset.to_chrono(now).date_naive())
+}
+
+/// Parses a wall clock time in the `HH:MM` format
+pub fn parse_time_arg(time_str: &str) -> Result<NaiveTime, chrono::ParseError> {
+    NaiveTime::parse_from_str(time_str, "%H:%M")
+}
+
+/// Falls back to the first saved location of a profile when none was given
+pub fn resolve_location(location: Option<String>, profile: &Profile) -> Result<String> {
+    match location {
+        Some(location) => Ok(location),
+        None => profile
+            .locations
+            .first()
+            .cloned()
+            .context("No location was given and the profile has no saved locations"),
+    }
+}
+
 /// Transforming of cmdline arguments to a relevant query helper structure
-pub fn parse_get_action(ga: args::GetArgs) -> WeatherCommand {
-    let date = if let Some(date) = ga.date {
-        parse_date_arg(&date).unwrap_or_default()
+/// Profile preferences fill in whatever wasn't specified on the command line, days of a range count from `now`
+pub fn parse_get_action(
+    ga: &args::GetArgs,
+    profile: &Profile,
+    now: DateTime<FixedOffset>,
+) -> Result<WeatherCommand> {
+    let date = if ga.from.is_some() || ga.to.is_some() {
+        let from = parse_day_arg(ga.from.as_deref(), now)?;
+        let to = parse_day_arg(ga.to.as_deref(), now)?;
+        let range = DayRange::new(from, to).context("The range has to start before it ends")?;
+        DateOffsetRepresentation::DayRange(range)
+    } else if let Some(date) = &ga.date {
+        parse_date_arg(date).unwrap_or_default()
     } else {
         DateOffsetRepresentation::Now
     };
-    WeatherCommand::new(ga.location, date)
+    let location = resolve_location(ga.location.clone(), profile)?;
+    Ok(WeatherCommand::new(location, date)
+        .with_units(profile.units)
+        .with_fields(ga.fields.clone()))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_date_arg;
+    use super::{parse_date_arg, parse_day_arg};
     use crate::command::DateOffsetRepresentation;
+    use chrono::{FixedOffset, NaiveDate, TimeZone};
 
     #[test]
     fn test_parse_date_arg() {
@@ -69,4 +131,61 @@ mod tests {
             DateOffsetRepresentation::DayOffset(5)
         );
     }
+
+    #[test]
+    fn test_parse_day_arg() {
+        // early on the 1st in Kyiv, while it's still the 31st in UTC
+        let now = FixedOffset::east_opt(3 * 3600)
+            .expect("Bad offset")
+            .with_ymd_and_hms(2023, 4, 1, 1, 30, 0)
+            .single()
+            .expect("Bad time");
+        assert_eq!(
+            parse_day_arg(Some("2023-03-31"), now).expect("Bad luck"),
+            NaiveDate::from_ymd_opt(2023, 3, 31).expect("Bad date")
+        );
+        assert_eq!(
+            parse_day_arg(Some("f1d"), now).expect("Bad luck"),
+            NaiveDate::from_ymd_opt(2023, 4, 2).expect("Bad date")
+        );
+        assert_eq!(
+            parse_day_arg(None, now).expect("Bad luck"),
+            NaiveDate::from_ymd_opt(2023, 4, 1).expect("Bad date")
+        );
+        assert_eq!(
+            parse_day_arg(Some("h1h"), now).expect("Bad luck"),
+            NaiveDate::from_ymd_opt(2023, 4, 1).expect("Bad date")
+        );
+        assert_eq!(
+            parse_day_arg(Some("H0D"), now).expect("Bad luck"),
+            NaiveDate::from_ymd_opt(2023, 4, 1).expect("Bad date")
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_day_arg() {
+        let now = FixedOffset::east_opt(3 * 3600)
+            .expect("Bad offset")
+            .with_ymd_and_hms(2023, 4, 1, 1, 30, 0)
+            .single()
+            .expect("Bad time");
+        [
+            "2023-02-30",
+            "tomorow",
+            "31/03/2023",
+            "f1x",
+            "fd",
+            "hNon8D",
+            "",
+            "ф1d",
+        ]
+        .into_iter()
+        .for_each(|day| {
+            assert!(
+                parse_day_arg(Some(day), now).is_err(),
+                "{:?} was taken for a day",
+                day
+            )
+        });
+    }
 }
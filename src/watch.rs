@@ -0,0 +1,162 @@
+use crate::report::{format_row, Report};
+use std::{collections::HashMap, time::Duration};
+
+/// Clears the terminal and moves the cursor to its top left corner, so a report is redrawn in place
+pub const CLEAR: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[1;7m";
+const RESET: &str = "\x1b[0m";
+
+/// The first retry after an upstream error is made this soon, unless the interval is shorter
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+/// Retries are never delayed longer than this, unless the interval is longer
+const MAX_RETRY: Duration = Duration::from_secs(60 * 60);
+
+/// Parses an interval such as `90s`, `10m` or `1h`, a bare number is in seconds
+pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let interval = interval.trim();
+    let (amount, unit) = match interval.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => interval.split_at(split),
+        None => (interval, "s"),
+    };
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("{} isn't a number of seconds, minutes or hours", interval))?;
+    let seconds = match unit {
+        "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 60 * 60,
+        _ => return Err(format!("Unknown interval unit {}, use s, m or h", unit)),
+    };
+    if seconds == 0 {
+        return Err("The interval has to be longer than zero".to_owned());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Delays between refreshes, they grow exponentially while upstream keeps failing
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    interval: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failures: 0,
+        }
+    }
+
+    /// Delay until the next refresh after a successful one
+    pub fn succeeded(&mut self) -> Duration {
+        self.failures = 0;
+        self.interval
+    }
+
+    /// Delay until the next attempt after a failed one, doubled with every consecutive failure
+    pub fn failed(&mut self) -> Duration {
+        let first = FIRST_RETRY.min(self.interval);
+        let max = MAX_RETRY.max(self.interval);
+        let delay = first
+            .checked_mul(2u32.saturating_pow(self.failures))
+            .unwrap_or(max)
+            .min(max);
+        self.failures = self.failures.saturating_add(1);
+        delay
+    }
+}
+
+/// Whether changed values may be highlighted, following the NO_COLOR convention
+pub fn highlights_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Renders a report like its `Display` does, highlighting values that differ from a previous report
+/// Values are matched by their section title and field name, new fields aren't highlighted
+pub fn render(report: &Report, previous: Option<&Report>, highlight: bool) -> String {
+    let previous: HashMap<(&str, &str), &str> = previous
+        .map(|previous| {
+            previous
+                .sections()
+                .iter()
+                .flat_map(|s| {
+                    s.contents()
+                        .iter()
+                        .map(move |(k, v)| ((s.title(), k.as_str()), v.as_str()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut out = format!("{}\n", report.title());
+    report.sections().iter().for_each(|section| {
+        out.push_str(&format!("{}\n", section.title()));
+        section.contents().iter().for_each(|(k, v)| {
+            let changed = previous
+                .get(&(section.title(), k.as_str()))
+                .is_some_and(|old| *old != v);
+            let wrap = if highlight && changed {
+                (HIGHLIGHT, RESET)
+            } else {
+                ("", "")
+            };
+            out.push_str(&format!("{}\n", format_row(k, v, wrap)));
+        });
+        out.push('\n');
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ReportSection;
+
+    fn report(temperature: &str) -> Report {
+        let mut report = Report::new("WeatherAPI - current");
+        report.add_section(ReportSection::new(
+            "Status".to_owned(),
+            vec![
+                ("Condition".to_owned(), "Overcast".to_owned()),
+                ("Temperature, C".to_owned(), temperature.to_owned()),
+            ],
+        ));
+        report
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_interval("45"), Ok(Duration::from_secs(45)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("10d").is_err());
+        assert!(parse_interval("m").is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(600));
+        assert_eq!(backoff.failed(), Duration::from_secs(30));
+        assert_eq!(backoff.failed(), Duration::from_secs(60));
+        assert_eq!(backoff.failed(), Duration::from_secs(120));
+        (0..10).for_each(|_| {
+            backoff.failed();
+        });
+        assert_eq!(backoff.failed(), MAX_RETRY);
+        assert_eq!(backoff.succeeded(), Duration::from_secs(600));
+        assert_eq!(backoff.failed(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_render_highlights_changes() {
+        let rendered = render(&report("7.5°"), Some(&report("6°")), true);
+        assert!(rendered.contains(&format!("| {}7.5°", HIGHLIGHT)));
+        assert!(!rendered.contains(&format!("| {}Overcast", HIGHLIGHT)));
+        // without a previous report there is nothing to compare against
+        assert_eq!(
+            render(&report("7.5°"), None, true),
+            report("7.5°").to_string()
+        );
+    }
+}